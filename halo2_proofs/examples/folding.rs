@@ -3,11 +3,20 @@ use halo2_proofs::{
     arithmetic::Field,
     circuit::{floor_planner::V1, Layouter, Value},
     plonk::*,
-    poly::{self, commitment::ParamsProver},
+    poly::{
+        self,
+        commitment::ParamsProver,
+        ipa::{
+            commitment::IPACommitmentScheme,
+            multiopen::{ProverIPA, VerifierIPA},
+            strategy::SingleStrategy,
+        },
+        VerificationStrategy,
+    },
     protostar::{
         self,
         accumulator::Accumulator,
-        // decider::create_proof,
+        decider::{create_proof, verify_proof},
     },
     transcript::{
         Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
@@ -284,35 +293,61 @@ fn main() {
 
         // Folding an accumulator with itself should yield the same one,
         // since (1-X)*acc + X*acc = acc
-        let acc = Accumulator::fold(&pk, acc1.clone(), acc1.clone(), &mut transcript);
+        let acc = Accumulator::fold(&pk, acc1.clone(), acc1.clone(), &mut transcript).unwrap();
         assert_eq!(acc, acc1);
         assert!(Accumulator::decide(&params, &pk, &acc));
 
-        let acc = Accumulator::fold(&pk, acc, acc2, &mut transcript);
+        let acc = Accumulator::fold(&pk, acc, acc2, &mut transcript).unwrap();
         assert!(Accumulator::decide(&params, &pk, &acc));
         (transcript.finalize(), acc)
     };
-    // For now we use pk for the verifier
-    let vk = pk.clone();
+    let vk = pk.get_vk();
     let v_acc = {
         let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-        let acc1 =
-            protostar::verifier::VerifierAccumulator::new(&vk, &[], &mut transcript).unwrap();
+        let acc1 = protostar::verifier::VerifierAccumulator::new(vk, &[], &mut transcript).unwrap();
 
-        let acc2 =
-            protostar::verifier::VerifierAccumulator::new(&vk, &[], &mut transcript).unwrap();
+        let acc2 = protostar::verifier::VerifierAccumulator::new(vk, &[], &mut transcript).unwrap();
 
         // Folding an accumulator with itself should yield the same one,
         // since (1-X)*acc + X*acc = acc
         let acc = protostar::verifier::VerifierAccumulator::fold(
-            &vk,
+            vk,
             acc1.clone(),
             acc1.clone(),
             &mut transcript,
-        );
+        )
+        .unwrap();
         assert_eq!(acc, acc1);
 
-        protostar::verifier::VerifierAccumulator::fold(&pk, acc, acc2, &mut transcript)
+        protostar::verifier::VerifierAccumulator::fold(vk, acc, acc2, &mut transcript).unwrap()
     };
     assert_eq!(v_acc, p_acc);
+
+    // Prove and verify the correctness of the final accumulator
+    let decider_proof = {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        create_proof::<IPACommitmentScheme<_>, ProverIPA<_>, _, _, _>(
+            &params,
+            &pk,
+            &p_acc,
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        transcript.finalize()
+    };
+    {
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&decider_proof[..]);
+        let strategy = SingleStrategy::new(&params);
+        assert!(
+            verify_proof::<IPACommitmentScheme<_>, VerifierIPA<_>, _, _, _>(
+                params.verifier_params(),
+                vk,
+                &v_acc,
+                strategy,
+                &mut transcript,
+            )
+            .is_ok()
+        );
+    }
 }
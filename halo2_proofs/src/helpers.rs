@@ -144,3 +144,28 @@ pub(crate) fn polynomial_slice_byte_length<F: PrimeField, B>(slice: &[Polynomial
     let field_len = F::default().to_repr().as_ref().len();
     4 + slice.len() * (4 + field_len * slice.get(0).map(|poly| poly.len()).unwrap_or(0))
 }
+
+/// Reads a vector of elements from buffer, each of which is parsed by `read`
+pub(crate) fn read_vec<R: io::Read, T>(
+    reader: &mut R,
+    mut read: impl FnMut(&mut R) -> io::Result<T>,
+) -> io::Result<Vec<T>> {
+    let mut len = [0u8; 4];
+    reader.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+
+    (0..len).map(|_| read(reader)).collect()
+}
+
+/// Writes a slice of elements to buffer, each of which is serialized by `write`
+pub(crate) fn write_slice<W: io::Write, T>(
+    slice: &[T],
+    writer: &mut W,
+    mut write: impl FnMut(&T, &mut W) -> io::Result<()>,
+) -> io::Result<()> {
+    writer.write_all(&(slice.len() as u32).to_be_bytes())?;
+    for item in slice.iter() {
+        write(item, writer)?;
+    }
+    Ok(())
+}
@@ -37,6 +37,22 @@ pub enum Error {
     /// The instance sets up a copy constraint involving a column that has not been
     /// included in the permutation.
     ColumnNotInPermutation(Column<Any>),
+    /// The Protostar accumulators do not have the shape expected by the key or by each other,
+    /// such as a different number of advice columns, lookup arguments or constraints.
+    AccumulatorShapeMismatch,
+    /// The error term of a Protostar accumulator is inconsistent with its witness,
+    /// such that the folding error polynomial does not interpolate it.
+    InconsistentAccumulatorError,
+    /// The input of a lookup argument is not contained in its table,
+    /// such that no valid Protostar accumulator can be created for the witness.
+    LookupInputNotInTable {
+        /// The name of the lookup argument.
+        name: String,
+        /// The first row at which the input is not contained in the table.
+        row: usize,
+        /// The values of the input expressions at `row`.
+        input: Vec<String>,
+    },
 }
 
 impl From<io::Error> for Error {
@@ -79,6 +95,19 @@ impl fmt::Display for Error {
                 "Column {:?} must be included in the permutation. Help: try applying `meta.enable_equalty` on the column",
                 column
             ),
+            Error::AccumulatorShapeMismatch => {
+                write!(f, "Accumulators do not match the shape of the circuit")
+            }
+            Error::InconsistentAccumulatorError => {
+                write!(f, "Accumulator error is inconsistent with its witness")
+            }
+            Error::LookupInputNotInTable { name, row, input } => write!(
+                f,
+                "Input ({}) of lookup \"{}\" at row {} is not contained in the table",
+                input.join(", "),
+                name,
+                row
+            ),
         }
     }
 }
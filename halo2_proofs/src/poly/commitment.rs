@@ -1,4 +1,5 @@
 use super::{
+    empty_lagrange,
     query::{ProverQuery, VerifierQuery},
     strategy::Guard,
     Coeff, LagrangeCoeff, Polynomial,
@@ -67,6 +68,27 @@ pub trait Params<'params, C: CurveAffine>: Sized + Clone {
         r: Blind<C::ScalarExt>,
     ) -> C::CurveExt;
 
+    /// This commits to a polynomial using only its non-zero evaluations `values`
+    /// at the given `rows` of the $2^k$ size evaluation domain,
+    /// so that the multiexp only involves the corresponding Lagrange bases.
+    /// The commitment will be blinded by the blinding factor `r`.
+    ///
+    /// The default implementation expands the evaluations into a dense polynomial
+    /// and calls `commit_lagrange`.
+    fn commit_lagrange_sparse(
+        &self,
+        rows: &[usize],
+        values: &[C::ScalarExt],
+        r: Blind<C::ScalarExt>,
+    ) -> C::CurveExt {
+        debug_assert_eq!(rows.len(), values.len());
+        let mut poly = empty_lagrange(self.n() as usize);
+        for (row, value) in rows.iter().zip(values.iter()) {
+            poly[*row] = *value;
+        }
+        self.commit_lagrange(&poly, r)
+    }
+
     /// Writes params to a buffer.
     fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()>;
 
@@ -106,6 +106,25 @@ impl<'params, C: CurveAffine> Params<'params, C> for ParamsIPA<C> {
         best_multiexp::<C>(&tmp_scalars, &tmp_bases)
     }
 
+    fn commit_lagrange_sparse(
+        &self,
+        rows: &[usize],
+        values: &[C::Scalar],
+        r: Blind<C::Scalar>,
+    ) -> C::Curve {
+        debug_assert_eq!(rows.len(), values.len());
+        let mut tmp_scalars = Vec::with_capacity(values.len() + 1);
+        let mut tmp_bases = Vec::with_capacity(values.len() + 1);
+
+        tmp_scalars.extend(values.iter());
+        tmp_scalars.push(r.0);
+
+        tmp_bases.extend(rows.iter().map(|row| self.g_lagrange[*row]));
+        tmp_bases.push(self.w);
+
+        best_multiexp::<C>(&tmp_scalars, &tmp_bases)
+    }
+
     /// Writes params to a buffer.
     fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         writer.write_all(&self.k.to_le_bytes())?;
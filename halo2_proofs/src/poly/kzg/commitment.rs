@@ -320,6 +320,17 @@ where
         best_multiexp(&scalars, &bases[0..size])
     }
 
+    fn commit_lagrange_sparse(
+        &self,
+        rows: &[usize],
+        values: &[E::Scalar],
+        _: Blind<E::Scalar>,
+    ) -> E::G1 {
+        debug_assert_eq!(rows.len(), values.len());
+        let bases: Vec<_> = rows.iter().map(|row| self.g_lagrange[*row]).collect();
+        best_multiexp(values, &bases)
+    }
+
     /// Writes params to a buffer.
     fn write<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
         self.write_custom(writer, SerdeFormat::RawBytes)
@@ -2,8 +2,11 @@
 
 pub mod accumulator;
 mod constraints;
+pub mod decider;
+pub mod folding;
 mod keygen;
 pub mod prover;
 pub mod verifier;
 
-pub use keygen::ProvingKey;
+pub use constraints::labeled::ColumnLabel;
+pub use keygen::{LookupChallenges, LookupTable, ProvingKey, VerifyingKey};
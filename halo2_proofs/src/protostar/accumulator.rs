@@ -1,4 +1,9 @@
-use std::iter::{self, zip};
+use std::{
+    array::from_fn,
+    borrow::Cow,
+    io,
+    iter::{self, zip},
+};
 
 use ff::Field;
 use group::Curve;
@@ -6,26 +11,37 @@ use halo2curves::CurveAffine;
 use rand_core::RngCore;
 
 use crate::{
-    arithmetic::{eval_polynomial, parallelize},
+    arithmetic::{
+        eval_polynomial, field_integers, kate_division, lagrange_interpolate, parallelize,
+    },
     dev::metadata::Gate,
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
+    plonk::Error,
     poly::{
         commitment::{Blind, Params},
         LagrangeCoeff, Polynomial,
     },
     transcript::{EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
 };
 
 use self::committed::Committed;
+pub use self::failure::DecideError;
 
 use super::{
-    constraints::{paired::Paired, polynomial::CommittedRef, Data},
+    constraints::{labeled::ColumnLabel, paired::Paired, polynomial::CommittedRef, Data},
     ProvingKey,
 };
 
+pub(super) mod column;
 pub(super) mod committed;
 pub(super) mod compressed_verifier;
+mod failure;
 pub(super) mod gate;
 pub(super) mod lookup;
+pub(super) mod masking;
+pub(super) mod permutation;
+pub(super) mod shuffle;
 
 /// An `Accumulator` contains the entirety of the IOP transcript,
 /// including commitments and verifier challenges.
@@ -33,74 +49,90 @@ pub(super) mod lookup;
 pub struct Accumulator<C: CurveAffine> {
     pub gate: gate::Transcript<C>,
     pub lookups: Vec<lookup::Transcript<C>>,
+    pub shuffles: Vec<shuffle::Transcript<C>>,
+    pub permutation: permutation::Transcript<C>,
     pub beta: compressed_verifier::Transcript<C>,
 
     pub ys: Vec<C::Scalar>,
 
     // Error value for all constraints
     pub error: C::Scalar,
+
+    // Whether a masking accumulator was folded into this one, in which case the linear constraints
+    // may not be satisfied and must be evaluated when folding.
+    pub masked: bool,
 }
 
 impl<C: CurveAffine> Accumulator<C> {
     /// Given two accumulators, run the folding reduction to produce a new accumulator.
     /// If both input accumulators are correct, the output accumulator will be correct w.h.p. .
+    ///
+    /// Returns an error if the accumulators do not match the shape of `pk`,
+    /// if their error terms are inconsistent with their witnesses, or if the transcript cannot be written to.
     pub fn fold<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
         pk: &ProvingKey<C>,
         acc0: Self,
         acc1: Self,
         transcript: &mut T,
-    ) -> Self {
-        // Create a data structure containing pairs of committed columns, from which we can compute the constraints to be evaluated.
-        let paired_data = Paired::<'_, C::Scalar>::new_data(pk, &acc0, &acc1);
+    ) -> Result<Self, Error> {
+        acc0.check_shape(pk)?;
+        acc1.check_shape(pk)?;
 
-        // Get the full constraint polynomial for the gate and lookups
-        let full_constraint = paired_data.full_constraint(pk.cs.gates(), pk.cs.lookups());
+        // Create a data structure containing pairs of committed columns, from which we can compute the constraints to be evaluated.
+        // The folded accumulator is computed from the original ones, so that columns which remain sparse are stored as such.
+        let dense = [&acc0, &acc1].map(|acc| acc.densified());
+        let paired_data = Paired::<'_, C::Scalar>::new_data(pk, [&*dense[0], &*dense[1]]);
+        let masked = acc0.masked || acc1.masked;
+
+        // Get the constraint polynomials for the gate, lookups, shuffles and permutation
+        let constraints = paired_data.all_constraints(
+            pk.cs.gates(),
+            pk.lookup_tables(),
+            pk.cs.shuffles(),
+            pk.cs.permutation(),
+        );
 
-        /*
-        Compute the error polynomial e(X) = ∑ᵢ βᵢ * Gᵢ(X)
-        NOTE: There are sevaral optimizations that can be performed at this point:
-        The i-th constraint Gᵢ is given by Gᵢ = ∑ⱼ yⱼ⋅ sⱼ,ᵢ⋅ Gⱼ,ᵢ, where
-          - yⱼ is a challenge for keeping all constraints linearly independent.
-          - sⱼ,ᵢ is a selector for the j-th constraint, which is 1 if the j-th constraint Gⱼ,ᵢ is active at row i,
-            and 0 otherwise.
-          - Gⱼ,ᵢ is the the j-th constraint, resulting from partially evaluating an expression Gⱼ in the the fixed
-            columns of row i.
-
-        - For each constraint Gⱼ, we can compute the polynomial eⱼ(X) = ∑ᵢ βᵢ⋅sⱼ,ᵢ⋅Gⱼ,ᵢ(X) indpendently of the other
-          constraints, and sum them at the end to obtain e(X) = ∑ⱼ yⱼ(X)⋅eⱼ(X).
-        - By checking the selectors, we can skip the evaluation of Gⱼ,ᵢ if it is not active at row i.
-        - The variables of the expression Gⱼ only need to be interpolated upto deg(Gⱼ)+1 rather than deg(e),
-          saving some unnecessary evaluations of Gⱼ.
-        - If a constraint G is linear (i.e. Gᵢ = L₀⋅(wᵢ−1) for checking that w₀ == 1, where L₀ is a fixed column)
-          then the error polynomial for this expression will always be 0, so we can skip the evaluation
-        */
-        let error_poly = Paired::<'_, C::Scalar>::evaluate_compressed_polynomial(
-            full_constraint,
-            pk.usable_rows.clone(),
+        // Compute the error polynomial e(X) = ∑ⱼ yⱼ(X)⋅eⱼ(X), where eⱼ(X) = ∑ᵢ βᵢ(X)⋅Gⱼ,ᵢ(X),
+        // and βᵢ(X) = β'ᵢ mod m(X) * β''ᵢ / m(X)
+        let mut error_poly = Paired::<'_, C::Scalar>::evaluate_compressed_polynomial(
+            constraints,
+            paired_data.ys(),
+            [
+                &dense[0].beta.beta.powers.values,
+                &dense[1].beta.beta.powers.values,
+            ],
+            [
+                &dense[0].beta.beta_sqrt.powers.values,
+                &dense[1].beta.beta_sqrt.powers.values,
+            ],
             pk.num_rows,
+            masked,
         );
 
-        debug_assert_eq!(error_poly.len(), pk.max_folding_constraints_degree() + 1);
+        // e(X) may have a smaller degree than the bound given by the proving key (or be empty if there are no constraints),
+        // but the verifier always expects the same number of coefficients.
+        debug_assert!(error_poly.len() <= pk.max_folding_constraints_degree() + 1);
+        error_poly.resize(pk.max_folding_constraints_degree() + 1, C::Scalar::ZERO);
 
         let error_poly_quotient = {
             let error0 = eval_polynomial(&error_poly, C::Scalar::ZERO);
             let error1 = eval_polynomial(&error_poly, C::Scalar::ONE);
 
             // Sanity checks for ensuring the error polynomial is correct
-
-            assert_eq!(error0, acc0.error);
-            assert_eq!(error1, acc1.error);
+            if error0 != acc0.error || error1 != acc1.error {
+                return Err(Error::InconsistentAccumulatorError);
+            }
 
             let mut error_poly_vanish = error_poly.clone();
             // subtract (1-t)e0 + te1 = e0 + t(e1-e0)
             error_poly_vanish[0] -= error0;
             error_poly_vanish[1] -= error1 - error0;
-            quotient_by_boolean_vanishing(&error_poly_vanish)
+            quotient_by_boolean_vanishing(&error_poly_vanish)?
         };
 
         // Send the coefficients of the error polynomial to the verifier in the clear.
         for coef in &error_poly_quotient {
-            let _ = transcript.write_scalar(*coef);
+            transcript.write_scalar(*coef)?;
         }
         /*
         Note: The verifier will have to check that e(0) = acc0.error and e(1) = acc1.error.
@@ -116,115 +148,669 @@ impl<C: CurveAffine> Accumulator<C> {
         let alpha = *transcript.squeeze_challenge_scalar::<C::Scalar>();
         let error = eval_polynomial(&error_poly, alpha);
 
-        let gate = gate::Transcript::merge(alpha, acc0.gate, acc1.gate);
-        let lookups = zip(acc0.lookups.into_iter(), acc1.lookups.into_iter())
-            .map(|(lookup0, lookup1)| lookup::Transcript::merge(alpha, lookup0, lookup1))
+        Ok(Self::merge(
+            &[C::Scalar::ONE - alpha, alpha],
+            vec![acc0, acc1],
+            error,
+        ))
+    }
+
+    /// Given `N` accumulators, run the folding reduction of ProtoGalaxy to produce a new accumulator,
+    /// using a single error polynomial and a single challenge.
+    ///
+    /// All accumulators are interpolated by the Lagrange polynomials Lₖ(X) over the points {0, 1, ..., N−1},
+    /// so that the error polynomial e(X) has degree d⋅(N−1), where d is the degree of the folded constraints.
+    /// If all input accumulators are correct, the output accumulator will be correct w.h.p. .
+    pub fn fold_many<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>, const N: usize>(
+        pk: &ProvingKey<C>,
+        accs: [Self; N],
+        transcript: &mut T,
+    ) -> Result<Self, Error> {
+        if N == 0 {
+            return Err(Error::AccumulatorShapeMismatch);
+        }
+        for acc in &accs {
+            acc.check_shape(pk)?;
+        }
+
+        // Create a data structure containing the N committed columns, from which we can compute the constraints to be evaluated.
+        let dense: Vec<_> = accs.iter().map(|acc| acc.densified()).collect();
+        let paired_data = Paired::<'_, C::Scalar, N>::new_data(pk, from_fn(|k| &*dense[k]));
+        let masked = accs.iter().any(|acc| acc.masked);
+
+        // Get the constraint polynomials for the gate, lookups, shuffles and permutation
+        let constraints = paired_data.all_constraints(
+            pk.cs.gates(),
+            pk.lookup_tables(),
+            pk.cs.shuffles(),
+            pk.cs.permutation(),
+        );
+
+        // Compute the error polynomial e(X) = ∑ⱼ yⱼ(X)⋅eⱼ(X), where eⱼ(X) = ∑ᵢ βᵢ(X)⋅Gⱼ,ᵢ(X),
+        // and βᵢ(X) = β'ᵢ mod m(X) * β''ᵢ / m(X)
+        let mut error_poly = Paired::<'_, C::Scalar, N>::evaluate_compressed_polynomial(
+            constraints,
+            paired_data.ys(),
+            from_fn(|k| &dense[k].beta.beta.powers.values),
+            from_fn(|k| &dense[k].beta.beta_sqrt.powers.values),
+            pk.num_rows,
+            masked,
+        );
+
+        let error_poly_len = pk.max_folding_constraints_degree() * (N - 1) + 1;
+        debug_assert!(error_poly.len() <= error_poly_len);
+        error_poly.resize(error_poly_len, C::Scalar::ZERO);
+
+        let error_poly_quotient = {
+            let points: Vec<_> = field_integers().take(N).collect();
+            let errors: Vec<_> = accs.iter().map(|acc| acc.error).collect();
+
+            // Sanity checks for ensuring the error polynomial is correct
+            for (point, error) in zip(&points, &errors) {
+                if eval_polynomial(&error_poly, *point) != *error {
+                    return Err(Error::InconsistentAccumulatorError);
+                }
+            }
+
+            // subtract ∑ₖ Lₖ(X)⋅eₖ
+            let mut error_poly_vanish = error_poly.clone();
+            for (coeff, interpolated) in zip(
+                error_poly_vanish.iter_mut(),
+                lagrange_interpolate(&points, &errors),
+            ) {
+                *coeff -= interpolated;
+            }
+            quotient_by_integer_vanishing(&error_poly_vanish, N)?
+        };
+
+        // Send the coefficients of the quotient
+        //         e(X) − ∑ₖ Lₖ(X)⋅eₖ
+        // e'(X) = ------------------
+        //           ∏ₖ (X − k)
+        // to the verifier in the clear, who computes e(α) = ∏ₖ (α − k)⋅e'(α) + ∑ₖ Lₖ(α)⋅eₖ
+        for coef in &error_poly_quotient {
+            transcript.write_scalar(*coef)?;
+        }
+
+        // Sample α, a challenge for computing the interpolation of all accumulators.
+        let alpha = *transcript.squeeze_challenge_scalar::<C::Scalar>();
+        let error = eval_polynomial(&error_poly, alpha);
+
+        Ok(Self::merge(
+            &lagrange_basis::<_, N>(alpha),
+            accs.into(),
+            error,
+        ))
+    }
+
+    /// Checks that the accumulator contains the number of columns, challenges and constraints
+    /// expected by the proving key, and that all columns have the same number of rows.
+    pub(super) fn check_shape(&self, pk: &ProvingKey<C>) -> Result<(), Error> {
+        let cs = &pk.cs;
+        let counts_ok = self.gate.instance.len() == cs.num_instance_columns
+            && self.gate.advice.len() == cs.num_advice_columns
+            && self.gate.challenges.len() == pk.max_challenge_powers().len()
+            && zip(&self.gate.challenges, pk.max_challenge_powers())
+                .all(|(powers, max_power)| powers.len() == *max_power)
+            && self.lookups.len() == pk.lookup_tables().len()
+            && zip(&self.lookups, pk.lookup_tables()).all(|(lookup, table)| {
+                lookup.thetas.len() == table.table_expressions().len()
+                    && lookup.h.len() == table.arguments().len()
+            })
+            && self.shuffles.len() == cs.shuffles.len()
+            && zip(&self.shuffles, &cs.shuffles)
+                .all(|(shuffle, arg)| shuffle.thetas.len() == arg.input_expressions.len())
+            && self.permutation.g.len() == cs.permutation.columns.len()
+            && self.permutation.h.len() == cs.permutation.columns.len()
+            && self.ys.len() == pk.num_folding_constraints();
+
+        let rows_ok = self
+            .gate
+            .instance
+            .iter()
+            .chain(&self.gate.advice)
+            .chain(
+                self.lookups
+                    .iter()
+                    .flat_map(|lookup| [&lookup.m, &lookup.g].into_iter().chain(&lookup.h)),
+            )
+            .chain(
+                self.shuffles
+                    .iter()
+                    .flat_map(|shuffle| [&shuffle.g, &shuffle.h]),
+            )
+            .chain(&self.permutation.g)
+            .chain(&self.permutation.h)
+            .chain([
+                &self.beta.beta.powers,
+                &self.beta.beta.error,
+                &self.beta.beta_sqrt.powers,
+                &self.beta.beta_sqrt.error,
+            ])
+            .all(|committed| committed.values.len() == pk.num_rows);
+
+        if counts_ok && rows_ok {
+            Ok(())
+        } else {
+            Err(Error::AccumulatorShapeMismatch)
+        }
+    }
+
+    /// Computes the linear combination ∑ₖ wₖ⋅accₖ of all accumulators with the given `weights`,
+    /// where the new `error` is given by the folding reduction.
+    fn merge(weights: &[C::Scalar], accs: Vec<Self>, error: C::Scalar) -> Self {
+        let mut gates = Vec::with_capacity(accs.len());
+        let mut lookups = Vec::with_capacity(accs.len());
+        let mut shuffles = Vec::with_capacity(accs.len());
+        let mut permutations = Vec::with_capacity(accs.len());
+        let mut betas = Vec::with_capacity(accs.len());
+        let mut ys = Vec::with_capacity(accs.len());
+        let masked = accs.iter().any(|acc| acc.masked);
+        for acc in accs {
+            gates.push(acc.gate);
+            lookups.push(acc.lookups);
+            shuffles.push(acc.shuffles);
+            permutations.push(acc.permutation);
+            betas.push(acc.beta);
+            ys.push(acc.ys);
+        }
+
+        let gate = gate::Transcript::merge(weights, gates);
+        let lookups = transpose(lookups)
+            .into_iter()
+            .map(|lookups| lookup::Transcript::merge(weights, lookups))
+            .collect();
+        let shuffles = transpose(shuffles)
+            .into_iter()
+            .map(|shuffles| shuffle::Transcript::merge(weights, shuffles))
             .collect();
-        let beta = compressed_verifier::Transcript::merge(alpha, acc0.beta, acc1.beta);
+        let permutation = permutation::Transcript::merge(weights, permutations);
+        let beta = compressed_verifier::Transcript::merge(weights, betas);
 
-        let ys = zip(acc0.ys.into_iter(), acc1.ys.into_iter())
-            .map(|(y0, y1)| y0 + alpha * (y1 - y0))
+        let ys = transpose(ys)
+            .into_iter()
+            .map(|ys| linear_combination(weights, ys))
             .collect();
 
         Self {
             gate,
             lookups,
+            shuffles,
+            permutation,
             beta,
             ys,
             error,
+            masked,
         }
     }
 
     /// Checks whether the accumulator is valid with regards to the proving key.
     /// - Check all commitments are correct
     /// - Check the error term is correct
-    /// - Verify the linear lookup constraints skipped during folding
+    /// - Verify the linear lookup, shuffle and permutation constraints skipped during folding
     /// - Check the correctness of the beta error vector
-    /// NOTE: Permutation and shuffle constraints are not verified here.
     pub fn decide<'params, P: Params<'params, C>>(
         params: &P,
         pk: &ProvingKey<C>,
         acc: &Self,
     ) -> bool {
+        Self::decide_detailed(params, pk, acc).is_ok()
+    }
+
+    /// Runs the same checks as `decide`, and returns the list of all checks which are not satisfied.
+    pub fn decide_detailed<'params, P: Params<'params, C>>(
+        params: &P,
+        pk: &ProvingKey<C>,
+        acc: &Self,
+    ) -> Result<(), Vec<DecideError<C::Scalar>>> {
         // Check all Committed columns are correct (commit(values;bline) == commitment)
-        let committed_ok = {
-            let committed_iter: Vec<&Committed<C>> = acc
-                .gate
-                .instance
-                .iter()
-                .chain(&acc.gate.advice)
-                .chain([&acc.beta.beta, &acc.beta.error].into_iter())
+        let mut errors: Vec<_> = acc
+            .committed_columns(pk)
+            .into_iter()
+            .filter(|(_, c)| !c.decide(params))
+            .map(|(column, _)| DecideError::Commitment { column })
+            .collect();
+
+        errors.extend(Self::unsatisfied_relations(pk, acc));
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Runs the same checks as `decide`, except that all commitments are checked at once
+    /// against a random linear combination of their columns.
+    /// This requires a single commitment rather than one per column, at the cost of
+    /// a soundness error of 1/|F| over the randomness sampled from `rng`.
+    ///
+    /// Like `decide`, this is the native check run by the holder of the witness columns.
+    /// It only batches the commitments of a single accumulator, and does not batch the
+    /// verification of `VerifierAccumulator`s, which only contain commitments.
+    pub fn decide_batched<'params, P: Params<'params, C>, R: RngCore>(
+        params: &P,
+        pk: &ProvingKey<C>,
+        acc: &Self,
+        rng: R,
+    ) -> bool {
+        let committed = acc.committed_columns(pk).into_iter().map(|(_, c)| c);
+        Committed::batch_decide(params, committed, rng)
+            && Self::unsatisfied_relations(pk, acc).is_empty()
+    }
+
+    /// Stores all columns of the accumulator densely.
+    pub fn densify(&mut self) {
+        let columns = self
+            .gate
+            .instance
+            .iter_mut()
+            .chain(self.gate.advice.iter_mut())
+            .chain(self.lookups.iter_mut().flat_map(|lookup| {
+                [&mut lookup.m, &mut lookup.g]
+                    .into_iter()
+                    .chain(lookup.h.iter_mut())
+            }))
+            .chain(
+                self.shuffles
+                    .iter_mut()
+                    .flat_map(|shuffle| [&mut shuffle.g, &mut shuffle.h]),
+            )
+            .chain(self.permutation.g.iter_mut())
+            .chain(self.permutation.h.iter_mut())
+            .chain([
+                &mut self.beta.beta.powers,
+                &mut self.beta.beta.error,
+                &mut self.beta.beta_sqrt.powers,
+                &mut self.beta.beta_sqrt.error,
+            ]);
+        for committed in columns {
+            committed.values.densify();
+        }
+    }
+
+    /// Returns the accumulator with all its columns stored densely, cloning it only if one of them is sparse.
+    /// Constraints are evaluated by indexing every column at each row, which requires a search in sparse columns.
+    fn densified(&self) -> Cow<'_, Self> {
+        let is_sparse = self
+            .gate
+            .instance
+            .iter()
+            .chain(self.gate.advice.iter())
+            .chain(
+                self.lookups
+                    .iter()
+                    .flat_map(|lookup| [&lookup.m, &lookup.g].into_iter().chain(lookup.h.iter())),
+            )
+            .chain(
+                self.shuffles
+                    .iter()
+                    .flat_map(|shuffle| [&shuffle.g, &shuffle.h]),
+            )
+            .chain(self.permutation.g.iter())
+            .chain(self.permutation.h.iter())
+            .chain([&self.beta.beta.powers, &self.beta.beta_sqrt.powers])
+            .any(|committed| committed.values.is_sparse());
+        if is_sparse {
+            let mut acc = self.clone();
+            acc.densify();
+            Cow::Owned(acc)
+        } else {
+            Cow::Borrowed(self)
+        }
+    }
+
+    /// Returns all committed columns of the accumulator, along with their labels.
+    fn committed_columns(&self, pk: &ProvingKey<C>) -> Vec<(ColumnLabel, &Committed<C>)> {
+        // Instance columns are public and are only committed to in the `query_instance` mode.
+        let instance = pk.query_instance().then_some(&self.gate.instance);
+        self.gate
+            .advice
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (ColumnLabel::Advice(i), c))
+            .chain(
+                instance
+                    .into_iter()
+                    .flatten()
+                    .enumerate()
+                    .map(|(i, c)| (ColumnLabel::Instance(i), c)),
+            )
+            .chain([
+                (ColumnLabel::Beta, &self.beta.beta.powers),
+                (ColumnLabel::BetaError, &self.beta.beta.error),
+                (ColumnLabel::BetaSqrt, &self.beta.beta_sqrt.powers),
+                (ColumnLabel::BetaSqrtError, &self.beta.beta_sqrt.error),
+            ])
+            .chain(self.lookups.iter().enumerate().flat_map(|(i, lookup)| {
+                [
+                    (ColumnLabel::LookupM(i), &lookup.m),
+                    (ColumnLabel::LookupG(i), &lookup.g),
+                ]
+                .into_iter()
                 .chain(
-                    acc.lookups
+                    lookup
+                        .h
                         .iter()
-                        .flat_map(|lookup| [&lookup.m, &lookup.g, &lookup.h].into_iter()),
+                        .enumerate()
+                        .map(move |(k, h)| (ColumnLabel::LookupH(i, k), h)),
                 )
-                .collect();
-            committed_iter.iter().all(|c| c.decide(params))
-        };
+            }))
+            .chain(self.shuffles.iter().enumerate().flat_map(|(i, shuffle)| {
+                [
+                    (ColumnLabel::ShuffleG(i), &shuffle.g),
+                    (ColumnLabel::ShuffleH(i), &shuffle.h),
+                ]
+            }))
+            .chain(
+                self.permutation
+                    .g
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (ColumnLabel::PermutationG(i), c)),
+            )
+            .chain(
+                self.permutation
+                    .h
+                    .iter()
+                    .enumerate()
+                    .map(|(i, c)| (ColumnLabel::PermutationH(i), c)),
+            )
+            .collect()
+    }
+
+    /// Checks all relations of the decider over the values of the committed columns,
+    /// and returns those which are not satisfied.
+    fn unsatisfied_relations(pk: &ProvingKey<C>, acc: &Self) -> Vec<DecideError<C::Scalar>> {
+        let mut errors = vec![];
+        let dense = acc.densified();
+        let acc: &Self = &dense;
 
         // Check Error term  (error == ∑ᵢ βᵢ * Gᵢ)
-        let error_ok = { acc.error == Self::error(&pk, &acc) };
-
-        // Check linear lookup constraint ∑ᵢ gᵢ == ∑ᵢ hᵢ
-        let lookups_ok = {
-            acc.lookups.iter().all(|lookup| {
-                let lhs: C::Scalar = lookup.g.values.iter().sum();
-                let rhs: C::Scalar = lookup.h.values.iter().sum();
-                lhs == rhs
-            })
-        };
+        let computed = Self::error(pk, acc);
+        if acc.error != computed {
+            errors.push(Self::error_contributions(pk, acc, computed));
+        }
 
-        // Check beta constraint eᵢ ≡ β ⋅ βᵢ − βᵢ₊₁, β₀ ≡ 1
-        let beta_ok = {
-            let beta_column = &acc.beta.beta.values;
-            let error_column = &acc.beta.error.values;
+        // Check linear lookup constraint ∑ᵢ gᵢ == ∑ₖ,ᵢ hₖ,ᵢ
+        for (index, (lookup, table)) in zip(&acc.lookups, pk.lookup_tables()).enumerate() {
+            let lhs: C::Scalar = lookup.g.values.sum();
+            let rhs: C::Scalar = lookup.h.iter().map(|h| h.values.sum()).sum();
+            if lhs != rhs {
+                errors.push(DecideError::Lookup {
+                    index,
+                    name: table.name(),
+                });
+            }
+        }
 
-            let beta = beta_column[1];
+        // Check linear shuffle constraint ∑ᵢ gᵢ == ∑ᵢ hᵢ
+        for (index, (shuffle, arg)) in zip(&acc.shuffles, pk.cs.shuffles()).enumerate() {
+            let lhs: C::Scalar = shuffle.g.values.sum();
+            let rhs: C::Scalar = shuffle.h.values.sum();
+            if lhs != rhs {
+                errors.push(DecideError::Shuffle {
+                    index,
+                    name: arg.name().to_string(),
+                });
+            }
+        }
 
-            let powers_ok = (1..pk.num_rows)
+        // Check linear permutation constraint ∑ⱼ,ᵢ hⱼ,ᵢ == ∑ⱼ,ᵢ gⱼ,ᵢ
+        {
+            let lhs: C::Scalar = acc.permutation.h.iter().map(|h| h.values.sum()).sum();
+            let rhs: C::Scalar = acc.permutation.g.iter().map(|g| g.values.sum()).sum();
+            if lhs != rhs {
+                errors.push(DecideError::Permutation);
+            }
+        }
+
+        // Check beta constraints eᵢ ≡ β ⋅ βᵢ − βᵢ₊₁, β₀ ≡ 1 for both β' and β''
+        let [beta_rows, beta_sqrt_rows] = acc.beta.unsatisfied_rows(pk);
+        errors.extend(
+            beta_rows
                 .into_iter()
-                .all(|i| error_column[i - 1] == beta_column[i - 1] * beta - beta_column[i]);
+                .map(|row| (ColumnLabel::Beta, row))
+                .chain(
+                    beta_sqrt_rows
+                        .into_iter()
+                        .map(|row| (ColumnLabel::BetaSqrt, row)),
+                )
+                .map(|(column, row)| DecideError::Beta { column, row }),
+        );
 
-            let init_ok = beta_column[0] == C::Scalar::ONE;
-            powers_ok && init_ok
-        };
+        errors
+    }
+
+    /// Splits the recomputed error term e = ∑ᵢ βᵢ⋅∑ⱼ yⱼ⋅Gⱼ,ᵢ into the contributions of the constraints Gⱼ
+    /// of each gate, lookup and shuffle argument, and of the permutation argument.
+    fn error_contributions(
+        pk: &ProvingKey<C>,
+        acc: &Self,
+        computed: C::Scalar,
+    ) -> DecideError<C::Scalar> {
+        let lagrange_data = Data::<CommittedRef<'_, C>>::new(pk, acc);
+        let constraints = lagrange_data.all_constraints(
+            pk.cs.gates(),
+            pk.lookup_tables(),
+            pk.cs.shuffles(),
+            pk.cs.permutation(),
+        );
+
+        // Compute yⱼ⋅∑ᵢ βᵢ⋅Gⱼ,ᵢ for each constraint Gⱼ, in the order given by `all_constraints`
+        let stride = compressed_verifier::sqrt_stride(pk.num_rows);
+        let mut contributions = zip(constraints.iter(), acc.ys.iter()).map(|(constraint, y)| {
+            let sum: C::Scalar = (0..pk.num_rows)
+                .map(|row_idx| {
+                    acc.beta.beta_at(row_idx, stride)
+                        * constraint.evaluate(
+                            &|&c| c,
+                            &|&challenge| *challenge.value,
+                            &|&fixed| fixed.column.values[fixed.row_idx(row_idx, pk.num_rows)],
+                            &|&witness| {
+                                witness.column.values[witness.row_idx(row_idx, pk.num_rows)]
+                            },
+                            &|&e| -e,
+                            &|a, b| a + b,
+                            &|a, b| a * b,
+                        )
+                })
+                .sum();
+            sum * y
+        });
 
-        committed_ok && error_ok && lookups_ok && beta_ok
+        let mut take_sum = |n: usize| -> C::Scalar { contributions.by_ref().take(n).sum() };
+        let gates = pk
+            .cs
+            .gates()
+            .iter()
+            .map(|gate| (gate.name().to_string(), take_sum(gate.polynomials().len())))
+            .collect();
+        let lookups = pk
+            .lookup_tables()
+            .iter()
+            .map(|table| (table.name(), take_sum(table.arguments().len() + 1)))
+            .collect();
+        let shuffles = pk
+            .cs
+            .shuffles()
+            .iter()
+            .map(|arg| (arg.name().to_string(), take_sum(2)))
+            .collect();
+        let permutation = take_sum(2 * pk.cs.permutation().columns.len());
+
+        DecideError::Error {
+            expected: acc.error,
+            computed,
+            gates,
+            lookups,
+            shuffles,
+            permutation,
+        }
     }
 
     /// Recompute the compressed error term e = ∑ᵢ βᵢ * Gᵢ
     pub fn error(pk: &ProvingKey<C>, acc: &Self) -> C::Scalar {
-        let lagrange_data = Data::<CommittedRef<'_, C>>::new(&pk, &acc);
+        let acc = acc.densified();
+        let lagrange_data = Data::<CommittedRef<'_, C>>::new(pk, &acc);
+
+        let graphs = lagrange_data.full_constraint_graphs(
+            pk.cs.gates(),
+            pk.lookup_tables(),
+            pk.cs.shuffles(),
+            pk.cs.permutation(),
+        );
 
-        let full_constraint = lagrange_data.full_constraint(pk.cs.gates(), pk.cs.lookups());
+        // Buffers for evaluating each graph at a single point, where the invariants are computed once
+        let graph_data: Vec<_> = graphs
+            .iter()
+            .map(|graph| {
+                let mut data = graph.data(1);
+                for (idx, challenge) in graph.challenges.iter().enumerate() {
+                    data.challenge_mut(idx)[0] = *challenge.value;
+                }
+                graph.evaluate_invariants(&mut data);
+                data
+            })
+            .collect();
 
         let mut error = pk.domain.empty_lagrange();
         parallelize(&mut error, |value, start| {
+            let mut graph_data = graph_data.clone();
             for (i, v) in value.iter_mut().enumerate() {
                 let row_idx = i + start;
-                *v = full_constraint.evaluate(
-                    &|&c| c,
-                    &|&challenge| *challenge.value,
-                    &|&fixed| fixed.column.values[fixed.row_idx(row_idx, pk.num_rows)],
-                    &|&witness| witness.column.values[witness.row_idx(row_idx, pk.num_rows)],
-                    &|&e| -e,
-                    &|a, b| a + b,
-                    &|a, b| a * b,
-                );
+                *v = C::Scalar::ZERO;
+                for (graph, data) in zip(graphs.iter(), graph_data.iter_mut()) {
+                    // Skip the graph if one of its selectors is off
+                    let selected = graph.selectors.iter().all(|&fixed_idx| {
+                        let query = &graph.fixed[fixed_idx];
+                        !bool::from(
+                            query.column.values[query.row_idx(row_idx, pk.num_rows)].is_zero(),
+                        )
+                    });
+                    if !selected {
+                        continue;
+                    }
+
+                    for (idx, query) in graph.fixed.iter().enumerate() {
+                        *data.fixed_mut(idx) =
+                            query.column.values[query.row_idx(row_idx, pk.num_rows)];
+                    }
+                    for (idx, query) in graph.witness.iter().enumerate() {
+                        data.witness_mut(idx)[0] =
+                            query.column.values[query.row_idx(row_idx, pk.num_rows)];
+                    }
+                    *v += graph.evaluate(data).get(0);
+                }
             }
         });
-        error.iter().sum()
+        let stride = compressed_verifier::sqrt_stride(pk.num_rows);
+        (0..pk.num_rows)
+            .map(|row_idx| acc.beta.beta_at(row_idx, stride) * error[row_idx])
+            .sum()
+    }
+}
+
+impl<C: SerdeCurveAffine> Accumulator<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes an accumulator to a buffer.
+    ///
+    /// Field and curve elements are written according to `format`,
+    /// as in [`crate::plonk::ProvingKey::write`].
+    pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        self.gate.write(writer, format)?;
+        write_slice(&self.lookups, writer, |lookup, writer| {
+            lookup.write(writer, format)
+        })?;
+        write_slice(&self.shuffles, writer, |shuffle, writer| {
+            shuffle.write(writer, format)
+        })?;
+        self.permutation.write(writer, format)?;
+        self.beta.write(writer, format)?;
+        write_slice(&self.ys, writer, |y, writer| y.write(writer, format))?;
+        self.error.write(writer, format)?;
+        writer.write_all(&[self.masked as u8])
+    }
+
+    /// Reads an accumulator from a buffer.
+    ///
+    /// Field and curve elements are read according to `format`,
+    /// as in [`crate::plonk::ProvingKey::read`].
+    /// Returns an error if the accumulator does not match the shape of `pk`.
+    pub fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let gate = gate::Transcript::read(reader, format, pk)?;
+        let lookups = read_vec(reader, |reader| {
+            lookup::Transcript::read(reader, format, pk)
+        })?;
+        let shuffles = read_vec(reader, |reader| {
+            shuffle::Transcript::read(reader, format, pk)
+        })?;
+        let permutation = permutation::Transcript::read(reader, format, pk)?;
+        let beta = compressed_verifier::Transcript::read(reader, format, pk)?;
+        let ys = read_vec(reader, |reader| C::Scalar::read(reader, format))?;
+        let error = C::Scalar::read(reader, format)?;
+        let masked = {
+            let mut byte = [0u8; 1];
+            reader.read_exact(&mut byte)?;
+            match byte[0] {
+                0 => false,
+                1 => true,
+                _ => {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        "invalid masked flag",
+                    ))
+                }
+            }
+        };
+
+        let acc = Self {
+            gate,
+            lookups,
+            shuffles,
+            permutation,
+            beta,
+            ys,
+            error,
+            masked,
+        };
+        acc.check_shape(pk)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(acc)
+    }
+
+    /// Writes an accumulator to a vector of bytes using [`Self::write`].
+    pub fn to_bytes(&self, format: SerdeFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes, format)
+            .expect("Writing to vector should not fail");
+        bytes
+    }
+
+    /// Reads an accumulator from a slice of bytes using [`Self::read`].
+    pub fn from_bytes(
+        mut bytes: &[u8],
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        Self::read(&mut bytes, format, pk)
     }
 }
 
 // Given a polynomial p(X) of degree d > 1, compute its quotient q(X)
 // such that p(X) = (1-X)X⋅q(X).
-// Panics if deg(p) ≤ 1 or if p(0) ≠ 0 or p(1) ≠ 0
-fn quotient_by_boolean_vanishing<F: Field>(poly: &[F]) -> Vec<F> {
+// Fails if deg(p) ≤ 1 or if p(0) ≠ 0 or p(1) ≠ 0
+fn quotient_by_boolean_vanishing<F: Field>(poly: &[F]) -> Result<Vec<F>, Error> {
     let n = poly.len();
-    assert!(n >= 2, "deg(poly) < 2");
-    assert!(poly[0].is_zero_vartime(), "poly(0) != 0");
+    if n < 2 || !poly[0].is_zero_vartime() {
+        return Err(Error::InconsistentAccumulatorError);
+    }
 
     let mut tmp = F::ZERO;
 
@@ -235,10 +821,68 @@ fn quotient_by_boolean_vanishing<F: Field>(poly: &[F]) -> Vec<F> {
     }
 
     // p(1) = ∑p_i = 0
-    assert_eq!(
-        *quotient.last().unwrap(),
-        poly.last().unwrap().neg(),
-        "poly(1) != 0"
-    );
-    quotient
+    if quotient.last() != poly.last().map(|last| last.neg()).as_ref() {
+        return Err(Error::InconsistentAccumulatorError);
+    }
+    Ok(quotient)
+}
+
+// Given a polynomial p(X) vanishing over {0, 1, ..., n−1}, compute its quotient q(X)
+// such that p(X) = X(X−1)⋯(X−(n−1))⋅q(X).
+// Fails if p(j) ≠ 0 for some j in {0, 1, ..., n−1}
+fn quotient_by_integer_vanishing<F: Field>(poly: &[F], n: usize) -> Result<Vec<F>, Error> {
+    field_integers()
+        .take(n)
+        .try_fold(poly.to_vec(), |quotient, point| {
+            if quotient.is_empty() || !eval_polynomial(&quotient, point).is_zero_vartime() {
+                return Err(Error::InconsistentAccumulatorError);
+            }
+            Ok(kate_division(&quotient, point))
+        })
+}
+
+/// Returns the evaluations Lₖ(x) of the Lagrange polynomials over the points {0, 1, ..., N−1},
+/// where Lₖ(k) = 1 and Lₖ(j) = 0 for j ≠ k.
+pub(super) fn lagrange_basis<F: Field, const N: usize>(x: F) -> [F; N] {
+    let points: Vec<F> = field_integers().take(N).collect();
+    from_fn(|k| {
+        let (numerator, denominator) = points
+            .iter()
+            .enumerate()
+            .filter(|(j, _)| *j != k)
+            .fold((F::ONE, F::ONE), |(num, denom), (_, point)| {
+                (num * (x - point), denom * (points[k] - point))
+            });
+        numerator * denominator.invert().unwrap()
+    })
+}
+
+/// Returns the evaluation at x of the polynomial X(X−1)⋯(X−(n−1)) vanishing over {0, 1, ..., n−1}.
+pub(super) fn vanishing_at_integers<F: Field>(x: F, n: usize) -> F {
+    field_integers::<F>()
+        .take(n)
+        .map(|point| x - point)
+        .product()
+}
+
+/// Computes the linear combination ∑ₖ wₖ⋅vₖ of `values` with `weights`.
+pub(super) fn linear_combination<F: Field>(
+    weights: &[F],
+    values: impl IntoIterator<Item = F>,
+) -> F {
+    zip(weights, values).fold(F::ZERO, |acc, (weight, value)| acc + *weight * value)
+}
+
+/// Given a list of N vectors of the same length, returns the vectors of their N entries at each index.
+pub(super) fn transpose<T>(vectors: Vec<Vec<T>>) -> Vec<Vec<T>> {
+    let len = vectors.first().map_or(0, Vec::len);
+    let mut iters: Vec<_> = vectors.into_iter().map(Vec::into_iter).collect();
+    (0..len)
+        .map(|_| {
+            iters
+                .iter_mut()
+                .map(|iter| iter.next().expect("vectors should have the same length"))
+                .collect()
+        })
+        .collect()
 }
@@ -0,0 +1,552 @@
+use std::{
+    io,
+    iter::zip,
+    ops::{Add, Index, IndexMut, Mul},
+};
+
+use ff::Field;
+use halo2curves::CurveAffine;
+
+use crate::{
+    arithmetic::parallelize,
+    helpers::SerdePrimeField,
+    poly::{
+        commitment::{Blind, Params},
+        empty_lagrange, LagrangeCoeff, Polynomial,
+    },
+    SerdeFormat,
+};
+
+/// Columns are stored sparsely when at most 1/`SPARSE_RATIO` of their rows are non-zero.
+const SPARSE_RATIO: usize = 4;
+
+/// Values of a committed column over all rows of the trace.
+///
+/// Columns which are only non-zero over a small region of the trace, such as the advice columns
+/// of a small circuit or the compressed powers of beta, are stored sparsely.
+/// Both representations behave identically, and only differ in their memory footprint
+/// and in the size of the multiexp required to commit to them.
+#[derive(Debug, Clone)]
+pub enum ColumnValues<F> {
+    Dense(Polynomial<F, LagrangeCoeff>),
+    Sparse(SparseColumn<F>),
+}
+
+/// Column of `num_rows` values, which only stores the non-zero `values` along with their `rows`,
+/// in increasing order.
+#[derive(Debug, Clone)]
+pub struct SparseColumn<F> {
+    num_rows: usize,
+    rows: Vec<usize>,
+    values: Vec<F>,
+    // Value of the rows which are not stored, returned when indexing them
+    zero: F,
+}
+
+impl<F: Field> SparseColumn<F> {
+    /// Returns the column containing only the non-zero entries of `column`.
+    pub fn from_dense(column: &[F]) -> Self {
+        let (rows, values) = column
+            .iter()
+            .enumerate()
+            .filter(|(_, value)| !bool::from(value.is_zero()))
+            .unzip();
+        Self {
+            num_rows: column.len(),
+            rows,
+            values,
+            zero: F::ZERO,
+        }
+    }
+
+    /// Returns the column of `num_rows` zeros.
+    pub fn zero(num_rows: usize) -> Self {
+        Self {
+            num_rows,
+            rows: vec![],
+            values: vec![],
+            zero: F::ZERO,
+        }
+    }
+
+    /// Returns the stored rows along with their values, in increasing order of rows.
+    pub fn entries(&self) -> impl Iterator<Item = (usize, &F)> {
+        zip(self.rows.iter().copied(), self.values.iter())
+    }
+
+    /// Returns the index of `row` in the stored rows,
+    /// or the index at which it should be inserted if it is not stored.
+    fn position(&self, row: usize) -> Result<usize, usize> {
+        self.rows.binary_search(&row)
+    }
+
+    /// Returns the dense column.
+    pub fn to_dense(&self) -> Polynomial<F, LagrangeCoeff> {
+        let mut column = empty_lagrange(self.num_rows);
+        for (row, value) in self.entries() {
+            column[row] = *value;
+        }
+        column
+    }
+
+    /// Returns the sum of two sparse columns, whose stored rows are the union of the rows of both.
+    fn add(&self, rhs: &Self) -> Self {
+        debug_assert_eq!(self.num_rows, rhs.num_rows);
+        let mut rows = Vec::with_capacity(self.rows.len() + rhs.rows.len());
+        let mut values = Vec::with_capacity(self.rows.len() + rhs.rows.len());
+        let mut lhs = self.entries().peekable();
+        let mut rhs = rhs.entries().peekable();
+        loop {
+            let (row, value) = match (lhs.peek(), rhs.peek()) {
+                (Some(&(l_row, l_value)), Some(&(r_row, r_value))) => {
+                    if l_row == r_row {
+                        lhs.next();
+                        rhs.next();
+                        (l_row, *l_value + r_value)
+                    } else if l_row < r_row {
+                        lhs.next();
+                        (l_row, *l_value)
+                    } else {
+                        rhs.next();
+                        (r_row, *r_value)
+                    }
+                }
+                (Some(&(row, value)), None) | (None, Some(&(row, value))) => {
+                    lhs.next();
+                    rhs.next();
+                    (row, *value)
+                }
+                (None, None) => break,
+            };
+            rows.push(row);
+            values.push(value);
+        }
+        Self {
+            num_rows: self.num_rows,
+            rows,
+            values,
+            zero: F::ZERO,
+        }
+    }
+}
+
+impl<F: Field> ColumnValues<F> {
+    /// Returns the values of `column`, which are stored sparsely if few of them are non-zero.
+    pub fn new(column: Polynomial<F, LagrangeCoeff>) -> Self {
+        let num_non_zero = column
+            .iter()
+            .filter(|value| !bool::from(value.is_zero()))
+            .count();
+        if num_non_zero * SPARSE_RATIO <= column.len() {
+            Self::Sparse(SparseColumn::from_dense(&column))
+        } else {
+            Self::Dense(column)
+        }
+    }
+
+    /// Returns the column of `num_rows` zeros.
+    pub fn zero(num_rows: usize) -> Self {
+        Self::Sparse(SparseColumn::zero(num_rows))
+    }
+
+    /// Number of rows of the column, including the ones which are not stored.
+    pub fn len(&self) -> usize {
+        match self {
+            Self::Dense(column) => column.len(),
+            Self::Sparse(column) => column.num_rows,
+        }
+    }
+
+    /// Returns true if the column has no rows.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the value at `row`, or `None` if it is out of bounds.
+    pub fn get(&self, row: usize) -> Option<&F> {
+        (row < self.len()).then(|| &self[row])
+    }
+
+    /// Iterates over the values of all rows of the column.
+    pub fn iter(&self) -> Iter<'_, F> {
+        match self {
+            Self::Dense(column) => Iter::Dense(column[..].iter()),
+            Self::Sparse(column) => Iter::Sparse {
+                column,
+                row: 0,
+                entry: 0,
+            },
+        }
+    }
+
+    /// Returns true if only the non-zero rows of the column are stored.
+    pub fn is_sparse(&self) -> bool {
+        matches!(self, Self::Sparse(_))
+    }
+
+    /// Stores the column densely, so that indexing a row does not require searching the stored rows.
+    pub fn densify(&mut self) {
+        if let Self::Sparse(column) = self {
+            *self = Self::Dense(column.to_dense());
+        }
+    }
+
+    /// Returns the sum of the values of all rows of the column.
+    pub fn sum(&self) -> F {
+        match self {
+            Self::Dense(column) => column.iter().sum(),
+            Self::Sparse(column) => column.values.iter().sum(),
+        }
+    }
+
+    /// Returns the dense column.
+    pub fn to_dense(&self) -> Polynomial<F, LagrangeCoeff> {
+        match self {
+            Self::Dense(column) => column.clone(),
+            Self::Sparse(column) => column.to_dense(),
+        }
+    }
+
+    /// Adds `scalar` times the column to `column`.
+    pub fn add_scaled_to(&self, scalar: F, column: &mut [F]) {
+        match self {
+            Self::Dense(values) => parallelize(column, |column, start| {
+                for (column, value) in zip(column, &values[start..]) {
+                    *column += scalar * value;
+                }
+            }),
+            Self::Sparse(values) => {
+                for (row, value) in values.entries() {
+                    column[row] += scalar * value;
+                }
+            }
+        }
+    }
+
+    /// Commits to the column, using a multiexp over the non-zero rows only if the column is sparse.
+    pub fn commit<'params, C: CurveAffine<ScalarExt = F>, P: Params<'params, C>>(
+        &self,
+        params: &P,
+        blind: Blind<F>,
+    ) -> C::Curve {
+        match self {
+            Self::Dense(column) => params.commit_lagrange(column, blind),
+            Self::Sparse(column) => {
+                params.commit_lagrange_sparse(&column.rows, &column.values, blind)
+            }
+        }
+    }
+}
+
+/// Iterator over the values of all rows of a column, returned by [`ColumnValues::iter`].
+#[derive(Debug, Clone)]
+pub enum Iter<'a, F> {
+    Dense(std::slice::Iter<'a, F>),
+    Sparse {
+        column: &'a SparseColumn<F>,
+        // Next row to be returned
+        row: usize,
+        // Index of the first stored row which is not smaller than `row`
+        entry: usize,
+    },
+}
+
+impl<'a, F> Iterator for Iter<'a, F> {
+    type Item = &'a F;
+
+    fn next(&mut self) -> Option<&'a F> {
+        match self {
+            Self::Dense(values) => values.next(),
+            Self::Sparse { column, row, entry } => {
+                let column: &'a SparseColumn<F> = column;
+                if *row == column.num_rows {
+                    return None;
+                }
+                let value = if column.rows.get(*entry) == Some(row) {
+                    *entry += 1;
+                    &column.values[*entry - 1]
+                } else {
+                    &column.zero
+                };
+                *row += 1;
+                Some(value)
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = match self {
+            Self::Dense(values) => values.len(),
+            Self::Sparse { column, row, .. } => column.num_rows - row,
+        };
+        (len, Some(len))
+    }
+}
+
+impl<'a, F> ExactSizeIterator for Iter<'a, F> {}
+
+impl<F: SerdePrimeField> ColumnValues<F> {
+    /// Writes the values of all rows of the column, in the same format as a dense `Polynomial`.
+    pub(crate) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        writer.write_all(&(self.len() as u32).to_be_bytes())?;
+        for value in self.iter() {
+            value.write(writer, format)?;
+        }
+        Ok(())
+    }
+
+    /// Reads the values of all rows of a column, and stores them sparsely if few of them are non-zero.
+    pub(crate) fn read<R: io::Read>(reader: &mut R, format: SerdeFormat) -> io::Result<Self> {
+        Polynomial::read(reader, format).map(Self::new)
+    }
+}
+
+impl<F: Field> From<Polynomial<F, LagrangeCoeff>> for ColumnValues<F> {
+    fn from(column: Polynomial<F, LagrangeCoeff>) -> Self {
+        Self::new(column)
+    }
+}
+
+impl<F: Field> Index<usize> for ColumnValues<F> {
+    type Output = F;
+
+    fn index(&self, row: usize) -> &F {
+        match self {
+            Self::Dense(column) => &column[row],
+            Self::Sparse(column) => {
+                assert!(row < column.num_rows, "row {} out of bounds", row);
+                match column.position(row) {
+                    Ok(idx) => &column.values[idx],
+                    Err(_) => &column.zero,
+                }
+            }
+        }
+    }
+}
+
+impl<F: Field> IndexMut<usize> for ColumnValues<F> {
+    /// Returns a mutable reference to the value at `row`.
+    /// A sparse column is converted to a dense one if `row` is not stored,
+    /// rather than inserting it in the middle of the stored rows.
+    fn index_mut(&mut self, row: usize) -> &mut F {
+        if let Self::Sparse(column) = self {
+            assert!(row < column.num_rows, "row {} out of bounds", row);
+            if column.position(row).is_err() {
+                *self = Self::Dense(column.to_dense());
+            }
+        }
+        match self {
+            Self::Dense(column) => &mut column[row],
+            Self::Sparse(column) => {
+                let idx = column.position(row).unwrap();
+                &mut column.values[idx]
+            }
+        }
+    }
+}
+
+/// Columns are equal if they have the same values at all rows, regardless of their representation.
+impl<F: Field> PartialEq for ColumnValues<F> {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && zip(self.iter(), other.iter()).all(|(a, b)| a == b)
+    }
+}
+
+impl<F: Field> Add<&ColumnValues<F>> for ColumnValues<F> {
+    type Output = ColumnValues<F>;
+
+    fn add(self, rhs: &Self) -> Self::Output {
+        match (self, rhs) {
+            (Self::Sparse(lhs), Self::Sparse(rhs)) => {
+                let sum = lhs.add(rhs);
+                if sum.rows.len() * SPARSE_RATIO <= sum.num_rows {
+                    Self::Sparse(sum)
+                } else {
+                    Self::Dense(sum.to_dense())
+                }
+            }
+            (Self::Dense(lhs), Self::Dense(rhs)) => Self::Dense(lhs + rhs),
+            (lhs, rhs) => {
+                let (mut sum, other) = match (lhs, rhs) {
+                    (Self::Dense(lhs), rhs) => (lhs, rhs.clone()),
+                    (lhs, Self::Dense(rhs)) => (rhs.clone(), lhs),
+                    _ => unreachable!(),
+                };
+                other.add_scaled_to(F::ONE, &mut sum);
+                Self::Dense(sum)
+            }
+        }
+    }
+}
+
+impl<F: Field> Mul<F> for ColumnValues<F> {
+    type Output = ColumnValues<F>;
+
+    fn mul(self, rhs: F) -> Self::Output {
+        match self {
+            Self::Dense(column) => Self::Dense(column * rhs),
+            Self::Sparse(mut column) => {
+                for value in column.values.iter_mut() {
+                    *value *= rhs;
+                }
+                Self::Sparse(column)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use group::Curve;
+    use halo2curves::pasta::pallas;
+    use rand_core::OsRng;
+
+    use super::ColumnValues;
+    use crate::{
+        poly::{
+            commitment::{Blind, ParamsProver},
+            empty_lagrange,
+            ipa::commitment::ParamsIPA,
+        },
+        protostar::{
+            accumulator::Accumulator,
+            verifier::tests::{fixture, range_check_circuits},
+            LookupChallenges,
+        },
+        transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer},
+    };
+
+    const NUM_ROWS: usize = 16;
+
+    /// Returns the column of `NUM_ROWS` rows containing `value` at each row of `rows`.
+    fn column(rows: &[usize], value: u64) -> ColumnValues<pallas::Scalar> {
+        let mut column = empty_lagrange(NUM_ROWS);
+        for row in rows {
+            column[*row] = pallas::Scalar::from(value);
+        }
+        ColumnValues::new(column)
+    }
+
+    fn is_sparse(column: &ColumnValues<pallas::Scalar>) -> bool {
+        matches!(column, ColumnValues::Sparse(_))
+    }
+
+    #[test]
+    fn test_sparse_add() {
+        let lhs = column(&[1, 3], 2);
+        let rhs = column(&[3, 7], 5);
+        assert!(is_sparse(&lhs) && is_sparse(&rhs));
+
+        // Rows stored by both columns are merged
+        let sum = lhs.clone() + &rhs;
+        assert!(is_sparse(&sum));
+        if let ColumnValues::Sparse(sparse) = &sum {
+            assert_eq!(sparse.rows, vec![1, 3, 7]);
+        }
+        let expected: Vec<_> = [0, 2, 0, 7, 0, 0, 0, 5]
+            .into_iter()
+            .map(pallas::Scalar::from)
+            .chain(std::iter::repeat(pallas::Scalar::ZERO))
+            .take(NUM_ROWS)
+            .collect();
+        assert!(sum.iter().eq(expected.iter()));
+
+        // The sum is stored densely once too many rows are non-zero
+        let sum = column(&[0, 2, 4], 1) + &column(&[1, 3], 1);
+        assert!(!is_sparse(&sum));
+        assert_eq!(sum, column(&[0, 1, 2, 3, 4], 1));
+
+        // Adding a sparse and a dense column yields the same values in either order
+        let dense = ColumnValues::Dense(column(&[1, 2], 3).to_dense());
+        assert_eq!(lhs.clone() + &dense, dense.clone() + &lhs);
+        assert!(!is_sparse(&(lhs + &dense)));
+    }
+
+    #[test]
+    fn test_sparse_index_mut() {
+        let mut column = column(&[2, 5], 1);
+
+        // Stored rows are updated in place
+        column[5] = pallas::Scalar::from(3);
+        assert!(is_sparse(&column));
+        assert_eq!(column[5], pallas::Scalar::from(3));
+
+        // Writing to a row which is not stored converts the column to a dense one
+        column[4] = pallas::Scalar::from(7);
+        assert!(!is_sparse(&column));
+        assert_eq!(column[2], pallas::Scalar::ONE);
+        assert_eq!(column[4], pallas::Scalar::from(7));
+        assert_eq!(column[5], pallas::Scalar::from(3));
+    }
+
+    #[test]
+    fn test_sparse_commit() {
+        let params = ParamsIPA::<pallas::Affine>::new(4);
+        let blind = Blind(pallas::Scalar::random(OsRng));
+
+        let sparse = column(&[0, 9], 4);
+        assert!(is_sparse(&sparse));
+        let dense = ColumnValues::Dense(sparse.to_dense());
+        assert_eq!(
+            sparse.commit(&params, blind).to_affine(),
+            dense.commit(&params, blind).to_affine()
+        );
+
+        let zero = ColumnValues::<pallas::Scalar>::zero(NUM_ROWS);
+        assert_eq!(
+            zero.commit(&params, blind).to_affine(),
+            ColumnValues::Dense(zero.to_dense())
+                .commit(&params, blind)
+                .to_affine()
+        );
+    }
+
+    #[test]
+    fn test_sparse_columns() {
+        let [circuit0, circuit1] = range_check_circuits();
+        let fixture = fixture(9, [&circuit0, &circuit1], LookupChallenges::Shared);
+        let (params, pk) = (&fixture.params, &fixture.pk);
+        let [acc0, acc1] = fixture.accs;
+
+        // Columns of the proving key are read at every row, and are only committed to sparsely
+        assert!(pk.selectors.iter().all(|c| !is_sparse(&c.values)));
+
+        // The witness only occupies a few rows, and the table fills half of the trace
+        assert!(acc0.gate.advice.iter().all(|c| is_sparse(&c.values)));
+        assert!(is_sparse(&acc0.lookups[0].m.values));
+        assert!(!is_sparse(&acc0.lookups[0].h[0].values));
+        assert!(is_sparse(&acc0.beta.beta.powers.values));
+        assert!(is_sparse(&acc0.beta.beta_sqrt.powers.values));
+
+        let [mut dense0, mut dense1] = [acc0.clone(), acc1.clone()];
+        dense0.densify();
+        dense1.densify();
+        assert!(!is_sparse(&dense0.lookups[0].m.values));
+
+        // Both representations have the same values, commitments and error
+        assert_eq!(acc0, dense0);
+        assert!(Accumulator::decide(params, pk, &dense0));
+        assert_eq!(
+            Accumulator::error(pk, &acc0),
+            Accumulator::error(pk, &dense0)
+        );
+
+        // Folding sparse and dense accumulators sends the same error polynomial and yields the same result
+        let fold = |acc0, acc1| {
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            let acc = Accumulator::fold(pk, acc0, acc1, &mut transcript).unwrap();
+            (acc, transcript.finalize())
+        };
+        let (acc2, proof) = fold(acc0, acc1);
+        let (dense2, dense_proof) = fold(dense0, dense1);
+        assert_eq!(proof, dense_proof);
+        assert_eq!(acc2, dense2);
+        assert!(Accumulator::decide(params, pk, &acc2));
+        assert!(Accumulator::decide_batched(params, pk, &acc2, OsRng));
+    }
+}
@@ -1,4 +1,8 @@
-use std::ops::{Add, Mul};
+use std::{
+    io,
+    iter::zip,
+    ops::{Add, Mul},
+};
 
 use ff::Field;
 use group::Curve;
@@ -6,18 +10,23 @@ use halo2curves::CurveAffine;
 use rand_core::RngCore;
 
 use crate::{
-    arithmetic::parallelize,
+    arithmetic::best_multiexp,
+    helpers::{SerdeCurveAffine, SerdePrimeField},
     poly::{
-        commitment::{self, Blind, Params},
-        LagrangeCoeff, Polynomial,
+        commitment::{Blind, Params},
+        empty_lagrange, LagrangeCoeff, Polynomial,
     },
-    transcript::{EncodedChallenge, TranscriptWrite},
+    protostar::ProvingKey,
+    transcript::{EncodedChallenge, Transcript, TranscriptWrite},
+    SerdeFormat,
 };
 
+use super::column::ColumnValues;
+
 /// Represents a committed column sent that the verifier can query.
 #[derive(PartialEq, Debug, Clone)]
 pub struct Committed<C: CurveAffine> {
-    pub values: Polynomial<C::Scalar, LagrangeCoeff>,
+    pub values: ColumnValues<C::Scalar>,
     pub commitment: C,
     pub blind: Blind<C::Scalar>,
 }
@@ -47,17 +56,114 @@ impl<C: CurveAffine> Mul<C::Scalar> for Committed<C> {
 }
 
 impl<C: CurveAffine> Committed<C> {
-    /// Compute the linear combination (1−α)⋅ c₀ + α⋅c₁
-    pub(super) fn merge(alpha: C::Scalar, committed0: Self, committed1: Self) -> Self {
-        committed0 * (C::Scalar::ONE - alpha) + committed1 * alpha
+    /// Compute the linear combination ∑ₖ wₖ⋅cₖ with the given `weights`
+    pub(super) fn merge(weights: &[C::Scalar], committed: Vec<Self>) -> Self {
+        zip(committed, weights)
+            .map(|(committed, weight)| committed * *weight)
+            .reduce(|acc, committed| acc + committed)
+            .expect("at least one column should be merged")
     }
 
     /// Checks whether the commitment is valid with regards to the underlying column
     pub(super) fn decide<'params, P: Params<'params, C>>(&self, params: &P) -> bool {
-        let commitment = params.commit_lagrange(&self.values, self.blind).to_affine();
-        debug_assert_eq!(commitment, self.commitment);
+        let commitment = self.values.commit(params, self.blind).to_affine();
         commitment == self.commitment
     }
+
+    /// Checks whether all commitments are valid with regards to their underlying columns,
+    /// using a single commitment to a random linear combination of the columns.
+    /// For random scalars ρₖ, checks that commit(∑ₖ ρₖ⋅cₖ; ∑ₖ ρₖ⋅bₖ) == ∑ₖ ρₖ⋅Cₖ,
+    /// which fails w.h.p. if any commitment Cₖ does not match its column cₖ and blind bₖ.
+    pub(super) fn batch_decide<'a, 'params, P: Params<'params, C>, R: RngCore>(
+        params: &P,
+        committed: impl IntoIterator<Item = &'a Self>,
+        mut rng: R,
+    ) -> bool
+    where
+        C: 'a,
+    {
+        let committed: Vec<_> = committed.into_iter().collect();
+        if committed.is_empty() {
+            return true;
+        }
+        let rhos: Vec<_> = committed
+            .iter()
+            .map(|_| C::Scalar::random(&mut rng))
+            .collect();
+
+        let mut values = empty_lagrange(committed[0].values.len());
+        let mut blind = Blind(C::Scalar::ZERO);
+        for (committed, rho) in zip(&committed, &rhos) {
+            committed.values.add_scaled_to(*rho, &mut values);
+            blind += committed.blind * *rho;
+        }
+
+        let commitments: Vec<_> = committed.iter().map(|c| c.commitment).collect();
+        let expected = best_multiexp(&rhos, &commitments);
+        let commitment = params.commit_lagrange(&values, blind);
+        commitment == expected
+    }
+}
+
+impl<C: SerdeCurveAffine> Committed<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the values of the column, followed by its commitment and blind, to a buffer.
+    pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        self.values.write(writer, format)?;
+        self.commitment.write(writer, format)?;
+        self.blind.0.write(writer, format)
+    }
+
+    /// Reads a committed column from a buffer,
+    /// and checks that it contains as many values as there are rows in `pk`.
+    pub fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        Self::read_with_num_rows(reader, format, pk.num_rows)
+    }
+
+    /// Reads a committed column from a buffer, and checks that it contains `num_rows` values.
+    pub(in crate::protostar) fn read_with_num_rows<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        num_rows: usize,
+    ) -> io::Result<Self> {
+        let values = ColumnValues::read(reader, format)?;
+        if values.len() != num_rows {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Committed column does not have the expected number of rows",
+            ));
+        }
+        let commitment = C::read(reader, format)?;
+        let blind = Blind(C::Scalar::read(reader, format)?);
+        Ok(Self {
+            values,
+            commitment,
+            blind,
+        })
+    }
+
+    /// Writes a committed column to a vector of bytes using [`Self::write`].
+    pub fn to_bytes(&self, format: SerdeFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes, format)
+            .expect("Writing to vector should not fail");
+        bytes
+    }
+
+    /// Reads a committed column from a slice of bytes using [`Self::read`].
+    pub fn from_bytes(
+        mut bytes: &[u8],
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        Self::read(&mut bytes, format, pk)
+    }
 }
 
 /// Given a set of columns to be sent to the verifier, compute their commitments and write them to transcript.
@@ -75,8 +181,8 @@ pub fn batch_commit<
     columns: I,
     mut rng: R,
     transcript: &mut T,
-) -> Vec<Committed<C>> {
-    let columns: Vec<_> = columns.collect();
+) -> io::Result<Vec<Committed<C>>> {
+    let columns: Vec<_> = columns.map(ColumnValues::new).collect();
 
     let blinds: Vec<_> = columns
         .iter()
@@ -85,16 +191,16 @@ pub fn batch_commit<
     let commitments_projective: Vec<_> = columns
         .iter()
         .zip(blinds.iter())
-        .map(|(poly, blind)| params.commit_lagrange(poly, *blind))
+        .map(|(values, blind)| values.commit(params, *blind))
         .collect();
     let mut commitments_affine = vec![C::identity(); commitments_projective.len()];
     C::CurveExt::batch_normalize(&commitments_projective, &mut commitments_affine);
 
     for commitment in &commitments_affine {
-        let _ = transcript.write_point(*commitment);
+        transcript.write_point(*commitment)?;
     }
 
-    columns
+    Ok(columns
         .into_iter()
         .zip(commitments_affine.into_iter())
         .zip(blinds.into_iter())
@@ -103,7 +209,7 @@ pub fn batch_commit<
             commitment,
             blind,
         })
-        .collect()
+        .collect())
 }
 
 /// Given a set of columns to be sent to the verifier, compute their commitments and write them to transcript.
@@ -119,8 +225,8 @@ pub fn batch_commit_transparent<
     params: &P,
     columns: I,
     transcript: &mut T,
-) -> Vec<Committed<C>> {
-    let columns: Vec<_> = columns.collect();
+) -> io::Result<Vec<Committed<C>>> {
+    let columns: Vec<_> = columns.map(ColumnValues::new).collect();
 
     let blinds: Vec<_> = columns
         .iter()
@@ -129,16 +235,16 @@ pub fn batch_commit_transparent<
     let commitments_projective: Vec<_> = columns
         .iter()
         .zip(blinds.iter())
-        .map(|(poly, blind)| params.commit_lagrange(poly, *blind))
+        .map(|(values, blind)| values.commit(params, *blind))
         .collect();
     let mut commitments_affine = vec![C::identity(); commitments_projective.len()];
     C::CurveExt::batch_normalize(&commitments_projective, &mut commitments_affine);
 
     for commitment in &commitments_affine {
-        let _ = transcript.write_point(*commitment);
+        transcript.write_point(*commitment)?;
     }
 
-    columns
+    Ok(columns
         .into_iter()
         .zip(commitments_affine.into_iter())
         .zip(blinds.into_iter())
@@ -147,7 +253,7 @@ pub fn batch_commit_transparent<
             commitment,
             blind,
         })
-        .collect()
+        .collect())
 }
 
 /// Compute a single blinded commitment and write it to the transcript
@@ -163,16 +269,17 @@ pub fn commit<
     values: Polynomial<C::Scalar, LagrangeCoeff>,
     mut rng: R,
     transcript: &mut T,
-) -> Committed<C> {
+) -> io::Result<Committed<C>> {
     let blind = Blind(C::Scalar::random(&mut rng));
-    let commitment = params.commit_lagrange(&values, blind).to_affine();
+    let values = ColumnValues::new(values);
+    let commitment = values.commit(params, blind).to_affine();
 
-    let _ = transcript.write_point(commitment);
-    Committed {
+    transcript.write_point(commitment)?;
+    Ok(Committed {
         values,
         commitment,
         blind,
-    }
+    })
 }
 
 /// Compute a single transparent commitment and write it to the transcript
@@ -186,14 +293,58 @@ pub fn commit_transparent<
     params: &P,
     values: Polynomial<C::Scalar, LagrangeCoeff>,
     transcript: &mut T,
-) -> Committed<C> {
+) -> io::Result<Committed<C>> {
     let blind = Blind(C::Scalar::default());
-    let commitment = params.commit_lagrange(&values, blind).to_affine();
+    let values = ColumnValues::new(values);
+    let commitment = values.commit(params, blind).to_affine();
 
-    let _ = transcript.write_point(commitment);
-    Committed {
+    transcript.write_point(commitment)?;
+    Ok(Committed {
         values,
         commitment,
         blind,
+    })
+}
+
+/// Given a set of columns known by the verifier, compute their commitments and add them to the transcript
+/// as common inputs, without writing them to the proof.
+/// Commitments are transparent using a default blinding value.
+/// Commitments to the identity cannot be added to the transcript and are skipped.
+pub fn batch_commit_public<
+    'params,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    I: Iterator<Item = Polynomial<C::Scalar, LagrangeCoeff>>,
+    E: EncodedChallenge<C>,
+    T: Transcript<C, E>,
+>(
+    params: &P,
+    columns: I,
+    transcript: &mut T,
+) -> io::Result<Vec<Committed<C>>> {
+    let columns: Vec<_> = columns.map(ColumnValues::new).collect();
+
+    let blind = Blind::default();
+    let commitments_projective: Vec<_> = columns
+        .iter()
+        .map(|values| values.commit(params, blind))
+        .collect();
+    let mut commitments_affine = vec![C::identity(); commitments_projective.len()];
+    C::CurveExt::batch_normalize(&commitments_projective, &mut commitments_affine);
+
+    for commitment in &commitments_affine {
+        if !bool::from(commitment.is_identity()) {
+            transcript.common_point(*commitment)?;
+        }
     }
+
+    Ok(columns
+        .into_iter()
+        .zip(commitments_affine.into_iter())
+        .map(|(values, commitment)| Committed {
+            values,
+            commitment,
+            blind,
+        })
+        .collect())
 }
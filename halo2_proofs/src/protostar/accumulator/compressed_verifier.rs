@@ -1,99 +1,254 @@
-use std::iter::zip;
-
-use super::committed::{commit_transparent, Committed};
-use crate::{
-    arithmetic::parallelize,
-    poly::{
-        commitment::{Blind, CommitmentScheme, Params},
-        Polynomial,
-    },
-    transcript::{EncodedChallenge, TranscriptWrite},
+use super::{
+    column::ColumnValues,
+    committed::{commit_transparent, Committed},
+    linear_combination,
 };
 use crate::{
-    poly::{empty_lagrange, LagrangeCoeff},
-    protostar::keygen::ProvingKey,
+    helpers::{SerdeCurveAffine, SerdePrimeField},
+    poly::{commitment::Blind, commitment::Params},
+    transcript::{EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
 };
+use crate::{poly::empty_lagrange, protostar::keygen::ProvingKey};
 use ff::Field;
-use group::Curve;
 use halo2curves::CurveAffine;
+use std::{io, iter::zip};
 
 /// Once all instance, advice and lookup witnesses have been sent to the verifier,
 /// the Beta transcript allows all constraints to be batched into a single one.
-/// The verifer sends a random value beta, and the prover commits to the vector of powers of beta.
-/// The constraint to be checked becomes ∑ᵢ βᵢ ⋅ Gᵢ.
-/// We need to compute the committed error vector for checking the correctness of beta, but
-/// we can let the verifier compute it.
+/// The verifer sends a random value beta, and the constraint to be checked becomes ∑ᵢ βᵢ ⋅ Gᵢ.
+///
+/// Rather than committing to all n powers of beta, we follow the compressed verifier of Protostar,
+/// and split the index of each row as i = i' + m⋅i'', where m = 2^⌈log(n)/2⌉ is the `sqrt_stride`.
+/// The prover commits to two vectors of size √n
+/// - β'ᵢ = βⁱ for i = 0, ..., m-1, stored in the first m rows of the column,
+/// - β''ⱼ = β^{m⋅j} for j = 0, ..., n/m-1, stored in the rows m⋅j of the column,
+///
+/// such that βᵢ = β'ᵢ mod m ⋅ β''ᵢ / m.
+/// We need to compute the committed error vectors for checking the correctness of β' and β'',
+/// but we can let the verifier compute them.
 #[derive(PartialEq, Debug, Clone)]
 pub struct Transcript<C: CurveAffine> {
-    pub beta: Committed<C>,
+    pub beta: Powers<C>,
+    pub beta_sqrt: Powers<C>,
+}
+
+/// Committed vector a of powers of `challenge`, whose entries are spaced s rows apart,
+/// along with the committed error vector eᵢ = challenge ⋅ aᵢ − aᵢ₊ₛ.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Powers<C: CurveAffine> {
+    pub challenge: C::Scalar,
+    pub powers: Committed<C>,
     pub error: Committed<C>,
 }
 
+/// Returns the stride m = 2^⌈k/2⌉ for a column with n = 2ᵏ rows,
+/// such that β' has size m and β'' has size n/m.
+pub fn sqrt_stride(num_rows: usize) -> usize {
+    let k = num_rows.trailing_zeros();
+    1 << ((k + 1) / 2)
+}
+
 impl<C: CurveAffine> Transcript<C> {
     /// Runs the final IOP protocol to generate beta,
-    /// and commit to the vector with the powers of beta.
+    /// and commit to the vectors β' and β'' of powers of beta.
     pub fn new<'params, P: Params<'params, C>, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
         params: &P,
         transcript: &mut T,
-    ) -> Self {
-        let n = params.n();
-
+    ) -> io::Result<Self> {
         let beta = *transcript.squeeze_challenge_scalar::<C::Scalar>();
+        Self::new_with_challenge(params, beta, transcript)
+    }
+
+    /// Commits to the vectors β' and β'' of powers of the given `beta`.
+    pub(super) fn new_with_challenge<
+        'params,
+        P: Params<'params, C>,
+        E: EncodedChallenge<C>,
+        T: TranscriptWrite<C, E>,
+    >(
+        params: &P,
+        beta: C::Scalar,
+        transcript: &mut T,
+    ) -> io::Result<Self> {
+        let n = params.n() as usize;
+        let stride = sqrt_stride(n);
+
+        let beta_sqrt = beta.pow_vartime([stride as u64]);
+
+        let beta = Powers::new(params, beta, 1, stride, transcript)?;
+        let beta_sqrt = Powers::new(params, beta_sqrt, stride, n / stride, transcript)?;
+
+        Ok(Self { beta, beta_sqrt })
+    }
+
+    /// Computes the linear combination ∑ₖ wₖ⋅txₖ with the given `weights`, where the error vectors
+    /// are given by the evaluation of the error polynomial.
+    pub(super) fn merge(weights: &[C::Scalar], transcripts: Vec<Self>) -> Self {
+        let (beta, beta_sqrt): (Vec<_>, Vec<_>) = transcripts
+            .into_iter()
+            .map(|transcript| (transcript.beta, transcript.beta_sqrt))
+            .unzip();
+        let beta = Powers::merge(weights, beta);
+        let beta_sqrt = Powers::merge(weights, beta_sqrt);
+        Self { beta, beta_sqrt }
+    }
+
+    /// Returns βᵢ = β'ᵢ mod m ⋅ β''ᵢ / m
+    pub fn beta_at(&self, row: usize, stride: usize) -> C::Scalar {
+        self.beta.powers.values[row % stride] * self.beta_sqrt.powers.values[row - row % stride]
+    }
+
+    /// Checks the correctness of β' and β'' with respect to their error vectors,
+    /// and returns the rows at which the constraints of each of them are not satisfied.
+    pub(super) fn unsatisfied_rows(&self, pk: &ProvingKey<C>) -> [Vec<usize>; 2] {
+        let n = pk.num_rows;
+        let stride = sqrt_stride(n);
+        [
+            self.beta.unsatisfied_rows(1, stride),
+            self.beta_sqrt.unsatisfied_rows(stride, n / stride),
+        ]
+    }
+}
+
+impl<C: SerdeCurveAffine> Transcript<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the vectors of powers β' and β'' to a buffer.
+    pub(super) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.beta.write(writer, format)?;
+        self.beta_sqrt.write(writer, format)
+    }
+
+    /// Reads the vectors of powers β' and β'' from a buffer.
+    pub(super) fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let beta = Powers::read(reader, format, pk)?;
+        let beta_sqrt = Powers::read(reader, format, pk)?;
+        Ok(Self { beta, beta_sqrt })
+    }
+}
+
+impl<C: SerdeCurveAffine> Powers<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        self.challenge.write(writer, format)?;
+        self.powers.write(writer, format)?;
+        self.error.write(writer, format)
+    }
+
+    fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let challenge = C::Scalar::read(reader, format)?;
+        let powers = Committed::read(reader, format, pk)?;
+        let error = Committed::read(reader, format, pk)?;
+        Ok(Self {
+            challenge,
+            powers,
+            error,
+        })
+    }
+}
+
+impl<C: CurveAffine> Powers<C> {
+    /// Commits to the `len` first powers of `challenge`, placed `stride` rows apart.
+    fn new<'params, P: Params<'params, C>, E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
+        params: &P,
+        challenge: C::Scalar,
+        stride: usize,
+        len: usize,
+        transcript: &mut T,
+    ) -> io::Result<Self> {
+        let n = params.n() as usize;
 
-        // Vector of powers of `beta`
-        let mut beta_values = empty_lagrange(n as usize);
-        parallelize(&mut beta_values, |o, start| {
-            let mut cur = beta.pow_vartime(&[start as u64]);
-            for v in o.iter_mut() {
-                *v = cur;
-                cur *= &beta;
-            }
-        });
+        let mut values = empty_lagrange(n);
+        let mut cur = C::Scalar::ONE;
+        for value in values.iter_mut().step_by(stride).take(len) {
+            *value = cur;
+            cur *= challenge;
+        }
 
         // No need to blind since the contents are known by the verifier
-        let committed = commit_transparent(params, beta_values, transcript);
+        let powers = commit_transparent(params, values, transcript)?;
 
         // During the creation of an initial accumulator, the error is expecte to be zero
         // It does not need to be committed since the verifier will set the commitment to the identity.
         let error = Committed {
-            values: empty_lagrange(n as usize),
+            values: ColumnValues::zero(n),
             commitment: C::identity(),
             blind: Blind(C::Scalar::ZERO),
         };
 
-        Self {
-            beta: committed,
+        Ok(Self {
+            challenge,
+            powers,
             error,
-        }
+        })
     }
 
-    pub(super) fn merge(alpha: C::Scalar, transcript0: Self, transcript1: Self) -> Self {
-        // Get the actual beta challenge from the vectors
-        let beta0 = transcript0.beta.values[1];
-        let beta1 = transcript1.beta.values[1];
-        // Compute the linear interpolation of both beta values
-        let beta = Committed::merge(alpha, transcript0.beta.clone(), transcript1.beta.clone());
-
+    fn merge(weights: &[C::Scalar], powers: Vec<Self>) -> Self {
         // The error is given by e = r⋅a - b, where r is a challenge and a,b are vectors.
         // More precisely in this context,
-        // - r is beta
-        // - a is the vector of powers of beta
-        // - b is a shifted by 1, s.t. b[i-1] = a[i] (only check from 1 onwards)
+        // - r is the challenge
+        // - a is the vector of powers of the challenge
+        // - b is a shifted by the stride, s.t. b[i-stride] = a[i] (only check from stride onwards)
         //
-        // We are given two transcripts, where
-        // - e0 = r0⋅a0 - b0
-        // - e1 = r1⋅a1 - b1
-        //
-        // The error polynomial is given by
-        // e(t) = ((1-t)⋅r0 + t⋅r1)⋅((1-t)⋅a0 + t⋅a1) - ((1-t)⋅b0 + t⋅b1)
-        // It is (almost) trivial to see, that
-        // e(t) = (1-t)⋅e0 + t⋅e1 + (1-t)t⋅[(r1-r0)⋅a0 + (r0-r1)⋅a1]
+        // We are given N transcripts, where eₖ = rₖ⋅aₖ - bₖ.
+        // Interpolating each of them with the weights wₖ = Lₖ(t) (for two transcripts, w₀ = 1-t and w₁ = t),
+        // the error polynomial is given by
+        // e(t) = (∑ₖ wₖ⋅rₖ)⋅(∑ₖ wₖ⋅aₖ) - ∑ₖ wₖ⋅bₖ
+        // Substituting bₖ = rₖ⋅aₖ - eₖ, and writing r = ∑ₖ wₖ⋅rₖ for the new challenge, we get
+        // e(t) = ∑ₖ wₖ⋅eₖ + ∑ₖ wₖ⋅(r − rₖ)⋅aₖ
+        // For two transcripts, this is equal to (1-t)⋅e0 + t⋅e1 + (1-t)t⋅[(r1-r0)⋅a0 + (r0-r1)⋅a1].
         // Therefore, the new error is given by evaluating the above polynomial in t = alpha.
-        let error = transcript0.error * (C::Scalar::ONE - alpha)
-            + transcript1.error * alpha
-            + (transcript0.beta * (beta1 - beta0) + transcript1.beta * (beta0 - beta1))
-                * (alpha * (C::Scalar::ONE - alpha));
+        let challenge = linear_combination(weights, powers.iter().map(|powers| powers.challenge));
+
+        let mut errors = Vec::with_capacity(2 * powers.len());
+        let mut error_weights = Vec::with_capacity(2 * powers.len());
+        let mut columns = Vec::with_capacity(powers.len());
+        for (powers, weight) in zip(powers, weights) {
+            errors.push(powers.error);
+            error_weights.push(*weight);
+            errors.push(powers.powers.clone());
+            error_weights.push(*weight * (challenge - powers.challenge));
+            columns.push(powers.powers);
+        }
+        let error = Committed::merge(&error_weights, errors);
+        let powers = Committed::merge(weights, columns);
+
+        Self {
+            challenge,
+            powers,
+            error,
+        }
+    }
+
+    /// Checks the constraints eᵢ ≡ r ⋅ aᵢ − aᵢ₊ₛ and a₀ ≡ 1 over the `len` entries of the vector,
+    /// where s is the `stride`, and returns the rows i at which they are not satisfied.
+    fn unsatisfied_rows(&self, stride: usize, len: usize) -> Vec<usize> {
+        let powers = &self.powers.values;
+        let error = &self.error.values;
 
-        Self { beta, error }
+        let init_ok = powers[0] == C::Scalar::ONE;
+        (0..len - 1)
+            .map(|j| j * stride)
+            .filter(|&i| {
+                let powers_ok = error[i] == powers[i] * self.challenge - powers[i + stride];
+                !powers_ok || (i == 0 && !init_ok)
+            })
+            .collect()
     }
 }
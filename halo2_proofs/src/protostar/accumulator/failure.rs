@@ -0,0 +1,102 @@
+use std::fmt;
+
+use crate::protostar::ColumnLabel;
+
+/// A check of the decider which is not satisfied by an accumulator,
+/// as returned by `Accumulator::decide_detailed`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecideError<F> {
+    /// The commitment to `column` does not match its values and blind.
+    Commitment {
+        /// The column whose commitment is invalid.
+        column: ColumnLabel,
+    },
+    /// The error term of the accumulator differs from the one recomputed from its columns.
+    /// The recomputed error is the sum of the contributions ∑ᵢ βᵢ⋅∑ⱼ yⱼ⋅Gⱼ,ᵢ of the constraints Gⱼ
+    /// defined by each gate, lookup table and shuffle argument, and by the permutation argument.
+    Error {
+        /// The error term stored in the accumulator.
+        expected: F,
+        /// The error term recomputed from the columns of the accumulator.
+        computed: F,
+        /// The contribution of the constraints of each gate, along with its name.
+        gates: Vec<(String, F)>,
+        /// The contribution of the constraints of each lookup table, along with the names of its arguments.
+        lookups: Vec<(String, F)>,
+        /// The contribution of the constraints of each shuffle argument, along with its name.
+        shuffles: Vec<(String, F)>,
+        /// The contribution of the constraints of the permutation argument.
+        permutation: F,
+    },
+    /// The linear constraint ∑ᵢ gᵢ = ∑ₖ,ᵢ hₖ,ᵢ of a lookup table is not satisfied.
+    Lookup {
+        /// The index of the lookup table, as given by `ProvingKey::lookup_tables`.
+        index: usize,
+        /// The names of the lookup arguments into the table.
+        name: String,
+    },
+    /// The linear constraint ∑ᵢ gᵢ = ∑ᵢ hᵢ of a shuffle argument is not satisfied.
+    Shuffle {
+        /// The index of the shuffle argument in the constraint system.
+        index: usize,
+        /// The name of the shuffle argument.
+        name: String,
+    },
+    /// The linear constraint ∑ⱼ,ᵢ hⱼ,ᵢ = ∑ⱼ,ᵢ gⱼ,ᵢ of the permutation argument is not satisfied.
+    Permutation,
+    /// The vector of powers in `column` does not satisfy eᵢ = β⋅βᵢ − βᵢ₊ₛ, or β₀ = 1, at `row`.
+    Beta {
+        /// Either `ColumnLabel::Beta` or `ColumnLabel::BetaSqrt`.
+        column: ColumnLabel,
+        /// The row at which the relation is not satisfied.
+        row: usize,
+    },
+}
+
+impl<F: fmt::Debug> fmt::Display for DecideError<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Commitment { column } => {
+                write!(f, "Commitment to column {:?} is invalid", column)
+            }
+            Self::Error {
+                expected,
+                computed,
+                gates,
+                lookups,
+                shuffles,
+                permutation,
+            } => {
+                writeln!(
+                    f,
+                    "Error term {:?} does not match the recomputed error {:?}, with contributions",
+                    expected, computed
+                )?;
+                for (name, contribution) in gates {
+                    writeln!(f, "- gate '{}': {:?}", name, contribution)?;
+                }
+                for (name, contribution) in lookups {
+                    writeln!(f, "- lookup '{}': {:?}", name, contribution)?;
+                }
+                for (name, contribution) in shuffles {
+                    writeln!(f, "- shuffle '{}': {:?}", name, contribution)?;
+                }
+                write!(f, "- permutation: {:?}", permutation)
+            }
+            Self::Lookup { index, name } => {
+                write!(f, "Lookup {} ('{}'): ∑ g ≠ ∑ h", index, name)
+            }
+            Self::Shuffle { index, name } => {
+                write!(f, "Shuffle {} ('{}'): ∑ g ≠ ∑ h", index, name)
+            }
+            Self::Permutation => write!(f, "Permutation: ∑ h ≠ ∑ g"),
+            Self::Beta { column, row } => {
+                write!(
+                    f,
+                    "Powers in column {:?} are invalid at row {}",
+                    column, row
+                )
+            }
+        }
+    }
+}
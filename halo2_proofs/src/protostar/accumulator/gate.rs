@@ -1,5 +1,6 @@
 use std::{
     collections::{BTreeMap, BTreeSet, HashMap},
+    io,
     iter::zip,
     ops::RangeTo,
 };
@@ -9,7 +10,9 @@ use halo2curves::CurveAffine;
 use rand_core::RngCore;
 
 use crate::{
+    arithmetic::powers,
     circuit::{layouter::SyncDeps, Value},
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
     plonk::{
         circuit::FloorPlanner,
         sealed::{self, SealedPhase},
@@ -21,11 +24,15 @@ use crate::{
         commitment::{Blind, Params},
         empty_lagrange, empty_lagrange_assigned, LagrangeCoeff, Polynomial,
     },
-    protostar::{accumulator::committed::batch_commit, ProvingKey},
+    protostar::{
+        accumulator::committed::{batch_commit, batch_commit_public},
+        ProvingKey,
+    },
     transcript::{EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
 };
 
-use super::committed::Committed;
+use super::{column::ColumnValues, committed::Committed, linear_combination, transpose};
 
 /// A gate transcript is the result of running the IOP for several rounds,
 /// where in each round, the prover sends commitments to one or more advice columns
@@ -36,7 +43,8 @@ use super::committed::Committed;
 pub struct Transcript<C: CurveAffine> {
     pub instance: Vec<Committed<C>>,
     pub advice: Vec<Committed<C>>,
-    pub challenges: Vec<C::Scalar>,
+    // challenges[i] = [cᵢ, cᵢ², …, cᵢᵈ], where d is the largest power of cᵢ in the constraints
+    pub challenges: Vec<Vec<C::Scalar>>,
 }
 
 impl<C: CurveAffine> Transcript<C> {
@@ -82,7 +90,7 @@ impl<C: CurveAffine> Transcript<C> {
                     // TODO(@adr1anh): Allocate only the required size for each column
                     let mut column = empty_lagrange(n);
 
-                    if values.len() > (column.len() - (meta.blinding_factors() + 1)) {
+                    if values.len() > n {
                         return Err(Error::InstanceTooLarge);
                     }
                     for (v, value) in zip(column.iter_mut(), values.iter()) {
@@ -92,9 +100,11 @@ impl<C: CurveAffine> Transcript<C> {
                 })
                 .collect::<Result<Vec<_>, _>>()?;
 
-            // TODO(@adr1anh): Add support for query instance
-            // if !P::QUERY_INSTANCE
-            {
+            if pk.query_instance() {
+                // For large instances, the verifier only folds the commitments,
+                // which it can compute itself from the public values.
+                batch_commit_public(params, instance_columns.into_iter(), transcript)?
+            } else {
                 // The instance is part of the transcript
                 for &instance in instances {
                     for value in instance {
@@ -105,16 +115,12 @@ impl<C: CurveAffine> Transcript<C> {
                 instance_columns
                     .into_iter()
                     .map(|column| Committed {
-                        values: column,
+                        values: ColumnValues::new(column),
                         commitment: C::identity(),
                         blind: Blind(C::Scalar::default()),
                     })
                     .collect()
             }
-            // else {
-            // // For large instances, we send a commitment to it and open it with PCS
-            // batch_commit_transparent(params, instance_columns.into_iter(), transcript);
-            // }
         };
 
         // Synthesize the circuit over multiple iterations
@@ -122,8 +128,6 @@ impl<C: CurveAffine> Transcript<C> {
         let mut advice_committed = BTreeMap::<usize, Committed<C>>::new();
         let mut challenges = HashMap::<usize, C::Scalar>::with_capacity(meta.num_challenges);
 
-        let unusable_rows_start = params.n() as usize - (meta.blinding_factors() + 1);
-
         let instances = instance
             .iter()
             .map(|committed| &committed.values)
@@ -136,11 +140,9 @@ impl<C: CurveAffine> Transcript<C> {
             advice: &mut advice_assigned,
             instances,
             challenges: &mut challenges,
-            // The prover will not be allowed to assign values to advice
-            // cells that exist within inactive rows, which include some
-            // number of blinding factors and an extra row for use in the
-            // permutation argument.
-            usable_rows: ..unusable_rows_start,
+            // Folding does not reserve rows for blinding factors,
+            // so the prover may assign values to all rows of the trace.
+            usable_rows: ..pk.num_rows,
             _marker: std::marker::PhantomData,
         };
 
@@ -168,29 +170,22 @@ impl<C: CurveAffine> Transcript<C> {
                 meta.constants.clone(),
             )?;
 
-            let mut advice_values = batch_invert_assigned::<C::Scalar>(
-                witness
-                    .advice
+            // Columns of the current phase are not assigned in later phases,
+            // so they can be moved out of the witness rather than cloned.
+            let advice_values = batch_invert_assigned::<C::Scalar>(
+                column_indices
                     .iter()
-                    .enumerate()
-                    .filter_map(|(column_index, advice)| {
-                        if column_indices.contains(&column_index) {
-                            Some(advice.clone())
-                        } else {
-                            None
-                        }
+                    .map(|column_index| {
+                        std::mem::replace(
+                            &mut witness.advice[*column_index],
+                            empty_lagrange_assigned(0),
+                        )
                     })
                     .collect(),
             );
 
-            // Add blinding factors to advice columns
-            for advice_values in &mut advice_values {
-                for cell in &mut advice_values[unusable_rows_start..] {
-                    *cell = C::Scalar::random(&mut rng);
-                }
-            }
-
-            let committed = batch_commit(params, advice_values.into_iter(), &mut rng, transcript);
+            // The commitments are hiding, so the columns do not need to be padded with random values
+            let committed = batch_commit(params, advice_values.into_iter(), &mut rng, transcript)?;
 
             for (column_index, committed) in column_indices.iter().zip(committed) {
                 advice_committed.insert(*column_index, committed);
@@ -207,8 +202,12 @@ impl<C: CurveAffine> Transcript<C> {
         }
 
         assert_eq!(challenges.len(), meta.num_challenges);
-        let challenges = (0..meta.num_challenges)
-            .map(|index| challenges.remove(&index).unwrap())
+        // Compute the powers of each challenge which are substituted in the constraints
+        let challenges = zip(0..meta.num_challenges, pk.max_challenge_powers())
+            .map(|(index, max_power)| {
+                let challenge = challenges.remove(&index).unwrap();
+                powers(challenge).skip(1).take(*max_power).collect()
+            })
             .collect::<Vec<_>>();
 
         Ok(Transcript {
@@ -218,27 +217,36 @@ impl<C: CurveAffine> Transcript<C> {
         })
     }
 
-    /// Computes the linear combination (1−α)⋅tx₀ + α⋅tx₁
-    pub(super) fn merge(alpha: C::Scalar, transcript0: Self, transcript1: Self) -> Self {
-        let advice = zip(
-            transcript0.advice.into_iter(),
-            transcript1.advice.into_iter(),
-        )
-        .map(|(committed0, committed1)| Committed::merge(alpha, committed0, committed1))
-        .collect();
-        let instance = zip(
-            transcript0.instance.into_iter(),
-            transcript1.instance.into_iter(),
-        )
-        .map(|(committed0, committed1)| Committed::merge(alpha, committed0, committed1))
-        .collect();
-
-        let challenges = zip(
-            transcript0.challenges.into_iter(),
-            transcript1.challenges.into_iter(),
-        )
-        .map(|(challenge0, challenge1)| challenge0 + (challenge1 - challenge0) * &alpha)
-        .collect();
+    /// Computes the linear combination ∑ₖ wₖ⋅txₖ with the given `weights`
+    pub(super) fn merge(weights: &[C::Scalar], transcripts: Vec<Self>) -> Self {
+        let mut advice = Vec::with_capacity(transcripts.len());
+        let mut instance = Vec::with_capacity(transcripts.len());
+        let mut challenges = Vec::with_capacity(transcripts.len());
+        for transcript in transcripts {
+            advice.push(transcript.advice);
+            instance.push(transcript.instance);
+            challenges.push(transcript.challenges);
+        }
+
+        let advice = transpose(advice)
+            .into_iter()
+            .map(|committed| Committed::merge(weights, committed))
+            .collect();
+        let instance = transpose(instance)
+            .into_iter()
+            .map(|committed| Committed::merge(weights, committed))
+            .collect();
+
+        // Each power of a challenge is merged as an independent challenge
+        let challenges = transpose(challenges)
+            .into_iter()
+            .map(|powers| {
+                transpose(powers)
+                    .into_iter()
+                    .map(|challenges| linear_combination(weights, challenges))
+                    .collect()
+            })
+            .collect();
         Self {
             instance,
             advice,
@@ -247,13 +255,53 @@ impl<C: CurveAffine> Transcript<C> {
     }
 }
 
+impl<C: SerdeCurveAffine> Transcript<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the instance and advice columns, followed by the powers of each challenge, to a buffer.
+    pub(super) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        write_slice(&self.instance, writer, |committed, writer| {
+            committed.write(writer, format)
+        })?;
+        write_slice(&self.advice, writer, |committed, writer| {
+            committed.write(writer, format)
+        })?;
+        write_slice(&self.challenges, writer, |powers, writer| {
+            write_slice(powers, writer, |power, writer| power.write(writer, format))
+        })
+    }
+
+    /// Reads a gate transcript from a buffer.
+    pub(super) fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let instance = read_vec(reader, |reader| Committed::read(reader, format, pk))?;
+        let advice = read_vec(reader, |reader| Committed::read(reader, format, pk))?;
+        let challenges = read_vec(reader, |reader| {
+            read_vec(reader, |reader| C::Scalar::read(reader, format))
+        })?;
+        Ok(Self {
+            instance,
+            advice,
+            challenges,
+        })
+    }
+}
+
 /// Cache for storing the evaluated witness data during all phases of the advice generation.
 struct WitnessCollection<'a, F: Field> {
     k: u32,
     current_phase: sealed::Phase,
     advice: &'a mut Vec<Polynomial<Assigned<F>, LagrangeCoeff>>,
     challenges: &'a mut HashMap<usize, F>,
-    instances: Vec<&'a Polynomial<F, LagrangeCoeff>>,
+    instances: Vec<&'a ColumnValues<F>>,
     usable_rows: RangeTo<usize>,
     _marker: std::marker::PhantomData<F>,
 }
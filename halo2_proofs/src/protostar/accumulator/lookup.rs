@@ -1,46 +1,103 @@
 use core::num;
-use std::{collections::HashMap, iter::zip, ops::Range};
+use std::{
+    collections::HashMap,
+    hash::{Hash, Hasher},
+    io,
+    iter::zip,
+};
 
 use crate::{
-    arithmetic::powers,
-    plonk::Expression,
+    arithmetic::{parallelize, powers},
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
+    plonk::{lookup::Argument, Error, Expression},
     poly::{commitment::Params, Rotation},
-    protostar::ProvingKey,
-    transcript::{EncodedChallenge, TranscriptWrite},
+    protostar::{LookupChallenges, ProvingKey},
+    transcript::{self, EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
 };
 use ff::PrimeField;
 use ff::{BatchInvert, Field};
 use halo2curves::CurveAffine;
 use rand_core::RngCore;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
-use super::committed::{batch_commit, commit, Committed};
+use super::{
+    column::ColumnValues,
+    committed::{batch_commit, commit, Committed},
+    linear_combination, transpose,
+};
 
+/// Columns and challenges of the logUp argument for a lookup table,
+/// with a column `h[k]` for the input of each argument k into the table.
 #[derive(PartialEq, Debug, Clone)]
 pub struct Transcript<C: CurveAffine> {
     pub m: Committed<C>,
     pub r: C::Scalar,
     pub thetas: Vec<C::Scalar>,
     pub g: Committed<C>,
-    pub h: Committed<C>,
+    pub h: Vec<Committed<C>>,
 }
 
 impl<C: CurveAffine> Transcript<C> {
-    pub(super) fn merge(alpha: C::Scalar, transcript0: Self, transcript1: Self) -> Self {
-        let m = Committed::merge(alpha, transcript0.m, transcript1.m);
-        let g = Committed::merge(alpha, transcript0.g, transcript1.g);
-        let h = Committed::merge(alpha, transcript0.h, transcript1.h);
-
-        let r = (transcript1.r - transcript0.r) * alpha + transcript0.r;
-        let thetas = zip(
-            transcript0.thetas.into_iter(),
-            transcript1.thetas.into_iter(),
-        )
-        .map(|(challenge0, challenge1)| (challenge1 - challenge0) * alpha + challenge0)
-        .collect();
+    /// Computes the linear combination ∑ₖ wₖ⋅txₖ with the given `weights`
+    pub(super) fn merge(weights: &[C::Scalar], transcripts: Vec<Self>) -> Self {
+        let r = linear_combination(weights, transcripts.iter().map(|tx| tx.r));
+        let thetas = transpose(transcripts.iter().map(|tx| tx.thetas.clone()).collect())
+            .into_iter()
+            .map(|thetas| linear_combination(weights, thetas))
+            .collect();
+
+        let mut m = Vec::with_capacity(transcripts.len());
+        let mut g = Vec::with_capacity(transcripts.len());
+        let mut h = Vec::with_capacity(transcripts.len());
+        for transcript in transcripts {
+            m.push(transcript.m);
+            g.push(transcript.g);
+            h.push(transcript.h);
+        }
+        let [m, g] = [m, g].map(|committed| Committed::merge(weights, committed));
+        let h = transpose(h)
+            .into_iter()
+            .map(|h| Committed::merge(weights, h))
+            .collect();
         Self { m, r, thetas, g, h }
     }
 }
 
+impl<C: SerdeCurveAffine> Transcript<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the multiplicities m, the challenges r and θ, and the columns g and hₖ to a buffer.
+    pub(super) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.m.write(writer, format)?;
+        self.r.write(writer, format)?;
+        write_slice(&self.thetas, writer, |theta, writer| {
+            theta.write(writer, format)
+        })?;
+        self.g.write(writer, format)?;
+        write_slice(&self.h, writer, |h, writer| h.write(writer, format))
+    }
+
+    /// Reads a lookup transcript from a buffer.
+    pub(super) fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let m = Committed::read(reader, format, pk)?;
+        let r = C::Scalar::read(reader, format)?;
+        let thetas = read_vec(reader, |reader| C::Scalar::read(reader, format))?;
+        let g = Committed::read(reader, format, pk)?;
+        let h = read_vec(reader, |reader| Committed::read(reader, format, pk))?;
+        Ok(Self { m, r, thetas, g, h })
+    }
+}
+
 pub fn new<
     'params,
     C: CurveAffine,
@@ -54,219 +111,290 @@ pub fn new<
     gate_tx: &super::gate::Transcript<C>,
     mut rng: R,
     transcript: &mut T,
-) -> Vec<Transcript<C>> {
-    let selectors = pk
-        .selectors
-        .iter()
-        .map(|c| c.values.as_ref())
-        .collect::<Vec<_>>();
-    let fixed = pk
-        .fixed
-        .iter()
-        .map(|c| c.values.as_ref())
-        .collect::<Vec<_>>();
+) -> Result<Vec<Transcript<C>>, Error> {
+    // The witness columns are indexed by the expressions of the constraint system
+    if gate_tx.instance.len() != pk.cs.num_instance_columns
+        || gate_tx.advice.len() != pk.cs.num_advice_columns
+        || gate_tx.challenges.len() != pk.cs.num_challenges
+    {
+        return Err(Error::AccumulatorShapeMismatch);
+    }
+
+    let selectors = pk.selectors.iter().map(|c| &c.values).collect::<Vec<_>>();
+    let fixed = pk.fixed.iter().map(|c| &c.values).collect::<Vec<_>>();
     let instance = gate_tx
         .instance
         .iter()
-        .map(|c| c.values.as_ref())
+        .map(|c| &c.values)
         .collect::<Vec<_>>();
-    let challenges = &gate_tx.challenges;
-    let advice = gate_tx
-        .advice
+    // Witness generation only requires the challenges, rather than their powers
+    let challenges = &gate_tx
+        .challenges
         .iter()
-        .map(|c| c.values.as_ref())
+        .map(|powers| powers[0])
         .collect::<Vec<_>>();
-
-    let m_columns = pk
-        .cs
-        .lookups
+    let advice = gate_tx.advice.iter().map(|c| &c.values).collect::<Vec<_>>();
+
+    // Evaluate the table and input expressions of each lookup table once,
+    // since they are used for computing all of m, g and h.
+    let evaluate_columns = |exprs: &[Expression<C::Scalar>]| {
+        exprs
+            .iter()
+            .map(|expr| {
+                evaluate_column(
+                    expr,
+                    pk.num_rows,
+                    &selectors,
+                    &fixed,
+                    &instance,
+                    &advice,
+                    challenges,
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    let tables: Vec<_> = pk
+        .lookup_tables()
         .iter()
-        .map(|lookup| {
-            pk.domain.lagrange_from_vec(build_m(
-                lookup,
-                &pk.usable_rows,
-                pk.num_rows,
-                &selectors,
-                &fixed,
-                &instance,
-                &advice,
-                challenges,
-            ))
+        .map(|table| evaluate_columns(table.table_expressions()))
+        .collect();
+    let inputs: Vec<Vec<_>> = pk
+        .lookup_tables()
+        .iter()
+        .map(|table| {
+            table
+                .arguments()
+                .iter()
+                .map(|arg| evaluate_columns(&arg.input_expressions))
+                .collect()
         })
-        .collect::<Vec<_>>();
+        .collect();
 
-    let m_committed = batch_commit(params, m_columns.into_iter(), &mut rng, transcript);
+    let m_columns = zip(pk.lookup_tables(), zip(&tables, &inputs))
+        .map(|(lookup_table, (table, inputs))| {
+            build_m(lookup_table.arguments(), table, inputs, pk.strict_lookups())
+                .map(|m| pk.domain.lagrange_from_vec(m))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
 
-    let [r, theta] = [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+    let m_committed = batch_commit(params, m_columns.into_iter(), &mut rng, transcript)?;
 
-    pk.cs
-        .lookups
-        .iter()
-        .zip(m_committed.into_iter())
-        .map(|(lookup, m)| {
-            let num_inputs = lookup.input_expressions.len();
-            let thetas = powers(theta).take(num_inputs).collect::<Vec<_>>();
-
-            let g_column = pk.domain.lagrange_from_vec(build_g(
-                lookup,
-                &pk.usable_rows,
-                pk.num_rows,
-                &selectors,
-                &fixed,
-                &instance,
-                &advice,
-                challenges,
-                &m.values,
-                &thetas,
-                r,
-            ));
-
-            let h_column = pk.domain.lagrange_from_vec(build_h(
-                lookup,
-                &pk.usable_rows,
-                pk.num_rows,
-                &selectors,
-                &fixed,
-                &instance,
-                &advice,
-                challenges,
-                &thetas,
-                r,
-            ));
+    let challenges = squeeze_challenges(pk.lookup_challenges(), tables.len(), transcript);
+
+    zip(zip(tables, inputs), zip(m_committed, challenges))
+        .map(|((table, inputs), (m, [r, theta]))| {
+            let thetas = powers(theta).take(table.len()).collect::<Vec<_>>();
 
-            let g = commit(params, g_column, &mut rng, transcript);
-            let h = commit(params, h_column, &mut rng, transcript);
+            let g_column = pk
+                .domain
+                .lagrange_from_vec(build_g(&table, &m.values, &thetas, r));
+            let h_columns = inputs
+                .iter()
+                .map(|input| pk.domain.lagrange_from_vec(build_h(input, &thetas, r)));
 
-            Transcript { m, r, thetas, g, h }
+            let g = commit(params, g_column, &mut rng, transcript)?;
+            let h = batch_commit(params, h_columns, &mut rng, transcript)?;
+
+            Ok(Transcript { m, r, thetas, g, h })
         })
         .collect()
 }
 
-fn build_m<F: PrimeField>(
-    lookup: &crate::plonk::lookup::Argument<F>,
-    usable_rows: &Range<usize>,
-    num_rows: usize,
-    selectors: &[&[F]],
-    fixed: &[&[F]],
-    instance: &[&[F]],
-    advice: &[&[F]],
-    challenges: &[F],
-) -> Vec<F> {
-    let mut row_evals_repr: Vec<u8> = Vec::new();
-
-    let mut map = HashMap::<Vec<u8>, usize>::new();
+/// Squeezes the challenges [r, θ] of `num_lookups` lookup tables, which are either shared by all of them,
+/// or sampled independently for each table.
+pub(in crate::protostar) fn squeeze_challenges<
+    C: CurveAffine,
+    E: EncodedChallenge<C>,
+    T: transcript::Transcript<C, E>,
+>(
+    lookup_challenges: LookupChallenges,
+    num_lookups: usize,
+    transcript: &mut T,
+) -> Vec<[C::Scalar; 2]> {
+    let mut squeeze = || [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+    match lookup_challenges {
+        LookupChallenges::Shared => vec![squeeze(); num_lookups],
+        LookupChallenges::PerTable => (0..num_lookups).map(|_| squeeze()).collect(),
+    }
+}
 
-    for row_idx in usable_rows.clone() {
-        row_evals_repr.clear();
+/// Row of the table or inputs of a lookup, given by the values of all `columns` at `row`.
+/// Rows are hashed from the canonical representation of their values.
+struct RowKey<'a, F> {
+    columns: &'a [Vec<F>],
+    row: usize,
+}
 
-        for expr in &lookup.table_expressions {
-            let eval = evaluate(
-                row_idx, num_rows, expr, selectors, fixed, instance, advice, challenges,
-            );
-            row_evals_repr.extend(eval.to_repr().as_ref().iter());
+impl<F: PrimeField> Hash for RowKey<'_, F> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for column in self.columns {
+            column[self.row].to_repr().as_ref().hash(state);
         }
-
-        map.insert(row_evals_repr.clone(), row_idx);
     }
+}
 
-    let mut m = vec![F::ZERO; num_rows];
+impl<F: PrimeField> PartialEq for RowKey<'_, F> {
+    fn eq(&self, other: &Self) -> bool {
+        zip(self.columns, other.columns).all(|(lhs, rhs)| lhs[self.row] == rhs[other.row])
+    }
+}
 
-    for row_idx in usable_rows.clone() {
-        row_evals_repr.clear();
+impl<F: PrimeField> Eq for RowKey<'_, F> {}
 
-        for expr in &lookup.input_expressions {
-            let eval = evaluate(
-                row_idx, num_rows, expr, selectors, fixed, instance, advice, challenges,
-            );
-            row_evals_repr.extend(eval.to_repr().as_ref().iter());
-        }
+/// Returns the multiplicities mᵢ of each row i of the `table` among the rows of all `inputs`,
+/// where repeated rows of the table are counted at their first occurrence only.
+/// If `strict` is set, fails at the first row of an input which is not contained in the table,
+/// and otherwise skips these rows.
+fn build_m<F: PrimeField>(
+    arguments: &[Argument<F>],
+    table: &[Vec<F>],
+    inputs: &[Vec<Vec<F>>],
+    strict: bool,
+) -> Result<Vec<F>, Error> {
+    let num_rows = table.first().map_or(0, Vec::len);
+
+    let table_rows = (0..num_rows)
+        .into_par_iter()
+        .fold(HashMap::new, |mut rows, row| {
+            rows.entry(RowKey {
+                columns: table,
+                row,
+            })
+            .or_insert(row);
+            rows
+        })
+        .reduce(HashMap::new, |mut rows, other| {
+            for (key, row) in other {
+                let entry = rows.entry(key).or_insert(row);
+                *entry = std::cmp::min(*entry, row);
+            }
+            rows
+        });
 
-        if let Some(index) = map.get(&row_evals_repr) {
-            m[*index] += F::ONE;
+    let mut m = vec![F::ZERO; num_rows];
+    for (arg, input) in zip(arguments, inputs) {
+        let table_indices = (0..num_rows)
+            .into_par_iter()
+            .map(|row| {
+                let key = RowKey {
+                    columns: input,
+                    row,
+                };
+                table_rows.get(&key).copied()
+            })
+            .collect::<Vec<_>>();
+
+        for (row, index) in table_indices.into_iter().enumerate() {
+            match index {
+                Some(index) => m[index] += F::ONE,
+                None if strict => {
+                    return Err(Error::LookupInputNotInTable {
+                        name: arg.name().to_string(),
+                        row,
+                        input: input
+                            .iter()
+                            .map(|column| format!("{:?}", column[row]))
+                            .collect(),
+                    })
+                }
+                None => {}
+            }
         }
     }
-
-    m
+    Ok(m)
 }
 
-fn build_g<F: PrimeField>(
-    lookup: &crate::plonk::lookup::Argument<F>,
-    usable_rows: &Range<usize>,
-    num_rows: usize,
-    selectors: &[&[F]],
-    fixed: &[&[F]],
-    instance: &[&[F]],
-    advice: &[&[F]],
-    challenges: &[F],
-    m: &[F],
-    thetas: &[F],
-    r: F,
-) -> Vec<F> {
-    let mut g = vec![F::ZERO; num_rows];
-
-    for row_idx in usable_rows.clone() {
-        g[row_idx] = evaluate_linear_combination(
-            row_idx,
-            num_rows,
-            &lookup.table_expressions,
-            thetas,
-            selectors,
-            fixed,
-            instance,
-            advice,
-            challenges,
-        ) + r;
+/// Returns gᵢ = mᵢ/(r + ∑ⱼ θʲ⋅tⱼ,ᵢ) for the columns tⱼ of the `table`.
+fn build_g<F: PrimeField>(table: &[Vec<F>], m: &ColumnValues<F>, thetas: &[F], r: F) -> Vec<F> {
+    match m {
+        ColumnValues::Dense(m) => {
+            let mut g = compressed_inverses(table, thetas, r);
+            parallelize(&mut g, |g, start| {
+                for (g, m) in zip(g, &m[start..]) {
+                    *g *= m;
+                }
+            });
+            g
+        }
+        // Only the rows of the table with a non-zero multiplicity need to be inverted
+        ColumnValues::Sparse(m) => {
+            let mut inverses: Vec<_> = m
+                .entries()
+                .map(|(row, _)| {
+                    zip(table, thetas).fold(r, |acc, (column, theta)| acc + *theta * column[row])
+                })
+                .collect();
+            inverses.iter_mut().batch_invert();
+
+            let mut g = vec![F::ZERO; table.first().map_or(0, Vec::len)];
+            for ((row, m), inverse) in zip(m.entries(), inverses) {
+                g[row] = inverse * m;
+            }
+            g
+        }
     }
+}
 
-    g.iter_mut().batch_invert();
+/// Returns hᵢ = 1/(r + ∑ⱼ θʲ⋅aⱼ,ᵢ) for the columns aⱼ of the `input`.
+/// Every row is inverted, including the ones where the selector of the argument is disabled,
+/// and the ones which were skipped by `build_m` in non-strict mode.
+fn build_h<F: PrimeField>(input: &[Vec<F>], thetas: &[F], r: F) -> Vec<F> {
+    compressed_inverses(input, thetas, r)
+}
 
-    for row_idx in usable_rows.clone() {
-        g[row_idx] *= m[row_idx];
-    }
-    g
+/// Returns the inverses 1/(r + ∑ⱼ θʲ⋅cⱼ,ᵢ) of the rows of `columns` compressed by `thetas`,
+/// which are batch-inverted in parallel.
+fn compressed_inverses<F: PrimeField>(columns: &[Vec<F>], thetas: &[F], r: F) -> Vec<F> {
+    let num_rows = columns.first().map_or(0, Vec::len);
+    let mut inverses = vec![r; num_rows];
+    parallelize(&mut inverses, |inverses, start| {
+        for (column, theta) in zip(columns, thetas) {
+            for (inverse, value) in zip(inverses.iter_mut(), &column[start..]) {
+                *inverse += *theta * value;
+            }
+        }
+        inverses.iter_mut().batch_invert();
+    });
+    inverses
 }
 
-fn build_h<F: PrimeField>(
-    lookup: &crate::plonk::lookup::Argument<F>,
-    usable_rows: &Range<usize>,
+/// Evaluates `expr` over all rows of the trace, in parallel.
+fn evaluate_column<F: Field>(
+    expr: &Expression<F>,
     num_rows: usize,
-    selectors: &[&[F]],
-    fixed: &[&[F]],
-    instance: &[&[F]],
-    advice: &[&[F]],
+    selectors: &[&ColumnValues<F>],
+    fixed: &[&ColumnValues<F>],
+    instance: &[&ColumnValues<F>],
+    advice: &[&ColumnValues<F>],
     challenges: &[F],
-    thetas: &[F],
-    r: F,
 ) -> Vec<F> {
-    let mut h = vec![F::ZERO; num_rows];
-
-    for row_idx in usable_rows.clone() {
-        h[row_idx] = evaluate_linear_combination(
-            row_idx,
-            num_rows,
-            &lookup.input_expressions,
-            thetas,
-            selectors,
-            fixed,
-            instance,
-            advice,
-            challenges,
-        ) + r;
-    }
-
-    h.iter_mut().batch_invert();
-    h
+    let mut column = vec![F::ZERO; num_rows];
+    parallelize(&mut column, |column, start| {
+        for (i, value) in column.iter_mut().enumerate() {
+            *value = evaluate(
+                start + i,
+                num_rows,
+                expr,
+                selectors,
+                fixed,
+                instance,
+                advice,
+                challenges,
+            );
+        }
+    });
+    column
 }
 
-fn evaluate_linear_combination<F: Field>(
+pub(super) fn evaluate_linear_combination<F: Field>(
     row_idx: usize,
     num_rows: usize,
     exprs: &[Expression<F>],
     coeffs: &[F],
-    selectors: &[&[F]],
-    fixed: &[&[F]],
-    instance: &[&[F]],
-    advice: &[&[F]],
+    selectors: &[&ColumnValues<F>],
+    fixed: &[&ColumnValues<F>],
+    instance: &[&ColumnValues<F>],
+    advice: &[&ColumnValues<F>],
     challenges: &[F],
 ) -> F {
     zip(exprs.iter(), coeffs.iter()).fold(F::ZERO, |acc, (expr, coeff)| {
@@ -280,10 +408,10 @@ fn evaluate<F: Field>(
     row_idx: usize,
     num_rows: usize,
     expr: &Expression<F>,
-    selectors: &[&[F]],
-    fixed: &[&[F]],
-    instance: &[&[F]],
-    advice: &[&[F]],
+    selectors: &[&ColumnValues<F>],
+    fixed: &[&ColumnValues<F>],
+    instance: &[&ColumnValues<F>],
+    advice: &[&ColumnValues<F>],
     challenges: &[F],
 ) -> F {
     let num_rows_i = num_rows as i32;
@@ -307,3 +435,175 @@ fn evaluate<F: Field>(
 fn get_rotation_idx(idx: usize, rot: Rotation, isize: i32) -> usize {
     (((idx as i32) + rot.0).rem_euclid(isize)) as usize
 }
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use halo2curves::pasta::{pallas, Fp};
+    use rand_core::OsRng;
+
+    use super::build_m;
+    use crate::{
+        plonk::{lookup::Argument, Error},
+        protostar::{
+            accumulator::{Accumulator, DecideError},
+            prover::create_accumulator,
+            verifier::tests::{
+                check_verifier_fold, fixture, two_lookups_circuit, Fixture, TwoLookupsCircuit,
+            },
+            LookupChallenges, ProvingKey,
+        },
+        transcript::{Blake2bWrite, Challenge255, TranscriptWriterBuffer},
+        SerdeFormat,
+    };
+
+    fn column(values: &[u64]) -> Vec<Fp> {
+        values.iter().map(|value| Fp::from(*value)).collect()
+    }
+
+    #[test]
+    fn test_build_m_first_occurrence() {
+        // Rows (1, 10) and (3, 30) appear twice in the table
+        let table = vec![
+            column(&[0, 1, 2, 1, 3, 3, 0, 4]),
+            column(&[0, 10, 20, 10, 30, 30, 0, 40]),
+        ];
+        let arguments = [Argument::new("a", vec![]), Argument::new("b", vec![])];
+        let inputs = vec![
+            vec![
+                column(&[1, 1, 3, 0, 0, 0, 0, 0]),
+                column(&[10, 10, 30, 0, 0, 0, 0, 0]),
+            ],
+            vec![
+                column(&[4, 3, 0, 0, 0, 0, 0, 0]),
+                column(&[40, 30, 0, 0, 0, 0, 0, 0]),
+            ],
+        ];
+
+        // Repeated rows are only counted at their first occurrence, over all arguments
+        let m = build_m(&arguments, &table, &inputs, true).unwrap();
+        assert_eq!(m, column(&[11, 2, 0, 0, 2, 0, 0, 1]));
+    }
+
+    #[test]
+    fn test_build_m_missing_input() {
+        let table = vec![column(&[0, 1, 2, 3])];
+        let arguments = [Argument::new("range", vec![])];
+        // The value 5 is not contained in the table
+        let inputs = vec![vec![column(&[1, 5, 0, 5])]];
+
+        // In strict mode, the first missing row is reported
+        match build_m(&arguments, &table, &inputs, true) {
+            Err(Error::LookupInputNotInTable { name, row, input }) => {
+                assert_eq!(name, "range");
+                assert_eq!(row, 1);
+                assert_eq!(input, vec![format!("{:?}", Fp::from(5))]);
+            }
+            _ => panic!("expected a missing lookup input"),
+        }
+
+        // Otherwise, missing rows are skipped
+        let m = build_m(&arguments, &table, &inputs, false).unwrap();
+        assert_eq!(m, column(&[1, 1, 0, 0]));
+    }
+
+    #[test]
+    fn test_lookup_challenges() {
+        let circuit0 = two_lookups_circuit::<false>([(1, 2), (3, 4), (5, 15)]);
+        let circuit1 = two_lookups_circuit::<false>([(0, 0), (7, 7), (15, 8)]);
+
+        for lookup_challenges in [LookupChallenges::Shared, LookupChallenges::PerTable] {
+            let Fixture {
+                params,
+                pk,
+                accs: [acc0, acc1],
+                mut transcript,
+            } = fixture(5, [&circuit0, &circuit1], lookup_challenges);
+            assert_eq!(pk.lookup_challenges(), lookup_challenges);
+
+            let shared = acc0.lookups[0].r == acc0.lookups[1].r
+                && acc0.lookups[0].thetas == acc0.lookups[1].thetas;
+            assert_eq!(shared, lookup_challenges == LookupChallenges::Shared);
+
+            let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+            assert!(Accumulator::decide(&params, &pk, &acc2));
+
+            // The verifier samples the challenges in the same way
+            check_verifier_fold(pk.get_vk(), &transcript.finalize(), [acc0, acc1, acc2]);
+
+            // The choice is preserved by serialization
+            let bytes = pk.to_bytes(SerdeFormat::RawBytes);
+            let pk_read = ProvingKey::<pallas::Affine>::from_bytes::<
+                TwoLookupsCircuit<pallas::Scalar, 16, false>,
+                _,
+            >(
+                &bytes,
+                &params,
+                SerdeFormat::RawBytes,
+                #[cfg(feature = "circuit-params")]
+                (),
+            )
+            .unwrap();
+            assert_eq!(pk_read.lookup_challenges(), lookup_challenges);
+        }
+    }
+
+    #[test]
+    fn test_shared_lookup_table() {
+        let circuit0 = two_lookups_circuit::<true>([(1, 2), (3, 4), (5, 15)]);
+        let circuit1 = two_lookups_circuit::<true>([(0, 0), (7, 7), (15, 8)]);
+        let Fixture {
+            params,
+            pk,
+            accs: [acc0, acc1],
+            mut transcript,
+        } = fixture(5, [&circuit0, &circuit1], LookupChallenges::Shared);
+
+        // Both lookups use the same table, so they are merged into a single one with two inputs
+        assert_eq!(pk.lookup_tables().len(), 1);
+        assert_eq!(pk.lookup_tables()[0].arguments().len(), 2);
+        assert_eq!(pk.lookup_tables()[0].name(), "lookup a, lookup b");
+
+        // Separate tables require an additional constraint g⋅(r + θ⋅table) − m
+        let circuit_separate = two_lookups_circuit::<false>([(1, 2), (3, 4), (5, 15)]);
+        let pk_separate = ProvingKey::new(&params, &circuit_separate).unwrap();
+        assert_eq!(pk_separate.lookup_tables().len(), 2);
+        assert_eq!(
+            pk.get_vk().num_folding_constraints + 1,
+            pk_separate.get_vk().num_folding_constraints
+        );
+
+        // The multiplicities count the rows of both inputs
+        assert_eq!(acc0.lookups.len(), 1);
+        assert_eq!(acc0.lookups[0].h.len(), 2);
+        assert_eq!(
+            acc0.lookups[0].m.values.sum(),
+            pallas::Scalar::from(2 * pk.num_rows as u64)
+        );
+        assert!(Accumulator::decide(&params, &pk, &acc0));
+
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc2));
+        check_verifier_fold(
+            pk.get_vk(),
+            &transcript.finalize(),
+            [acc0, acc1, acc2.clone()],
+        );
+
+        // Errors still refer to the argument whose input is missing
+        let circuit_bad = two_lookups_circuit::<true>([(1, 2), (3, 16), (5, 15)]);
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let err = create_accumulator(&params, &pk, &circuit_bad, &[], OsRng, &mut transcript)
+            .unwrap_err();
+        assert!(matches!(err, Error::LookupInputNotInTable { name, .. } if name == "lookup b"));
+
+        // The linear constraint is checked over the sum of both inputs
+        let mut acc_bad = acc2;
+        acc_bad.lookups[0].h[1].values[0] += pallas::Scalar::ONE;
+        let errors = Accumulator::decide_detailed(&params, &pk, &acc_bad).unwrap_err();
+        assert!(errors.contains(&DecideError::Lookup {
+            index: 0,
+            name: "lookup a, lookup b".to_string(),
+        }));
+    }
+}
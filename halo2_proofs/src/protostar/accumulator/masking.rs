@@ -0,0 +1,180 @@
+use ff::Field;
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::{
+    arithmetic::powers,
+    plonk::Error,
+    poly::{
+        commitment::{Blind, Params},
+        empty_lagrange, LagrangeCoeff, Polynomial,
+    },
+    protostar::ProvingKey,
+    transcript::{EncodedChallenge, TranscriptWrite},
+};
+
+use super::{
+    column::ColumnValues,
+    committed::{batch_commit, commit, Committed},
+    compressed_verifier, gate, lookup, permutation, shuffle, Accumulator,
+};
+
+/// Samples a random accumulator which satisfies the relaxed relation defined by `pk`,
+/// and writes all its commitments and challenges to the transcript.
+///
+/// All committed columns are uniformly random and blinded, and all challenges are sampled by the prover.
+/// The linear constraints ∑ᵢ gᵢ = ∑ᵢ hᵢ of the lookup tables, shuffle and permutation arguments are satisfied
+/// by adjusting the first entry of the h columns, and the error term is recomputed from the columns.
+/// Folding an accumulator with a masking accumulator hides its witness,
+/// since the columns, blinds and error polynomial of the fold are masked by uniformly random ones.
+///
+/// Instance columns are public, so they are set to zero and are not committed to.
+pub(in crate::protostar) fn new<
+    'params,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    E: EncodedChallenge<C>,
+    R: RngCore,
+    T: TranscriptWrite<C, E>,
+>(
+    params: &P,
+    pk: &ProvingKey<C>,
+    mut rng: R,
+    transcript: &mut T,
+) -> Result<Accumulator<C>, Error> {
+    let cs = &pk.cs;
+    let num_rows = pk.num_rows;
+
+    let random_column = |rng: &mut R| {
+        let mut column = empty_lagrange(num_rows);
+        for value in column.iter_mut() {
+            *value = C::Scalar::random(&mut *rng);
+        }
+        column
+    };
+
+    let instance = (0..cs.num_instance_columns)
+        .map(|_| Committed {
+            values: ColumnValues::zero(num_rows),
+            commitment: C::identity(),
+            blind: Blind(C::Scalar::ZERO),
+        })
+        .collect();
+    let advice: Vec<_> = (0..cs.num_advice_columns)
+        .map(|_| random_column(&mut rng))
+        .collect();
+    let advice = batch_commit(params, advice.into_iter(), &mut rng, transcript)?;
+    let challenges = pk
+        .max_challenge_powers()
+        .iter()
+        .map(|max_power| {
+            let challenge = C::Scalar::random(&mut rng);
+            transcript.write_scalar(challenge)?;
+            Ok(powers(challenge).skip(1).take(*max_power).collect())
+        })
+        .collect::<Result<_, Error>>()?;
+    let gate = gate::Transcript {
+        instance,
+        advice,
+        challenges,
+    };
+
+    let lookups = pk
+        .lookup_tables()
+        .iter()
+        .map(|table| {
+            let [r, theta] = [(); 2].map(|_| C::Scalar::random(&mut rng));
+            transcript.write_scalar(r)?;
+            transcript.write_scalar(theta)?;
+            let thetas = powers(theta)
+                .take(table.table_expressions().len())
+                .collect();
+
+            let [m, g] = [(); 2].map(|_| random_column(&mut rng));
+            let mut h: Vec<_> = (0..table.arguments().len())
+                .map(|_| random_column(&mut rng))
+                .collect();
+            let difference = sum_difference([&g], &h);
+            h[0][0] += difference;
+            let m = commit(params, m, &mut rng, transcript)?;
+            let g = commit(params, g, &mut rng, transcript)?;
+            let h = batch_commit(params, h.into_iter(), &mut rng, transcript)?;
+            Ok(lookup::Transcript { m, r, thetas, g, h })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let shuffles = cs
+        .shuffles
+        .iter()
+        .map(|arg| {
+            let [r, theta] = [(); 2].map(|_| C::Scalar::random(&mut rng));
+            transcript.write_scalar(r)?;
+            transcript.write_scalar(theta)?;
+            let thetas = powers(theta).take(arg.input_expressions.len()).collect();
+
+            let [g, mut h] = [(); 2].map(|_| random_column(&mut rng));
+            let difference = sum_difference([&g], [&h]);
+            h[0] += difference;
+            let g = commit(params, g, &mut rng, transcript)?;
+            let h = commit(params, h, &mut rng, transcript)?;
+            Ok(shuffle::Transcript { r, thetas, g, h })
+        })
+        .collect::<Result<_, Error>>()?;
+
+    let permutation = {
+        let num_columns = cs.permutation.columns.len();
+        if num_columns == 0 {
+            permutation::Transcript {
+                r: C::Scalar::ZERO,
+                theta: C::Scalar::ZERO,
+                g: vec![],
+                h: vec![],
+            }
+        } else {
+            let [r, theta] = [(); 2].map(|_| C::Scalar::random(&mut rng));
+            transcript.write_scalar(r)?;
+            transcript.write_scalar(theta)?;
+
+            let g: Vec<_> = (0..num_columns).map(|_| random_column(&mut rng)).collect();
+            let mut h: Vec<_> = (0..num_columns).map(|_| random_column(&mut rng)).collect();
+            let difference = sum_difference(&g, &h);
+            h[0][0] += difference;
+            let h = batch_commit(params, h.into_iter(), &mut rng, transcript)?;
+            let g = batch_commit(params, g.into_iter(), &mut rng, transcript)?;
+            permutation::Transcript { r, theta, g, h }
+        }
+    };
+
+    let beta = C::Scalar::random(&mut rng);
+    transcript.write_scalar(beta)?;
+    let beta = compressed_verifier::Transcript::new_with_challenge(params, beta, transcript)?;
+
+    let y = C::Scalar::random(&mut rng);
+    transcript.write_scalar(y)?;
+    let ys = powers(y).take(pk.num_folding_constraints()).collect();
+
+    let mut acc = Accumulator {
+        gate,
+        lookups,
+        shuffles,
+        permutation,
+        beta,
+        ys,
+        error: C::Scalar::ZERO,
+        masked: true,
+    };
+    acc.error = Accumulator::error(pk, &acc);
+    transcript.write_scalar(acc.error)?;
+
+    Ok(acc)
+}
+
+/// Returns ∑ⱼ,ᵢ gⱼ,ᵢ − ∑ⱼ,ᵢ hⱼ,ᵢ, which must be added to a single entry of h for both sums to be equal.
+fn sum_difference<'a, F: Field + 'a>(
+    g: impl IntoIterator<Item = &'a Polynomial<F, LagrangeCoeff>>,
+    h: impl IntoIterator<Item = &'a Polynomial<F, LagrangeCoeff>>,
+) -> F {
+    let g_sum: F = g.into_iter().flat_map(|g| g.iter()).sum();
+    let h_sum: F = h.into_iter().flat_map(|h| h.iter()).sum();
+    g_sum - h_sum
+}
@@ -0,0 +1,155 @@
+use std::{io, iter::zip};
+
+use ff::{BatchInvert, Field};
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::{
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
+    plonk::Any,
+    poly::{commitment::Params, LagrangeCoeff, Polynomial},
+    protostar::ProvingKey,
+    transcript::{EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
+};
+
+use super::{
+    column::ColumnValues,
+    committed::{batch_commit, Committed},
+    linear_combination, transpose,
+};
+
+/// The permutation argument is expressed as a logUp multiset equality between the sets
+/// {(wⱼ,ᵢ, idⱼ,ᵢ)} and {(wⱼ,ᵢ, σⱼ,ᵢ)}, where wⱼ is the j-th column in the permutation,
+/// idⱼ,ᵢ is the label of cell (j,i) and σⱼ,ᵢ the label of the cell it is mapped to.
+/// For each column, the prover commits to the inverses
+/// - hⱼ,ᵢ = 1/(r + wⱼ,ᵢ + θ⋅idⱼ,ᵢ)
+/// - gⱼ,ᵢ = 1/(r + wⱼ,ᵢ + θ⋅σⱼ,ᵢ)
+///
+/// Their correctness is checked by folding constraints, while the linear constraint
+/// ∑ⱼ,ᵢ hⱼ,ᵢ = ∑ⱼ,ᵢ gⱼ,ᵢ is checked by the decider.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Transcript<C: CurveAffine> {
+    pub r: C::Scalar,
+    pub theta: C::Scalar,
+    pub g: Vec<Committed<C>>,
+    pub h: Vec<Committed<C>>,
+}
+
+impl<C: CurveAffine> Transcript<C> {
+    /// Runs the 1-round logUp IOP for the permutation argument.
+    /// If the circuit has no columns involved in the permutation, no challenges are sampled.
+    pub fn new<
+        'params,
+        P: Params<'params, C>,
+        E: EncodedChallenge<C>,
+        R: RngCore,
+        T: TranscriptWrite<C, E>,
+    >(
+        params: &P,
+        pk: &ProvingKey<C>,
+        gate_tx: &super::gate::Transcript<C>,
+        mut rng: R,
+        transcript: &mut T,
+    ) -> io::Result<Self> {
+        let columns = &pk.cs.permutation.columns;
+
+        if columns.is_empty() {
+            return Ok(Self {
+                r: C::Scalar::ZERO,
+                theta: C::Scalar::ZERO,
+                g: vec![],
+                h: vec![],
+            });
+        }
+
+        let [r, theta] = [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+
+        let values: Vec<_> = columns
+            .iter()
+            .map(|column| match column.column_type() {
+                Any::Advice(_) => &gate_tx.advice[column.index()].values,
+                Any::Fixed => &pk.fixed[column.index()].values,
+                Any::Instance => &gate_tx.instance[column.index()].values,
+            })
+            .collect();
+
+        let h_columns: Vec<_> = zip(values.iter(), pk.permutation_ids.iter())
+            .map(|(values, ids)| build_inverses(pk, values, &ids.values, r, theta))
+            .collect();
+        let g_columns: Vec<_> = zip(values.iter(), pk.permutation_sigmas.iter())
+            .map(|(values, sigmas)| build_inverses(pk, values, &sigmas.values, r, theta))
+            .collect();
+
+        let h = batch_commit(params, h_columns.into_iter(), &mut rng, transcript)?;
+        let g = batch_commit(params, g_columns.into_iter(), &mut rng, transcript)?;
+
+        Ok(Self { r, theta, g, h })
+    }
+
+    /// Computes the linear combination ∑ₖ wₖ⋅txₖ with the given `weights`
+    pub(super) fn merge(weights: &[C::Scalar], transcripts: Vec<Self>) -> Self {
+        let r = linear_combination(weights, transcripts.iter().map(|tx| tx.r));
+        let theta = linear_combination(weights, transcripts.iter().map(|tx| tx.theta));
+
+        let mut g = Vec::with_capacity(transcripts.len());
+        let mut h = Vec::with_capacity(transcripts.len());
+        for transcript in transcripts {
+            g.push(transcript.g);
+            h.push(transcript.h);
+        }
+        let [g, h] = [g, h].map(|columns| {
+            transpose(columns)
+                .into_iter()
+                .map(|committed| Committed::merge(weights, committed))
+                .collect()
+        });
+        Self { r, theta, g, h }
+    }
+}
+
+impl<C: SerdeCurveAffine> Transcript<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the challenges r and θ, and the columns g and h to a buffer.
+    pub(super) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.r.write(writer, format)?;
+        self.theta.write(writer, format)?;
+        write_slice(&self.g, writer, |g, writer| g.write(writer, format))?;
+        write_slice(&self.h, writer, |h, writer| h.write(writer, format))
+    }
+
+    /// Reads a permutation transcript from a buffer.
+    pub(super) fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let r = C::Scalar::read(reader, format)?;
+        let theta = C::Scalar::read(reader, format)?;
+        let g = read_vec(reader, |reader| Committed::read(reader, format, pk))?;
+        let h = read_vec(reader, |reader| Committed::read(reader, format, pk))?;
+        Ok(Self { r, theta, g, h })
+    }
+}
+
+/// Computes the column 1/(r + wᵢ + θ⋅labelᵢ) over all rows.
+fn build_inverses<C: CurveAffine>(
+    pk: &ProvingKey<C>,
+    values: &ColumnValues<C::Scalar>,
+    labels: &ColumnValues<C::Scalar>,
+    r: C::Scalar,
+    theta: C::Scalar,
+) -> Polynomial<C::Scalar, LagrangeCoeff> {
+    let mut inverses = pk.domain.empty_lagrange();
+    for row_idx in 0..pk.num_rows {
+        inverses[row_idx] = r + values[row_idx] + theta * labels[row_idx];
+    }
+    inverses.iter_mut().batch_invert();
+    inverses
+}
@@ -0,0 +1,159 @@
+use std::{io, iter::zip};
+
+use ff::BatchInvert;
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::{
+    arithmetic::powers,
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
+    poly::commitment::Params,
+    protostar::ProvingKey,
+    transcript::{EncodedChallenge, TranscriptWrite},
+    SerdeFormat,
+};
+
+use super::{
+    column::ColumnValues,
+    committed::{commit, Committed},
+    linear_combination, transpose,
+};
+
+/// The shuffle argument is expressed as a logUp multiset equality where all multiplicities are 1.
+/// For a shuffle between the inputs {aᵢ} and the shuffled values {sᵢ}, the prover commits to
+/// - hᵢ = 1/(r + ∑ⱼ θʲ⋅aⱼ,ᵢ)
+/// - gᵢ = 1/(r + ∑ⱼ θʲ⋅sⱼ,ᵢ)
+///
+/// Their correctness is checked by folding constraints, while the linear constraint
+/// ∑ᵢ hᵢ = ∑ᵢ gᵢ is checked by the decider.
+#[derive(PartialEq, Debug, Clone)]
+pub struct Transcript<C: CurveAffine> {
+    pub r: C::Scalar,
+    pub thetas: Vec<C::Scalar>,
+    pub g: Committed<C>,
+    pub h: Committed<C>,
+}
+
+impl<C: CurveAffine> Transcript<C> {
+    /// Computes the linear combination ∑ₖ wₖ⋅txₖ with the given `weights`
+    pub(super) fn merge(weights: &[C::Scalar], transcripts: Vec<Self>) -> Self {
+        let r = linear_combination(weights, transcripts.iter().map(|tx| tx.r));
+        let thetas = transpose(transcripts.iter().map(|tx| tx.thetas.clone()).collect())
+            .into_iter()
+            .map(|thetas| linear_combination(weights, thetas))
+            .collect();
+
+        let mut g = Vec::with_capacity(transcripts.len());
+        let mut h = Vec::with_capacity(transcripts.len());
+        for transcript in transcripts {
+            g.push(transcript.g);
+            h.push(transcript.h);
+        }
+        let [g, h] = [g, h].map(|committed| Committed::merge(weights, committed));
+        Self { r, thetas, g, h }
+    }
+}
+
+impl<C: SerdeCurveAffine> Transcript<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes the challenges r and θ, and the columns g and h to a buffer.
+    pub(super) fn write<W: io::Write>(
+        &self,
+        writer: &mut W,
+        format: SerdeFormat,
+    ) -> io::Result<()> {
+        self.r.write(writer, format)?;
+        write_slice(&self.thetas, writer, |theta, writer| {
+            theta.write(writer, format)
+        })?;
+        self.g.write(writer, format)?;
+        self.h.write(writer, format)
+    }
+
+    /// Reads a shuffle transcript from a buffer.
+    pub(super) fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        pk: &ProvingKey<C>,
+    ) -> io::Result<Self> {
+        let r = C::Scalar::read(reader, format)?;
+        let thetas = read_vec(reader, |reader| C::Scalar::read(reader, format))?;
+        let g = Committed::read(reader, format, pk)?;
+        let h = Committed::read(reader, format, pk)?;
+        Ok(Self { r, thetas, g, h })
+    }
+}
+
+/// Runs the 1-round logUp IOP for all shuffle arguments.
+/// If the circuit has no shuffle arguments, no challenges are sampled.
+pub fn new<
+    'params,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    E: EncodedChallenge<C>,
+    R: RngCore,
+    T: TranscriptWrite<C, E>,
+>(
+    params: &P,
+    pk: &ProvingKey<C>,
+    gate_tx: &super::gate::Transcript<C>,
+    mut rng: R,
+    transcript: &mut T,
+) -> io::Result<Vec<Transcript<C>>> {
+    if pk.cs.shuffles.is_empty() {
+        return Ok(vec![]);
+    }
+
+    let selectors = pk.selectors.iter().map(|c| &c.values).collect::<Vec<_>>();
+    let fixed = pk.fixed.iter().map(|c| &c.values).collect::<Vec<_>>();
+    let instance = gate_tx
+        .instance
+        .iter()
+        .map(|c| &c.values)
+        .collect::<Vec<_>>();
+    // Witness generation only requires the challenges, rather than their powers
+    let challenges = &gate_tx
+        .challenges
+        .iter()
+        .map(|powers| powers[0])
+        .collect::<Vec<_>>();
+    let advice = gate_tx.advice.iter().map(|c| &c.values).collect::<Vec<_>>();
+
+    let [r, theta] = [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+
+    pk.cs
+        .shuffles
+        .iter()
+        .map(|shuffle| {
+            let num_inputs = shuffle.input_expressions.len();
+            let thetas = powers(theta).take(num_inputs).collect::<Vec<_>>();
+
+            let [g_column, h_column] = [&shuffle.shuffle_expressions, &shuffle.input_expressions]
+                .map(|exprs| {
+                    let mut column = pk.domain.empty_lagrange();
+                    for row_idx in 0..pk.num_rows {
+                        column[row_idx] = r + super::lookup::evaluate_linear_combination(
+                            row_idx,
+                            pk.num_rows,
+                            exprs,
+                            &thetas,
+                            &selectors,
+                            &fixed,
+                            &instance,
+                            &advice,
+                            challenges,
+                        );
+                    }
+                    column.iter_mut().batch_invert();
+                    column
+                });
+
+            let g = commit(params, g_column, &mut rng, transcript)?;
+            let h = commit(params, h_column, &mut rng, transcript)?;
+
+            Ok(Transcript { r, thetas, g, h })
+        })
+        .collect()
+}
@@ -4,15 +4,20 @@ use ff::Field;
 use halo2curves::CurveAffine;
 
 use crate::{
-    plonk::{self, lookup},
+    plonk::{self, permutation, Any},
     poly::{Basis, LagrangeCoeff, Polynomial},
 };
 
-use self::expression::{QueriedExpression, QueryType};
+use self::{
+    expression::{QueriedExpression, QueryType},
+    graph::ExpressionGraph,
+};
 
-use super::{accumulator::Accumulator, ProvingKey};
+use super::{accumulator::Accumulator, LookupTable, ProvingKey};
 
 pub(crate) mod expression;
+pub(crate) mod graph;
+pub(crate) mod labeled;
 pub(crate) mod paired;
 pub(crate) mod polynomial;
 
@@ -23,25 +28,50 @@ pub struct Data<T: QueryType> {
     selectors: Vec<T::Fixed>,
     instance: Vec<T::Witness>,
     advice: Vec<T::Witness>,
-    challenges: Vec<T::Challenge>,
-    beta: T::Witness,
+    // challenges[i][d-1] contains the variable for the power cᵢᵈ
+    challenges: Vec<Vec<T::Challenge>>,
     lookups: Vec<LookupData<T>>,
+    shuffles: Vec<ShuffleData<T>>,
+    permutation: PermutationData<T>,
     ys: Vec<T::Challenge>,
 }
 
 pub struct LookupData<T: QueryType> {
     m: T::Witness,
+    g: T::Witness,
+    h: Vec<T::Witness>,
+    thetas: Vec<T::Challenge>,
+    r: T::Challenge,
+}
+
+pub struct ShuffleData<T: QueryType> {
     g: T::Witness,
     h: T::Witness,
     thetas: Vec<T::Challenge>,
     r: T::Challenge,
 }
 
+pub struct PermutationData<T: QueryType> {
+    ids: Vec<T::Fixed>,
+    sigmas: Vec<T::Fixed>,
+    g: Vec<T::Witness>,
+    h: Vec<T::Witness>,
+    r: T::Challenge,
+    theta: T::Challenge,
+}
+
 impl<T: QueryType> Data<T> {
+    /// Returns the variables yⱼ used for combining the constraints.
+    pub fn ys(&self) -> &[T::Challenge] {
+        &self.ys
+    }
+
     pub fn all_constraints(
         &self,
         gates: &[plonk::circuit::Gate<T::F>],
-        lookups: &[plonk::lookup::Argument<T::F>],
+        lookups: &[LookupTable<T::F>],
+        shuffles: &[plonk::shuffle::Argument<T::F>],
+        permutation: &permutation::Argument,
     ) -> Vec<QueriedExpression<T>> {
         let gate_constraints: Vec<_> = gates
             .iter()
@@ -62,6 +92,81 @@ impl<T: QueryType> Data<T> {
         let lookup_constraints: Vec<_> = lookups
             .iter()
             .zip(self.lookups.iter())
+            .flat_map(|(table, data)| {
+                // Get expressions for the inputs input_0, ..., input_k of each argument
+                let inputs = table
+                    .arguments()
+                    .iter()
+                    .map(|arg| {
+                        arg.input_expressions()
+                            .iter()
+                            .map(|e| {
+                                T::from_expression(
+                                    e,
+                                    &self.selectors,
+                                    &self.fixed,
+                                    &self.instance,
+                                    &self.advice,
+                                    &self.challenges,
+                                )
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .collect::<Vec<_>>();
+
+                // Get expressions for tables table_0, ..., table_k
+                let tables = table
+                    .table_expressions()
+                    .iter()
+                    .map(|e| {
+                        T::from_expression(
+                            e,
+                            &self.selectors,
+                            &self.fixed,
+                            &self.instance,
+                            &self.advice,
+                            &self.challenges,
+                        )
+                    })
+                    .collect::<Vec<_>>();
+
+                // Get expressions for variables r, m, g
+                let r = T::new_challenge(data.r);
+                let m = T::new_witness(data.m);
+                let g = T::new_witness(data.g);
+
+                // Get expressions for variables theta_0, ..., theta_k
+                let thetas = data
+                    .thetas
+                    .iter()
+                    .map(|theta| T::new_challenge(*theta))
+                    .collect::<Vec<_>>();
+
+                let one = T::new_constant(T::F::ONE);
+
+                // h_a * (r + theta_1 * input_a_1 + ... + theta_k * input_a_k ) - 1 for each argument a
+                let input_constraints: Vec<_> = zip(inputs, data.h.iter())
+                    .map(|(inputs, h)| {
+                        T::new_witness(*h)
+                            * zip(inputs, thetas.iter()).fold(r.clone(), |acc, (input, theta)| {
+                                acc + (input * theta.clone())
+                            })
+                            - one.clone()
+                    })
+                    .collect();
+
+                let table_constraint = g * zip(tables, thetas.iter())
+                    .fold(r, |acc, (table, theta)| acc + (table * theta.clone()))
+                    - m;
+                input_constraints
+                    .into_iter()
+                    .chain(std::iter::once(table_constraint))
+            })
+            .collect();
+
+        let shuffle_constraints: Vec<_> = shuffles
+            .iter()
+            .zip(self.shuffles.iter())
             .flat_map(|(arg, data)| {
                 // Get expressions for inputs input_0, ..., input_k
                 let inputs = arg
@@ -79,9 +184,9 @@ impl<T: QueryType> Data<T> {
                     })
                     .collect::<Vec<_>>();
 
-                // Get expressions for tables table_0, ..., table_k
-                let tables = arg
-                    .table_expressions()
+                // Get expressions for shuffled values shuffle_0, ..., shuffle_k
+                let shuffles = arg
+                    .shuffle_expressions()
                     .iter()
                     .map(|e| {
                         T::from_expression(
@@ -95,9 +200,8 @@ impl<T: QueryType> Data<T> {
                     })
                     .collect::<Vec<_>>();
 
-                // Get expressions for variables r, m, g, h
+                // Get expressions for variables r, g, h
                 let r = T::new_challenge(data.r);
-                let m = T::new_witness(data.m);
                 let g = T::new_witness(data.g);
                 let h = T::new_witness(data.h);
 
@@ -110,36 +214,122 @@ impl<T: QueryType> Data<T> {
 
                 let one = T::new_constant(T::F::ONE);
 
-                // h * (r + theta_1 * input_1 + ... + theta_k * input_k )
+                // h * (r + theta_1 * input_1 + ... + theta_k * input_k ) - 1
                 let input_constraint =
                     h * zip(inputs, thetas.iter()).fold(r.clone(), |acc, (input, theta)| {
                         acc + (input * theta.clone())
-                    }) - one;
+                    }) - one.clone();
 
-                let table_constraint = g * zip(tables, thetas.iter())
-                    .fold(r, |acc, (table, theta)| acc + (table * theta.clone()))
-                    - m;
-                [input_constraint, table_constraint].into_iter()
+                // g * (r + theta_1 * shuffle_1 + ... + theta_k * shuffle_k ) - 1
+                let shuffle_constraint = g * zip(shuffles, thetas.iter())
+                    .fold(r, |acc, (shuffle, theta)| acc + (shuffle * theta.clone()))
+                    - one;
+                [input_constraint, shuffle_constraint].into_iter()
             })
             .collect();
 
-        [&gate_constraints[..], &lookup_constraints[..]].concat()
+        let permutation_constraints: Vec<_> = {
+            let data = &self.permutation;
+
+            let r = T::new_challenge(data.r);
+            let theta = T::new_challenge(data.theta);
+            let one = T::new_constant(T::F::ONE);
+
+            permutation
+                .columns
+                .iter()
+                .enumerate()
+                .flat_map(|(i, column)| {
+                    // Get expression for the values w of the column
+                    let w = match column.column_type() {
+                        Any::Advice(_) => T::new_witness(self.advice[column.index()]),
+                        Any::Fixed => T::new_fixed(self.fixed[column.index()]),
+                        Any::Instance => T::new_witness(self.instance[column.index()]),
+                    };
+
+                    // Get expressions for variables id, sigma, g, h
+                    let id = T::new_fixed(data.ids[i]);
+                    let sigma = T::new_fixed(data.sigmas[i]);
+                    let g = T::new_witness(data.g[i]);
+                    let h = T::new_witness(data.h[i]);
+
+                    // h * (r + w + theta * id) - 1
+                    let id_constraint =
+                        h * (r.clone() + w.clone() + theta.clone() * id) - one.clone();
+                    // g * (r + w + theta * sigma) - 1
+                    let sigma_constraint =
+                        g * (r.clone() + w + theta.clone() * sigma) - one.clone();
+                    [id_constraint, sigma_constraint].into_iter()
+                })
+                .collect()
+        };
+
+        [
+            &gate_constraints[..],
+            &lookup_constraints[..],
+            &shuffle_constraints[..],
+            &permutation_constraints[..],
+        ]
+        .concat()
+    }
+
+    /// Returns the expressions whose sum over all rows must vanish.
+    /// These correspond to the linear logUp constraints which are not folded,
+    /// and must instead be checked by the decider.
+    pub fn linear_constraints(&self) -> Vec<QueriedExpression<T>> {
+        let lookup_constraints = self.lookups.iter().map(|data| {
+            let hs = data.h.iter().map(|h| T::new_witness(*h));
+            T::new_witness(data.g) - T::sum(hs)
+        });
+
+        let shuffle_constraints = self
+            .shuffles
+            .iter()
+            .map(|data| T::new_witness(data.g) - T::new_witness(data.h));
+
+        let permutation_constraint = {
+            let data = &self.permutation;
+            let hs = data.h.iter().map(|h| T::new_witness(*h));
+            let gs = data.g.iter().map(|g| -T::new_witness(*g));
+            (!data.h.is_empty()).then(|| T::sum(hs.chain(gs)))
+        };
+
+        lookup_constraints
+            .chain(shuffle_constraints)
+            .chain(permutation_constraint)
+            .collect()
     }
 
+    /// Returns the random linear combination ∑ⱼ yⱼ⋅Gⱼ of all constraints.
+    /// The compressed constraint at row i is obtained by multiplying it with βᵢ,
+    /// which is not a column of the accumulator and must therefore be applied by the caller.
     pub fn full_constraint(
         &self,
         gates: &[plonk::circuit::Gate<T::F>],
-        lookups: &[plonk::lookup::Argument<T::F>],
+        lookups: &[LookupTable<T::F>],
+        shuffles: &[plonk::shuffle::Argument<T::F>],
+        permutation: &permutation::Argument,
     ) -> QueriedExpression<T> {
-        let beta = T::new_witness(self.beta);
-
-        let constraints = self.all_constraints(gates, lookups);
+        let constraints = self.all_constraints(gates, lookups, shuffles, permutation);
 
         let ys = self
             .ys
             .iter()
             .map(|y| T::new_challenge(*y))
             .collect::<Vec<_>>();
-        beta * T::linear_combination(&constraints, &ys)
+        T::linear_combination(&constraints, &ys)
+    }
+
+    /// Returns the random linear combination ∑ⱼ yⱼ⋅Gⱼ of all constraints, compiled into graphs
+    /// whose outputs add up to it. See `ExpressionGraph::linear_combination`.
+    pub fn full_constraint_graphs(
+        &self,
+        gates: &[plonk::circuit::Gate<T::F>],
+        lookups: &[LookupTable<T::F>],
+        shuffles: &[plonk::shuffle::Argument<T::F>],
+        permutation: &permutation::Argument,
+    ) -> Vec<ExpressionGraph<T>> {
+        let constraints = self.all_constraints(gates, lookups, shuffles, permutation);
+        ExpressionGraph::linear_combination(constraints, &self.ys)
     }
 }
@@ -1,8 +1,11 @@
-use std::ops::{Add, Mul, Neg, Sub};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ops::{Add, Mul, Neg, Sub},
+};
 
 use ff::Field;
 
-use crate::poly::Rotation;
+use crate::{plonk, poly::Rotation};
 
 /// Mirror of a `plonk::Expression` where nodes have been moved to a `Queries` structure, and replaced with their indices therein.
 #[derive(Clone)]
@@ -146,11 +149,11 @@ impl<T> ColumnQuery<T> {
     }
 }
 
-/// Reference to a challenge variable
-/// TODO: Circuit expressions containing powers of challenges will blow up the degree of the error polynomial.
-/// By substituting powers of a challenge with a symbolic variable, we can reduce the degree of the error polynomial.
-/// This requires the verifier to compute powers of the challenges it sends to the prover,
-/// and treat them as separate challenges during folding.
+/// Reference to a challenge variable.
+/// Circuit expressions containing powers of challenges would blow up the degree of the error polynomial.
+/// Instead, each power cᵈ of a challenge is substituted with a separate variable,
+/// whose value is computed by the verifier when creating the accumulator,
+/// and which is folded independently of the other powers of c.
 #[derive(Clone, Copy, PartialEq)]
 pub struct ChallengeQuery<T> {
     pub value: T,
@@ -176,56 +179,69 @@ pub trait QueryType {
     type Witness: Copy + PartialEq;
 
     /// Convert a plonk::Expression into a QueriedExpression, where leaves contain references to the underlying data.
+    ///
+    /// Powers of challenges are substituted by a single challenge variable, where `challenges[i][d-1]`
+    /// is the variable corresponding to cᵢᵈ.
+    /// All occurrences of the challenge cᵢ among the factors of a product are grouped into a single power.
     fn from_expression(
         expr: &crate::plonk::Expression<Self::F>,
         selectors: &[Self::Fixed],
         fixed: &[Self::Fixed],
         instance: &[Self::Witness],
         advice: &[Self::Witness],
-        challenges: &[Self::Challenge],
+        challenges: &[Vec<Self::Challenge>],
     ) -> QueriedExpression<Self> {
-        expr.evaluate(
-            &QueriedExpression::<Self>::Constant,
-            &|selector_column| {
-                QueriedExpression::<Self>::Fixed(ColumnQuery {
-                    column: selectors[selector_column.index()],
-                    rotation: 0,
-                })
-            },
-            &|fixed_column| {
+        let recurse = |expr: &crate::plonk::Expression<Self::F>| {
+            Self::from_expression(expr, selectors, fixed, instance, advice, challenges)
+        };
+        match expr {
+            plonk::Expression::Constant(constant) => Self::new_constant(*constant),
+            plonk::Expression::Selector(selector_column) => {
+                Self::new_fixed(selectors[selector_column.index()])
+            }
+            plonk::Expression::Fixed(fixed_column) => {
                 QueriedExpression::<Self>::Fixed(ColumnQuery {
                     column: fixed[fixed_column.column_index()],
                     rotation: fixed_column.rotation().0,
                 })
-            },
-            &|advice_column| {
-                QueriedExpression::<Self>::Witness(ColumnQuery {
-                    column: advice[advice_column.column_index()],
+            }
+            plonk::Expression::Advice(advice_column) => Self::new_witness_with_rotations(
+                advice[advice_column.column_index()],
+                advice_column.rotation(),
+            ),
+            plonk::Expression::Instance(instance_column) => Self::new_witness_with_rotations(
+                instance[instance_column.column_index()],
+                instance_column.rotation(),
+            ),
+            plonk::Expression::Challenge(challenge) => {
+                Self::new_challenge(challenges[challenge.index()][0])
+            }
+            plonk::Expression::Negated(negated) => -recurse(negated),
+            plonk::Expression::Sum(a, b) => recurse(a) + recurse(b),
+            plonk::Expression::Product(_, _) => {
+                let mut factors = Vec::new();
+                product_factors(expr, &mut factors);
 
-                    rotation: advice_column.rotation().0,
-                })
-            },
-            &|instance_column| {
-                QueriedExpression::<Self>::Witness(ColumnQuery {
-                    column: instance[instance_column.column_index()],
-                    rotation: instance_column.rotation().0,
-                })
-            },
-            &|challenge| {
-                QueriedExpression::<Self>::Challenge(ChallengeQuery {
-                    value: challenges[challenge.index()],
-                })
-            },
-            &|negated| QueriedExpression::<Self>::Negated(negated.into()),
-            &|a, b| QueriedExpression::<Self>::Sum(a.into(), b.into()),
-            &|a, b| QueriedExpression::<Self>::Product(a.into(), b.into()),
-            &|a, b| {
-                QueriedExpression::<Self>::Product(
-                    a.into(),
-                    QueriedExpression::<Self>::Constant(b).into(),
-                )
-            },
-        )
+                // Count the number of occurrences of each challenge
+                let mut challenge_powers = BTreeMap::<usize, usize>::new();
+                let mut others = Vec::new();
+                for factor in factors {
+                    if let plonk::Expression::Challenge(challenge) = factor {
+                        *challenge_powers.entry(challenge.index()).or_default() += 1;
+                    } else {
+                        others.push(recurse(factor));
+                    }
+                }
+
+                challenge_powers
+                    .into_iter()
+                    .map(|(index, power)| Self::new_challenge(challenges[index][power - 1]))
+                    .chain(others)
+                    .reduce(|acc, factor| acc * factor)
+                    .unwrap()
+            }
+            plonk::Expression::Scaled(a, scalar) => recurse(a) * Self::new_constant(*scalar),
+        }
     }
 
     /// Create a Constant QueriedExpression
@@ -323,3 +339,81 @@ impl<T: QueryType> IndexedExpression<T> {
         }
     }
 }
+
+/// Appends to `factors` the sub-expressions of `expr` which are not themselves products.
+fn product_factors<'a, F: Field>(
+    expr: &'a plonk::Expression<F>,
+    factors: &mut Vec<&'a plonk::Expression<F>>,
+) {
+    if let plonk::Expression::Product(a, b) = expr {
+        product_factors(a, factors);
+        product_factors(b, factors);
+    } else {
+        factors.push(expr);
+    }
+}
+
+/// For each challenge cᵢ of the constraint system, updates `max_powers[i]` with the largest d such that
+/// cᵢᵈ appears in `expr` after the substitution performed by `QueryType::from_expression`.
+pub fn max_challenge_powers<F: Field>(expr: &plonk::Expression<F>, max_powers: &mut [usize]) {
+    match expr {
+        plonk::Expression::Challenge(challenge) => {
+            let max_power = &mut max_powers[challenge.index()];
+            *max_power = std::cmp::max(*max_power, 1);
+        }
+        plonk::Expression::Negated(a) | plonk::Expression::Scaled(a, _) => {
+            max_challenge_powers(a, max_powers)
+        }
+        plonk::Expression::Sum(a, b) => {
+            max_challenge_powers(a, max_powers);
+            max_challenge_powers(b, max_powers);
+        }
+        plonk::Expression::Product(_, _) => {
+            let mut factors = Vec::new();
+            product_factors(expr, &mut factors);
+
+            let mut challenge_powers = BTreeMap::<usize, usize>::new();
+            for factor in factors {
+                if let plonk::Expression::Challenge(challenge) = factor {
+                    *challenge_powers.entry(challenge.index()).or_default() += 1;
+                } else {
+                    max_challenge_powers(factor, max_powers);
+                }
+            }
+            for (index, power) in challenge_powers {
+                max_powers[index] = std::cmp::max(max_powers[index], power);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns the degree of `expr` during folding, where each power of a challenge is a single variable.
+pub fn folding_degree<F: Field>(expr: &plonk::Expression<F>) -> usize {
+    match expr {
+        plonk::Expression::Constant(_)
+        | plonk::Expression::Selector(_)
+        | plonk::Expression::Fixed(_) => 0,
+        plonk::Expression::Advice(_)
+        | plonk::Expression::Instance(_)
+        | plonk::Expression::Challenge(_) => 1,
+        plonk::Expression::Negated(a) | plonk::Expression::Scaled(a, _) => folding_degree(a),
+        plonk::Expression::Sum(a, b) => std::cmp::max(folding_degree(a), folding_degree(b)),
+        plonk::Expression::Product(_, _) => {
+            let mut factors = Vec::new();
+            product_factors(expr, &mut factors);
+
+            let mut challenges = BTreeSet::new();
+            factors
+                .into_iter()
+                .map(|factor| match factor {
+                    // Only the first occurrence of each challenge increases the degree
+                    plonk::Expression::Challenge(challenge) => {
+                        challenges.insert(challenge.index()) as usize
+                    }
+                    _ => folding_degree(factor),
+                })
+                .sum()
+        }
+    }
+}
@@ -0,0 +1,760 @@
+use std::{collections::HashMap, iter::zip};
+
+use ff::Field;
+
+use super::expression::{ChallengeQuery, ColumnQuery, Expression, QueriedExpression, QueryType};
+
+// Constants which are always present in an `ExpressionGraph`.
+const ZERO: ValueSource = ValueSource::Constant(0);
+const ONE: ValueSource = ValueSource::Constant(1);
+const TWO: ValueSource = ValueSource::Constant(2);
+
+/// Operand of a `Calculation`, given by its index in the corresponding list of an `ExpressionGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ValueSource {
+    Constant(usize),
+    Challenge(usize),
+    Fixed(usize),
+    Witness(usize),
+    /// Result of a calculation which only depends on constants and challenges.
+    Invariant(usize),
+    /// Result of a calculation which is performed at each row.
+    Intermediate(usize),
+}
+
+impl ValueSource {
+    /// Returns true if the value is the same at all rows.
+    fn is_invariant(&self) -> bool {
+        matches!(
+            self,
+            Self::Constant(_) | Self::Challenge(_) | Self::Invariant(_)
+        )
+    }
+}
+
+/// Operation performed by an `ExpressionGraph`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Calculation {
+    Add(ValueSource, ValueSource),
+    Sub(ValueSource, ValueSource),
+    Mul(ValueSource, ValueSource),
+    Square(ValueSource),
+    Double(ValueSource),
+    Negate(ValueSource),
+}
+
+impl Calculation {
+    /// Returns true if all operands are the same at all rows.
+    fn is_invariant(&self) -> bool {
+        match self {
+            Self::Add(a, b) | Self::Sub(a, b) | Self::Mul(a, b) => {
+                a.is_invariant() && b.is_invariant()
+            }
+            Self::Square(a) | Self::Double(a) | Self::Negate(a) => a.is_invariant(),
+        }
+    }
+
+    /// Writes the result of the calculation at each evaluation point into `result`.
+    fn evaluate<F: Field>(&self, values: &Values<'_, F>, result: &mut [F]) {
+        fn unary<F: Field>(a: Operand<'_, F>, result: &mut [F], op: impl Fn(F) -> F) {
+            for (idx, result) in result.iter_mut().enumerate() {
+                *result = op(a.get(idx));
+            }
+        }
+        fn binary<F: Field>(
+            a: Operand<'_, F>,
+            b: Operand<'_, F>,
+            result: &mut [F],
+            op: impl Fn(F, F) -> F,
+        ) {
+            for (idx, result) in result.iter_mut().enumerate() {
+                *result = op(a.get(idx), b.get(idx));
+            }
+        }
+
+        match *self {
+            Self::Add(a, b) => binary(values.get(a), values.get(b), result, |a, b| a + b),
+            Self::Sub(a, b) => binary(values.get(a), values.get(b), result, |a, b| a - b),
+            Self::Mul(a, b) => binary(values.get(a), values.get(b), result, |a, b| a * b),
+            Self::Square(a) => unary(values.get(a), result, |a| a.square()),
+            Self::Double(a) => unary(values.get(a), result, |a| a.double()),
+            Self::Negate(a) => unary(values.get(a), result, |a| -a),
+        }
+    }
+}
+
+/// Value of an operand at each evaluation point.
+#[derive(Clone, Copy)]
+pub enum Operand<'a, F> {
+    /// The value is the same at all points.
+    Scalar(F),
+    Evals(&'a [F]),
+}
+
+impl<'a, F: Copy> Operand<'a, F> {
+    /// Returns the value at the evaluation point `idx`.
+    pub fn get(&self, idx: usize) -> F {
+        match self {
+            Self::Scalar(value) => *value,
+            Self::Evals(evals) => evals[idx],
+        }
+    }
+}
+
+/// Flat list of calculations evaluating a `QueriedExpression`, obtained by constant folding
+/// and common subexpression elimination.
+///
+/// Calculations which only depend on constants and challenges are invariant,
+/// and are evaluated once for all rows. The remaining ones are evaluated at each row.
+/// Every calculation can be evaluated over several points at once, which is used during folding
+/// where the challenges and witness values are polynomials evaluated over a domain.
+pub struct ExpressionGraph<T: QueryType> {
+    pub constants: Vec<T::F>,
+    pub challenges: Vec<ChallengeQuery<T::Challenge>>,
+    pub fixed: Vec<ColumnQuery<T::Fixed>>,
+    pub witness: Vec<ColumnQuery<T::Witness>>,
+    // Indices of the fixed queries which are factors of the expression.
+    // If any of them vanishes at a row, then so does the expression.
+    pub selectors: Vec<usize>,
+    invariants: Vec<Calculation>,
+    calculations: Vec<Calculation>,
+    output: ValueSource,
+    degree: usize,
+}
+
+impl<T: QueryType> ExpressionGraph<T> {
+    pub fn new(expr: QueriedExpression<T>) -> Self {
+        let mut builder = GraphBuilder {
+            graph: Self {
+                constants: vec![T::F::ZERO, T::F::ONE, T::F::ONE.double()],
+                challenges: Vec::new(),
+                fixed: Vec::new(),
+                witness: Vec::new(),
+                selectors: Vec::new(),
+                invariants: Vec::new(),
+                calculations: Vec::new(),
+                output: ZERO,
+                degree: expr.degree(),
+            },
+            cache: HashMap::new(),
+        };
+        let output = builder.add_expression(&expr);
+
+        let mut graph = builder.graph;
+        graph.output = output;
+        graph.selectors = fixed_factors::<T>(&expr)
+            .iter()
+            .map(|query| find_or_insert(&mut graph.fixed, query))
+            .collect();
+        graph
+    }
+
+    /// Returns the graphs whose outputs add up to ∑ⱼ yⱼ⋅Gⱼ.
+    ///
+    /// Constraints with the same degree and the same fixed factors S are grouped into a single graph
+    /// evaluating S⋅∑ⱼ yⱼ⋅Pⱼ, where Gⱼ = S⋅Pⱼ. This allows the whole group to be skipped at rows where
+    /// a selector is off, and subexpressions shared by the constraints of a gate to be computed once.
+    pub fn linear_combination(
+        constraints: Vec<QueriedExpression<T>>,
+        ys: &[T::Challenge],
+    ) -> Vec<Self> {
+        assert_eq!(constraints.len(), ys.len());
+
+        // Fixed factors, degree and terms yⱼ⋅Pⱼ of each group
+        let mut groups: Vec<(Vec<_>, usize, Vec<_>)> = Vec::new();
+        for (constraint, y) in zip(constraints, ys) {
+            let degree = constraint.degree();
+            let (selectors, constraint) = split_fixed_factors::<T>(constraint);
+            let term = T::new_challenge(*y) * constraint;
+            match groups
+                .iter_mut()
+                .find(|(other, other_degree, _)| *other == selectors && *other_degree == degree)
+            {
+                Some((_, _, terms)) => terms.push(term),
+                None => groups.push((selectors, degree, vec![term])),
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(selectors, _, terms)| {
+                let expr = selectors.into_iter().fold(T::sum(terms), |expr, selector| {
+                    Expression::Fixed(selector) * expr
+                });
+                Self::new(expr)
+            })
+            .collect()
+    }
+
+    /// Returns the degree of the expression, considering challenges and witness queries as variables.
+    pub fn degree(&self) -> usize {
+        self.degree
+    }
+
+    /// Allocates the buffers for evaluating the graph over `num_evals` points.
+    pub fn data(&self, num_evals: usize) -> GraphData<T::F> {
+        GraphData {
+            num_evals,
+            challenges: vec![T::F::ZERO; self.challenges.len() * num_evals],
+            fixed: vec![T::F::ZERO; self.fixed.len()],
+            witness: vec![T::F::ZERO; self.witness.len() * num_evals],
+            invariants: vec![T::F::ZERO; self.invariants.len() * num_evals],
+            intermediates: vec![T::F::ZERO; self.calculations.len() * num_evals],
+        }
+    }
+
+    /// Evaluates the invariant calculations, given the challenge values in `data`.
+    /// This only needs to be done once, before evaluating the graph at each row.
+    pub fn evaluate_invariants(&self, data: &mut GraphData<T::F>) {
+        let num_evals = data.num_evals;
+        for (idx, calculation) in self.invariants.iter().enumerate() {
+            let (previous, result) = data.invariants.split_at_mut(idx * num_evals);
+            let values = Values {
+                constants: &self.constants,
+                challenges: &data.challenges,
+                fixed: &[],
+                witness: &[],
+                invariants: previous,
+                intermediates: &[],
+                num_evals,
+            };
+            calculation.evaluate(&values, &mut result[..num_evals]);
+        }
+    }
+
+    /// Evaluates the expression at a row, given the fixed and witness values in `data`.
+    pub fn evaluate<'d>(&'d self, data: &'d mut GraphData<T::F>) -> Operand<'d, T::F> {
+        let num_evals = data.num_evals;
+        for (idx, calculation) in self.calculations.iter().enumerate() {
+            let (previous, result) = data.intermediates.split_at_mut(idx * num_evals);
+            let values = Values {
+                constants: &self.constants,
+                challenges: &data.challenges,
+                fixed: &data.fixed,
+                witness: &data.witness,
+                invariants: &data.invariants,
+                intermediates: previous,
+                num_evals,
+            };
+            calculation.evaluate(&values, &mut result[..num_evals]);
+        }
+
+        let data: &'d GraphData<T::F> = data;
+        Values {
+            constants: &self.constants,
+            challenges: &data.challenges,
+            fixed: &data.fixed,
+            witness: &data.witness,
+            invariants: &data.invariants,
+            intermediates: &data.intermediates,
+            num_evals,
+        }
+        .get(self.output)
+    }
+}
+
+/// Buffers for evaluating an `ExpressionGraph` over `num_evals` points.
+/// Fixed values are the same at all points, while the other values are stored contiguously
+/// for each challenge, witness and calculation.
+#[derive(Clone)]
+pub struct GraphData<F> {
+    num_evals: usize,
+    challenges: Vec<F>,
+    fixed: Vec<F>,
+    witness: Vec<F>,
+    invariants: Vec<F>,
+    intermediates: Vec<F>,
+}
+
+impl<F> GraphData<F> {
+    pub fn num_evals(&self) -> usize {
+        self.num_evals
+    }
+
+    /// Returns the buffer for the evaluations of the challenge at index `idx`.
+    pub fn challenge_mut(&mut self, idx: usize) -> &mut [F] {
+        &mut self.challenges[idx * self.num_evals..(idx + 1) * self.num_evals]
+    }
+
+    /// Returns the buffer for the value of the fixed query at index `idx`.
+    pub fn fixed_mut(&mut self, idx: usize) -> &mut F {
+        &mut self.fixed[idx]
+    }
+
+    /// Returns the buffer for the evaluations of the witness query at index `idx`.
+    pub fn witness_mut(&mut self, idx: usize) -> &mut [F] {
+        &mut self.witness[idx * self.num_evals..(idx + 1) * self.num_evals]
+    }
+}
+
+/// Views into the buffers from which the operands of a calculation are read.
+struct Values<'a, F> {
+    constants: &'a [F],
+    challenges: &'a [F],
+    fixed: &'a [F],
+    witness: &'a [F],
+    invariants: &'a [F],
+    intermediates: &'a [F],
+    num_evals: usize,
+}
+
+impl<'a, F: Copy> Values<'a, F> {
+    fn get(&self, source: ValueSource) -> Operand<'a, F> {
+        let evals = |values: &'a [F], idx: usize| {
+            Operand::Evals(&values[idx * self.num_evals..(idx + 1) * self.num_evals])
+        };
+        match source {
+            ValueSource::Constant(idx) => Operand::Scalar(self.constants[idx]),
+            ValueSource::Challenge(idx) => evals(self.challenges, idx),
+            ValueSource::Fixed(idx) => Operand::Scalar(self.fixed[idx]),
+            ValueSource::Witness(idx) => evals(self.witness, idx),
+            ValueSource::Invariant(idx) => evals(self.invariants, idx),
+            ValueSource::Intermediate(idx) => evals(self.intermediates, idx),
+        }
+    }
+}
+
+/// Compiles a `QueriedExpression` into an `ExpressionGraph`.
+struct GraphBuilder<T: QueryType> {
+    graph: ExpressionGraph<T>,
+    // Existing calculations, used for eliminating common subexpressions
+    cache: HashMap<Calculation, ValueSource>,
+}
+
+impl<T: QueryType> GraphBuilder<T> {
+    fn add_expression(&mut self, expr: &QueriedExpression<T>) -> ValueSource {
+        match expr {
+            Expression::Constant(constant) => self.add_constant(*constant),
+            Expression::Challenge(challenge) => {
+                ValueSource::Challenge(find_or_insert(&mut self.graph.challenges, challenge))
+            }
+            Expression::Fixed(fixed) => {
+                ValueSource::Fixed(find_or_insert(&mut self.graph.fixed, fixed))
+            }
+            Expression::Witness(witness) => {
+                ValueSource::Witness(find_or_insert(&mut self.graph.witness, witness))
+            }
+            Expression::Negated(a) => {
+                let a = self.add_expression(a);
+                self.negate(a)
+            }
+            Expression::Sum(a, b) => {
+                let a = self.add_expression(a);
+                // Undo the subtraction a − b = a + (−b)
+                if let Expression::Negated(b) = &**b {
+                    let b = self.add_expression(b);
+                    self.sub(a, b)
+                } else {
+                    let b = self.add_expression(b);
+                    self.add(a, b)
+                }
+            }
+            Expression::Product(_, _) => {
+                let mut factors = Vec::new();
+                product_factors::<T>(expr, &mut factors);
+                let mut factors: Vec<_> = factors
+                    .into_iter()
+                    .map(|factor| self.add_expression(factor))
+                    .collect();
+
+                // Multiply the invariant factors first, so that their product is computed once for all rows,
+                // and constants come first so that they are folded together.
+                factors.sort_by_key(|factor| (!factor.is_invariant(), *factor));
+                factors
+                    .into_iter()
+                    .reduce(|acc, factor| self.mul(acc, factor))
+                    .unwrap()
+            }
+        }
+    }
+
+    fn add_constant(&mut self, constant: T::F) -> ValueSource {
+        ValueSource::Constant(find_or_insert(&mut self.graph.constants, &constant))
+    }
+
+    fn constant(&self, source: ValueSource) -> Option<T::F> {
+        match source {
+            ValueSource::Constant(idx) => Some(self.graph.constants[idx]),
+            _ => None,
+        }
+    }
+
+    fn calculation(&self, source: ValueSource) -> Option<Calculation> {
+        match source {
+            ValueSource::Invariant(idx) => Some(self.graph.invariants[idx]),
+            ValueSource::Intermediate(idx) => Some(self.graph.calculations[idx]),
+            _ => None,
+        }
+    }
+
+    fn add_calculation(&mut self, calculation: Calculation) -> ValueSource {
+        if let Some(source) = self.cache.get(&calculation) {
+            return *source;
+        }
+        let source = if calculation.is_invariant() {
+            self.graph.invariants.push(calculation);
+            ValueSource::Invariant(self.graph.invariants.len() - 1)
+        } else {
+            self.graph.calculations.push(calculation);
+            ValueSource::Intermediate(self.graph.calculations.len() - 1)
+        };
+        self.cache.insert(calculation, source);
+        source
+    }
+
+    fn negate(&mut self, a: ValueSource) -> ValueSource {
+        if let Some(a) = self.constant(a) {
+            return self.add_constant(-a);
+        }
+        match self.calculation(a) {
+            Some(Calculation::Negate(a)) => a,
+            _ => self.add_calculation(Calculation::Negate(a)),
+        }
+    }
+
+    fn add(&mut self, a: ValueSource, b: ValueSource) -> ValueSource {
+        if let (Some(a), Some(b)) = (self.constant(a), self.constant(b)) {
+            return self.add_constant(a + b);
+        }
+        if a == ZERO {
+            b
+        } else if b == ZERO {
+            a
+        } else if a == b {
+            self.add_calculation(Calculation::Double(a))
+        } else {
+            self.add_calculation(Calculation::Add(a.min(b), a.max(b)))
+        }
+    }
+
+    fn sub(&mut self, a: ValueSource, b: ValueSource) -> ValueSource {
+        if let (Some(a), Some(b)) = (self.constant(a), self.constant(b)) {
+            return self.add_constant(a - b);
+        }
+        if b == ZERO {
+            a
+        } else if a == ZERO {
+            self.negate(b)
+        } else if a == b {
+            ZERO
+        } else {
+            self.add_calculation(Calculation::Sub(a, b))
+        }
+    }
+
+    fn mul(&mut self, a: ValueSource, b: ValueSource) -> ValueSource {
+        if let (Some(a), Some(b)) = (self.constant(a), self.constant(b)) {
+            return self.add_constant(a * b);
+        }
+        if a == ZERO || b == ZERO {
+            ZERO
+        } else if a == ONE {
+            b
+        } else if b == ONE {
+            a
+        } else if a == TWO {
+            self.add_calculation(Calculation::Double(b))
+        } else if b == TWO {
+            self.add_calculation(Calculation::Double(a))
+        } else if a == b {
+            self.add_calculation(Calculation::Square(a))
+        } else {
+            self.add_calculation(Calculation::Mul(a.min(b), a.max(b)))
+        }
+    }
+}
+
+fn find_or_insert<T: PartialEq + Copy>(container: &mut Vec<T>, elem: &T) -> usize {
+    if let Some(idx) = container.iter().position(|x| *x == *elem) {
+        idx
+    } else {
+        container.push(*elem);
+        container.len() - 1
+    }
+}
+
+/// Appends to `factors` the sub-expressions of `expr` which are not themselves products.
+fn product_factors<'a, T: QueryType>(
+    expr: &'a QueriedExpression<T>,
+    factors: &mut Vec<&'a QueriedExpression<T>>,
+) {
+    if let Expression::Product(a, b) = expr {
+        product_factors::<T>(a, factors);
+        product_factors::<T>(b, factors);
+    } else {
+        factors.push(expr);
+    }
+}
+
+/// Returns the fixed queries which are factors of the product at the root of `expr`.
+fn fixed_factors<T: QueryType>(expr: &QueriedExpression<T>) -> Vec<ColumnQuery<T::Fixed>> {
+    match expr {
+        Expression::Fixed(query) => vec![*query],
+        Expression::Negated(expr) => fixed_factors::<T>(expr),
+        Expression::Product(a, b) => {
+            let mut factors = fixed_factors::<T>(a);
+            factors.extend(fixed_factors::<T>(b));
+            factors
+        }
+        _ => vec![],
+    }
+}
+
+/// Splits `expr` = S⋅P into the fixed queries whose product is S, and the remaining expression P.
+fn split_fixed_factors<T: QueryType>(
+    expr: QueriedExpression<T>,
+) -> (Vec<ColumnQuery<T::Fixed>>, QueriedExpression<T>) {
+    match expr {
+        Expression::Fixed(query) => (vec![query], T::new_constant(T::F::ONE)),
+        Expression::Negated(expr) => {
+            let (factors, expr) = split_fixed_factors::<T>(*expr);
+            (factors, -expr)
+        }
+        Expression::Product(a, b) => {
+            let (mut factors, a) = split_fixed_factors::<T>(*a);
+            let (factors_b, b) = split_fixed_factors::<T>(*b);
+            factors.extend(factors_b);
+            (factors, a * b)
+        }
+        expr => (vec![], expr),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ff::Field;
+    use halo2curves::pasta::{pallas, Fp};
+
+    use super::{Calculation, ExpressionGraph, ValueSource, ZERO};
+    use crate::{
+        protostar::{
+            accumulator::{compressed_verifier::sqrt_stride, Accumulator},
+            constraints::{
+                expression::{QueriedExpression, QueryType},
+                labeled::{ColumnLabel, Labeled},
+                polynomial::CommittedRef,
+                Data,
+            },
+            verifier::tests::{fixture, range_check_circuits, Fixture},
+            LookupChallenges,
+        },
+        transcript::TranscriptWriterBuffer,
+    };
+
+    type Expr = QueriedExpression<Labeled<Fp>>;
+
+    fn constant(value: u64) -> Expr {
+        Labeled::<Fp>::new_constant(Fp::from(value))
+    }
+
+    fn challenge(value: u64) -> Expr {
+        Labeled::<Fp>::new_challenge(Fp::from(value))
+    }
+
+    fn fixed(idx: usize) -> Expr {
+        Labeled::<Fp>::new_fixed(ColumnLabel::Fixed(idx))
+    }
+
+    fn advice(idx: usize) -> Expr {
+        Labeled::<Fp>::new_witness(ColumnLabel::Advice(idx))
+    }
+
+    /// Value of the column `label` at the single row at which the expressions are evaluated.
+    fn column_value(label: ColumnLabel) -> Fp {
+        match label {
+            ColumnLabel::Fixed(idx) => Fp::from(11 + idx as u64),
+            ColumnLabel::Advice(idx) => Fp::from(101 + idx as u64),
+            _ => unreachable!(),
+        }
+    }
+
+    /// Evaluates the tree of `expr` directly.
+    fn evaluate_tree(expr: &Expr) -> Fp {
+        expr.evaluate(
+            &|&c| c,
+            &|challenge| challenge.value,
+            &|fixed| column_value(fixed.column),
+            &|witness| column_value(witness.column),
+            &|e| -*e,
+            &|a, b| a + b,
+            &|a, b| a * b,
+        )
+    }
+
+    /// Evaluates `graph` at a single point.
+    fn evaluate_graph(graph: &ExpressionGraph<Labeled<Fp>>) -> Fp {
+        let mut data = graph.data(1);
+        for (idx, challenge) in graph.challenges.iter().enumerate() {
+            data.challenge_mut(idx)[0] = challenge.value;
+        }
+        for (idx, query) in graph.fixed.iter().enumerate() {
+            *data.fixed_mut(idx) = column_value(query.column);
+        }
+        for (idx, query) in graph.witness.iter().enumerate() {
+            data.witness_mut(idx)[0] = column_value(query.column);
+        }
+        graph.evaluate_invariants(&mut data);
+        graph.evaluate(&mut data).get(0)
+    }
+
+    #[test]
+    fn test_constant_folding() {
+        // (2 + 3)⋅a − 5⋅a = 0
+        let expr = (constant(2) + constant(3)) * advice(0) - constant(5) * advice(0);
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(expr);
+        assert_eq!(graph.output, ZERO);
+        assert_eq!(evaluate_graph(&graph), Fp::ZERO);
+
+        // 1⋅a + 0 = a
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(constant(1) * advice(0) + constant(0));
+        assert_eq!(graph.output, ValueSource::Witness(0));
+        assert!(graph.calculations.is_empty());
+
+        // a⋅(3 − 1) = 2⋅a is computed as a doubling, and −(−a) as a
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(advice(0) * (constant(3) - constant(1)));
+        assert_eq!(
+            graph.calculations,
+            vec![Calculation::Double(ValueSource::Witness(0))]
+        );
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(-(-advice(0)));
+        assert_eq!(graph.output, ValueSource::Witness(0));
+    }
+
+    #[test]
+    fn test_common_subexpressions() {
+        // (a⋅b + c)² + b⋅a, where a⋅b and b⋅a are the same calculation.
+        // Operands of commutative calculations are sorted, and columns come before intermediates.
+        let ab_c = advice(0) * advice(1) + advice(2);
+        let expr = ab_c.clone() * ab_c + advice(1) * advice(0);
+        let expected = evaluate_tree(&expr);
+
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(expr);
+        let [a, b, c] = [0, 1, 2].map(ValueSource::Witness);
+        let ab = ValueSource::Intermediate(0);
+        let ab_c = ValueSource::Intermediate(1);
+        let square = ValueSource::Intermediate(2);
+        assert_eq!(
+            graph.calculations,
+            vec![
+                Calculation::Mul(a, b),
+                Calculation::Add(c, ab),
+                Calculation::Square(ab_c),
+                Calculation::Add(ab, square),
+            ]
+        );
+        assert_eq!(evaluate_graph(&graph), expected);
+    }
+
+    #[test]
+    fn test_invariants() {
+        // The product c₀⋅c₁ of challenges is computed once for all rows, before multiplying by a
+        let expr = challenge(3) * advice(0) * challenge(5);
+        let expected = evaluate_tree(&expr);
+
+        let graph = ExpressionGraph::<Labeled<Fp>>::new(expr);
+        assert_eq!(
+            graph.invariants,
+            vec![Calculation::Mul(
+                ValueSource::Challenge(0),
+                ValueSource::Challenge(1)
+            )]
+        );
+        assert_eq!(
+            graph.calculations,
+            vec![Calculation::Mul(
+                ValueSource::Witness(0),
+                ValueSource::Invariant(0)
+            )]
+        );
+        assert_eq!(evaluate_graph(&graph), expected);
+    }
+
+    #[test]
+    fn test_linear_combination() {
+        // q⋅(a − b) and q⋅(c − 1) share their selector and degree, unlike q⋅a⋅b and r⋅a
+        let constraints = vec![
+            fixed(0) * (advice(0) - advice(1)),
+            fixed(0) * advice(0) * advice(1),
+            fixed(0) * (advice(2) - constant(1)),
+            fixed(1) * advice(0),
+        ];
+        let ys: Vec<_> = (0..constraints.len() as u64)
+            .map(|y| Fp::from(7 + y))
+            .collect();
+        let expected: Fp = constraints
+            .iter()
+            .zip(ys.iter())
+            .map(|(constraint, y)| evaluate_tree(constraint) * y)
+            .sum();
+
+        let graphs = ExpressionGraph::<Labeled<Fp>>::linear_combination(constraints, &ys);
+        assert_eq!(graphs.len(), 3);
+        let selectors: Vec<_> = graphs
+            .iter()
+            .map(|graph| {
+                graph
+                    .selectors
+                    .iter()
+                    .map(|idx| graph.fixed[*idx].column)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        assert_eq!(
+            selectors,
+            vec![
+                vec![ColumnLabel::Fixed(0)],
+                vec![ColumnLabel::Fixed(0)],
+                vec![ColumnLabel::Fixed(1)],
+            ]
+        );
+        assert_eq!(
+            graphs
+                .iter()
+                .map(|graph| graph.degree())
+                .collect::<Vec<_>>(),
+            vec![2, 3, 2]
+        );
+        assert_eq!(graphs.iter().map(evaluate_graph).sum::<Fp>(), expected);
+    }
+
+    #[test]
+    fn test_expression_graph() {
+        let [circuit0, circuit1] = range_check_circuits();
+        let Fixture {
+            pk,
+            accs: [acc0, acc1],
+            mut transcript,
+            ..
+        } = fixture(9, [&circuit0, &circuit1], LookupChallenges::Shared);
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+        assert_ne!(acc.error, pallas::Scalar::ZERO);
+
+        // Evaluate the tree of ∑ⱼ yⱼ⋅Gⱼ at every row, without any optimization
+        let data = Data::<CommittedRef<'_, pallas::Affine>>::new(&pk, &acc);
+        let full_constraint = data.full_constraint(
+            pk.cs.gates(),
+            pk.lookup_tables(),
+            pk.cs.shuffles(),
+            pk.cs.permutation(),
+        );
+        let stride = sqrt_stride(pk.num_rows);
+        let error: pallas::Scalar = (0..pk.num_rows)
+            .map(|row_idx| {
+                acc.beta.beta_at(row_idx, stride)
+                    * full_constraint.evaluate(
+                        &|&c| c,
+                        &|&challenge| *challenge.value,
+                        &|&fixed| fixed.column.values[fixed.row_idx(row_idx, pk.num_rows)],
+                        &|&witness| witness.column.values[witness.row_idx(row_idx, pk.num_rows)],
+                        &|&e| -e,
+                        &|a, b| a + b,
+                        &|a, b| a * b,
+                    )
+            })
+            .sum();
+
+        // The graphs evaluated by the prover while folding and while recomputing the error agree with it
+        assert_eq!(error, acc.error);
+        assert_eq!(Accumulator::error(&pk, &acc), error);
+    }
+}
@@ -0,0 +1,123 @@
+use ff::Field;
+use halo2curves::CurveAffine;
+
+use crate::{plonk::ConstraintSystem, protostar::verifier::VerifierAccumulator};
+
+use super::{expression::QueryType, Data, LookupData, PermutationData, ShuffleData};
+
+/// Identifies a column of the proving key or of an accumulator, independently of how it is stored.
+/// This allows the prover and the verifier of the decider to agree on the set of queried columns,
+/// even though the former holds the values and the latter only the commitments.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColumnLabel {
+    Selector(usize),
+    Fixed(usize),
+    PermutationId(usize),
+    PermutationSigma(usize),
+    Instance(usize),
+    Advice(usize),
+    Beta,
+    BetaError,
+    BetaSqrt,
+    BetaSqrtError,
+    LookupM(usize),
+    LookupG(usize),
+    // Column hₖ of the lookup table i, for its k-th argument
+    LookupH(usize, usize),
+    ShuffleG(usize),
+    ShuffleH(usize),
+    PermutationG(usize),
+    PermutationH(usize),
+}
+
+/// Defines a QueriedExpression where leaves are labels of columns, and challenges are field elements.
+pub struct Labeled<F> {
+    _marker: std::marker::PhantomData<F>,
+}
+
+impl<F: Field> QueryType for Labeled<F> {
+    type F = F;
+    type Challenge = F;
+    type Fixed = ColumnLabel;
+    type Witness = ColumnLabel;
+}
+
+impl<F: Field> Data<Labeled<F>> {
+    /// Create a `Data` object for the accumulator `acc`, where columns are replaced by their label.
+    pub fn new<C>(cs: &ConstraintSystem<F>, acc: &VerifierAccumulator<C>) -> Self
+    where
+        C: CurveAffine<ScalarExt = F>,
+    {
+        let selectors: Vec<_> = (0..cs.num_selectors).map(ColumnLabel::Selector).collect();
+        let fixed: Vec<_> = (0..cs.num_fixed_columns).map(ColumnLabel::Fixed).collect();
+
+        let instance: Vec<_> = (0..cs.num_instance_columns)
+            .map(ColumnLabel::Instance)
+            .collect();
+
+        let advice: Vec<_> = (0..cs.num_advice_columns)
+            .map(ColumnLabel::Advice)
+            .collect();
+
+        let challenges = acc.challenges.clone();
+
+        let lookups: Vec<_> = acc
+            .lookup_accumulators
+            .iter()
+            .enumerate()
+            .map(|(i, lookup)| LookupData {
+                m: ColumnLabel::LookupM(i),
+                g: ColumnLabel::LookupG(i),
+                h: (0..lookup.h.len())
+                    .map(|k| ColumnLabel::LookupH(i, k))
+                    .collect(),
+                thetas: lookup.thetas.clone(),
+                r: lookup.r,
+            })
+            .collect();
+
+        let shuffles: Vec<_> = acc
+            .shuffle_accumulators
+            .iter()
+            .enumerate()
+            .map(|(i, shuffle)| ShuffleData {
+                g: ColumnLabel::ShuffleG(i),
+                h: ColumnLabel::ShuffleH(i),
+                thetas: shuffle.thetas.clone(),
+                r: shuffle.r,
+            })
+            .collect();
+
+        let num_permutation_columns = cs.permutation.columns.len();
+        let permutation = PermutationData {
+            ids: (0..num_permutation_columns)
+                .map(ColumnLabel::PermutationId)
+                .collect(),
+            sigmas: (0..num_permutation_columns)
+                .map(ColumnLabel::PermutationSigma)
+                .collect(),
+            g: (0..num_permutation_columns)
+                .map(ColumnLabel::PermutationG)
+                .collect(),
+            h: (0..num_permutation_columns)
+                .map(ColumnLabel::PermutationH)
+                .collect(),
+            r: acc.permutation_accumulator.r,
+            theta: acc.permutation_accumulator.theta,
+        };
+
+        let ys = acc.ys.clone();
+
+        Self {
+            fixed,
+            selectors,
+            instance,
+            advice,
+            challenges,
+            lookups,
+            shuffles,
+            permutation,
+            ys,
+        }
+    }
+}
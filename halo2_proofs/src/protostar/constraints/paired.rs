@@ -1,77 +1,123 @@
 use std::{
+    array::from_fn,
     iter::zip,
     ops::{AddAssign, Range},
 };
 
 use ff::Field;
 use halo2curves::CurveAffine;
+use rayon::prelude::{IntoParallelIterator, ParallelIterator};
 
 use crate::{
     arithmetic::{field_integers, lagrange_interpolate},
-    poly::{LagrangeCoeff, Polynomial},
-    protostar::{accumulator::Accumulator, ProvingKey},
+    protostar::{
+        accumulator::{
+            column::ColumnValues, compressed_verifier::sqrt_stride, lagrange_basis, Accumulator,
+        },
+        ProvingKey,
+    },
 };
 
 use super::{
-    expression::{IndexedExpression, QueriedExpression, QueryType},
-    Data, LookupData,
+    expression::{QueriedExpression, QueryType},
+    graph::{ExpressionGraph, GraphData},
+    Data, LookupData, PermutationData, ShuffleData,
 };
 
-/// Defines a QueriedExpression where leaves are references values from two accumulators.
-pub struct Paired<'a, F> {
+/// Defines a QueriedExpression where leaves are references values from `N` accumulators,
+/// which are a pair by default.
+pub struct Paired<'a, F, const N: usize = 2> {
     _marker: std::marker::PhantomData<&'a F>,
 }
 
-impl<'a, F: Field> QueryType for Paired<'a, F> {
+impl<'a, F: Field, const N: usize> QueryType for Paired<'a, F, N> {
     type F = F;
-    type Challenge = [&'a F; 2];
-    type Fixed = &'a Polynomial<F, LagrangeCoeff>;
-    type Witness = [&'a Polynomial<F, LagrangeCoeff>; 2];
+    type Challenge = [&'a F; N];
+    type Fixed = &'a ColumnValues<F>;
+    type Witness = [&'a ColumnValues<F>; N];
 }
 
-impl<'a, F: Field> Paired<'a, F> {
-    /// Create a `Data` object from two accumulators, where each variable contains a pair of references to the
-    /// some column from two different accumulators. This allows us to create a QueriedExpression where the leaves
-    /// contain these two references.
-    pub fn new_data<C>(
-        pk: &'a ProvingKey<C>,
-        acc0: &'a Accumulator<C>,
-        acc1: &'a Accumulator<C>,
-    ) -> Data<Self>
+impl<'a, F: Field, const N: usize> Paired<'a, F, N> {
+    /// Create a `Data` object from `N` accumulators, where each variable contains the references to the
+    /// same column from `N` different accumulators. This allows us to create a QueriedExpression where the leaves
+    /// contain these references.
+    pub fn new_data<C>(pk: &'a ProvingKey<C>, accs: [&'a Accumulator<C>; N]) -> Data<Self>
     where
         C: CurveAffine<ScalarExt = F>,
     {
         let selectors: Vec<_> = pk.selectors.iter().map(|c| &c.values).collect();
         let fixed: Vec<_> = pk.fixed.iter().map(|c| &c.values).collect();
 
-        let instance: Vec<_> = zip(&acc0.gate.instance, &acc1.gate.instance)
-            .map(|(i0, i1)| [&i0.values, &i1.values])
+        let instance: Vec<_> = (0..accs[0].gate.instance.len())
+            .map(|i| from_fn(|k| &accs[k].gate.instance[i].values))
             .collect();
 
-        let advice: Vec<_> = zip(&acc0.gate.advice, &acc1.gate.advice)
-            .map(|(a0, a1)| [&a0.values, &a1.values])
+        let advice: Vec<_> = (0..accs[0].gate.advice.len())
+            .map(|i| from_fn(|k| &accs[k].gate.advice[i].values))
             .collect();
 
-        let challenges: Vec<_> = zip(&acc0.gate.challenges, &acc1.gate.challenges)
-            .map(|(c0, c1)| [c0, c1])
+        let challenges: Vec<_> = (0..accs[0].gate.challenges.len())
+            .map(|i| {
+                (0..accs[0].gate.challenges[i].len())
+                    .map(|power| from_fn(|k| &accs[k].gate.challenges[i][power]))
+                    .collect()
+            })
             .collect();
 
-        let beta = [&acc0.beta.beta.values, &acc1.beta.beta.values];
-
-        let lookups: Vec<_> = zip(&acc0.lookups, &acc1.lookups)
-            .map(|(lookup0, lookup1)| {
-                let m = [&lookup0.m.values, &lookup1.m.values];
-                let g = [&lookup0.g.values, &lookup1.g.values];
-                let h = [&lookup0.h.values, &lookup1.h.values];
-                let thetas: Vec<_> = zip(&lookup0.thetas, &lookup1.thetas)
-                    .map(|(theta0, theta1)| [theta0, theta1])
+        let lookups: Vec<_> = (0..accs[0].lookups.len())
+            .map(|i| {
+                let lookups: [_; N] = from_fn(|k| &accs[k].lookups[i]);
+                let m = lookups.map(|lookup| &lookup.m.values);
+                let g = lookups.map(|lookup| &lookup.g.values);
+                let h: Vec<_> = (0..lookups[0].h.len())
+                    .map(|k| lookups.map(|lookup| &lookup.h[k].values))
                     .collect();
-                let r = [&lookup0.r, &lookup1.r];
+                let thetas: Vec<_> = (0..lookups[0].thetas.len())
+                    .map(|j| lookups.map(|lookup| &lookup.thetas[j]))
+                    .collect();
+                let r = lookups.map(|lookup| &lookup.r);
                 LookupData { m, g, h, thetas, r }
             })
             .collect();
 
-        let ys: Vec<_> = zip(&acc0.ys, &acc1.ys).map(|(y0, y1)| [y0, y1]).collect();
+        let shuffles: Vec<_> = (0..accs[0].shuffles.len())
+            .map(|i| {
+                let shuffles: [_; N] = from_fn(|k| &accs[k].shuffles[i]);
+                let g = shuffles.map(|shuffle| &shuffle.g.values);
+                let h = shuffles.map(|shuffle| &shuffle.h.values);
+                let thetas: Vec<_> = (0..shuffles[0].thetas.len())
+                    .map(|j| shuffles.map(|shuffle| &shuffle.thetas[j]))
+                    .collect();
+                let r = shuffles.map(|shuffle| &shuffle.r);
+                ShuffleData { g, h, thetas, r }
+            })
+            .collect();
+
+        let permutation = {
+            let permutations = accs.map(|acc| &acc.permutation);
+            let ids: Vec<_> = pk.permutation_ids.iter().map(|c| &c.values).collect();
+            let sigmas: Vec<_> = pk.permutation_sigmas.iter().map(|c| &c.values).collect();
+            let g: Vec<_> = (0..permutations[0].g.len())
+                .map(|i| permutations.map(|permutation| &permutation.g[i].values))
+                .collect();
+            let h: Vec<_> = (0..permutations[0].h.len())
+                .map(|i| permutations.map(|permutation| &permutation.h[i].values))
+                .collect();
+            let r = permutations.map(|permutation| &permutation.r);
+            let theta = permutations.map(|permutation| &permutation.theta);
+            PermutationData {
+                ids,
+                sigmas,
+                g,
+                h,
+                r,
+                theta,
+            }
+        };
+
+        let ys: Vec<_> = (0..accs[0].ys.len())
+            .map(|i| accs.map(|acc| &acc.ys[i]))
+            .collect();
 
         Data::<Self> {
             fixed,
@@ -79,103 +125,264 @@ impl<'a, F: Field> Paired<'a, F> {
             instance,
             advice,
             challenges,
-            beta,
             lookups,
+            shuffles,
+            permutation,
             ys,
         }
     }
 
-    /// Given an expression G where the variables are the linear polynomials interpolating between
-    /// the challenges and witness columns from two accumulators,
-    /// return the polynomial e(X) = ∑ᵢ βᵢ(X) G(fᵢ, wᵢ(X), rᵢ(X)).
+    /// Given the constraints Gⱼ where the variables are the polynomials interpolating the challenges and
+    /// witness columns from `N` accumulators over {0, 1, ..., N−1},
+    /// return the polynomial e(X) = ∑ⱼ yⱼ(X)⋅eⱼ(X), where eⱼ(X) = ∑ᵢ βᵢ(X) Gⱼ(fᵢ, wᵢ(X), rᵢ(X)),
+    /// and βᵢ(X) = β'ᵢ mod m(X)⋅β''ᵢ / m(X) is given by the compressed powers of beta of all accumulators.
     ///
-    /// The strategy for evaluating e(X) is as follows:
-    /// - Let D = {0,1,...,d} be the evaluation domain containing the first d + 1 integers, where d is the degree of e(X).
-    /// - For each row i, we evaluate the expression eᵢ(X) = βᵢ(X) G(fᵢ, wᵢ(X), rᵢ(X)) over D,
-    ///   and add it to the running sum for e(D) = ∑ᵢ eᵢ(D).
-    ///   - The input variables βᵢ(X), wᵢ(X), rᵢ(X) are linear polynomials of the form pᵢ(X) = (1−X)⋅pᵢ,₀ + X⋅pᵢ,₁,
-    ///     where pᵢ,₀ and pᵢ,₁ are values at the same position but from two different accumulators.
-    ///   - For each variable fᵢ, compute fᵢ(D) by setting
+    /// The constraints are first compiled into graphs evaluating Hₛ = ∑ⱼ yⱼ⋅Gⱼ for groups s of constraints
+    /// sharing the same fixed factors and degree (see `ExpressionGraph::linear_combination`),
+    /// so that e(X) = ∑ₛ eₛ(X) where eₛ(X) = ∑ᵢ βᵢ(X) Hₛ,ᵢ(X).
+    ///
+    /// The strategy for evaluating each eₛ(X) is as follows:
+    /// - Let Dₛ = {0,1,...,dₛ} be the evaluation domain containing the first dₛ + 1 integers, where dₛ is the degree of eₛ(X).
+    /// - For each row i, we evaluate the expression eₛ,ᵢ(X) = β'ᵢ mod m(X) Hₛ(fᵢ, wᵢ(X), rᵢ(X), y(X)) over Dₛ,
+    ///   and add it to the running sum for the block eₛ,ₖ(Dₛ) = ∑ᵢ₍ᵢ / m = ₖ₎ eₛ,ᵢ(Dₛ).
+    ///   - The input variables β'ᵢ(X), wᵢ(X), rᵢ(X), y(X) are polynomials of the form pᵢ(X) = ∑ₖ Lₖ(X)⋅pᵢ,ₖ of degree N−1,
+    ///     where pᵢ,ₖ is the value at the same position in the k-th accumulator,
+    ///     and Lₖ(X) is the Lagrange polynomial over {0, 1, ..., N−1} such that Lₖ(k) = 1.
+    ///   - For each variable fᵢ, compute fᵢ(Dₛ) from the evaluations of the Lagrange basis over Dₛ,
+    ///     which are precomputed once.
+    ///     For a pair of accumulators, pᵢ(X) is linear and we instead set
     ///     - pᵢ(0) = pᵢ,₀
     ///     - pᵢ(1) = pᵢ,₁
-    ///     - pᵢ(j) = pᵢ(j-1) + (pᵢ,₁ − pᵢ,₀) for j = 2, ..., d.
-    ///   - Since challenge variables are the same for each row, we compute the evaluations only once.
-    /// - Given the Expression for e(X), we evaluate it point-wise as eᵢ(j) = βᵢ(j) G(fᵢ, wᵢ(j), rᵢ(j)) for j in D.
+    ///     - pᵢ(l) = pᵢ(l-1) + (pᵢ,₁ − pᵢ,₀) for l = 2, ..., dₛ.
+    ///   - Since challenge variables are the same for each row, we compute their evaluations only once,
+    ///     along with the calculations of the graph which only depend on them.
+    /// - At the end of each block of m rows, the block sum is multiplied by β''ₖ(Dₛ) and added to eₛ(Dₛ).
+    ///
+    /// The following optimizations are performed:
+    /// - Blocks are evaluated in parallel, and the partial sums of each thread are added at the end.
+    /// - If a fixed column (such as a selector) appears as a factor of the group Hₛ,
+    ///   and it vanishes at row i, the evaluation of Hₛ,ᵢ is skipped.
+    /// - Each eₛ(X) is evaluated only over the dₛ + 1 points required by its own degree.
+    /// - If a constraint Gⱼ is linear (i.e. Gⱼ,ᵢ = L₀⋅(wᵢ−1) for checking that w₀ == 1, where L₀ is a fixed column),
+    ///   then eⱼ(X) is always 0 since Gⱼ is satisfied by all accumulators, so we can skip its evaluation.
+    ///   This does not hold if one of the accumulators is `masked`, since the masking accumulator sampled by
+    ///   `create_hiding_accumulator` has random witness columns which violate them.
     ///
-    /// TODO: As an optimization, we can get away with evaluating the polynomial only at the points 2,...,d,
-    /// since e(0) and e(1) are the existing errors from both accumulators. If we let D' = D \ {0,1}, then we can compute
-    /// e(D') and reinsert the evaluations at 0 and 1 for the final result before the conversion to coefficients.
+    /// TODO: As an optimization, we can get away with evaluating the polynomial only at the points N,...,d,
+    /// since e(0), ..., e(N−1) are the existing errors from the accumulators. If we let D' = D \ {0,...,N−1}, then we can compute
+    /// e(D') and reinsert the evaluations at 0, ..., N−1 for the final result before the conversion to coefficients.
     pub fn evaluate_compressed_polynomial(
-        expr: QueriedExpression<Self>,
-        rows: Range<usize>,
+        constraints: Vec<QueriedExpression<Self>>,
+        ys: &[<Self as QueryType>::Challenge],
+        beta: <Self as QueryType>::Witness,
+        beta_sqrt: <Self as QueryType>::Witness,
         num_rows: usize,
+        masked: bool,
     ) -> Vec<F> {
-        // Convert the expression into an indexed one, where all leaves are extracted into vectors,
-        // and replaced by the index in these vectors.
-        // This allows us to separate the evaluation of the variables from the evaluation of the expression,
-        // since the expression leaves will point to the indices in buffers where the evaluations are stored.
-        let indexed = IndexedExpression::<Paired<'a, F>>::new(expr);
-        // Evaluate the polynomial at the points 0,1,...,d, where d is the degree of expr,
-        // since the polynomial e(X) has d+1 coefficients.
-        let num_evals = indexed.expr.degree() + 1;
-
-        // For two transcripts with respective challenge, c₀, c₁,
-        // compute the evaluations of the polynomial c(X) = (1−X)⋅c₀ + X⋅c₁
-        let challenges: Vec<_> = indexed
-            .challenges
-            .iter()
-            .map(|queried_challenge| {
-                EvaluatedError::new_from_boolean_evals(
-                    *queried_challenge.value[0],
-                    *queried_challenge.value[1],
-                    num_evals,
-                )
+        debug_assert_eq!(constraints.len(), ys.len());
+
+        // Linear constraints are satisfied by all unmasked accumulators, so they do not contribute to e(X).
+        let (constraints, ys): (Vec<_>, Vec<_>) = zip(constraints, ys.iter().copied())
+            .filter(|(constraint, _)| masked || constraint.degree() > 1)
+            .unzip();
+        let graphs = ExpressionGraph::linear_combination(constraints, &ys);
+
+        // Every variable has degree N−1, so eₛ(X) = ∑ᵢ βᵢ(X) Hₛ,ᵢ(X) has degree (dₛ + 2)⋅(N−1)
+        let num_evals = |graph: &ExpressionGraph<Self>| (graph.degree() + 2) * (N - 1) + 1;
+
+        let max_num_evals = match graphs.iter().map(num_evals).max() {
+            Some(max_num_evals) => max_num_evals,
+            None => return vec![],
+        };
+
+        // Evaluations of the Lagrange basis Lₖ(l) for l = 0, 1, ..., max_num_evals - 1
+        let basis: Vec<[F; N]> = field_integers()
+            .take(max_num_evals)
+            .map(lagrange_basis)
+            .collect();
+
+        let groups: Vec<_> = graphs
+            .into_iter()
+            .map(|graph| {
+                let num_evals = num_evals(&graph);
+                ConstraintGroup::new(graph, &basis, num_evals)
             })
             .collect();
 
-        // For each variable of the expression, we allocate buffers for storing their evaluations at each row.
-        // - Fixed variables are considered as constants, so we only need to fetch the value from the proving key
-        //   and consider fᵢ(j) = fᵢ for all j
-        // - Witness variables are interpolated from the values at the two accumulators,
-        //   and the evaluations are stored in a buffer.
-        let mut fixed = vec![F::ZERO; indexed.fixed.len()];
-        let mut witness = vec![EvaluatedError::<F>::new(num_evals); indexed.witness.len()];
+        let stride = sqrt_stride(num_rows);
 
-        // Running sum for e(D) = ∑ᵢ eᵢ(D)
-        let mut sum = EvaluatedError::<F>::new(num_evals);
+        // Evaluate eₛ(Dₛ) over all blocks of rows in parallel
+        let sums = (0..num_rows / stride)
+            .into_par_iter()
+            .fold(
+                || BlockEvaluator::new(&groups, max_num_evals),
+                |mut evaluator, block| {
+                    let block_rows = block * stride..(block + 1) * stride;
+                    evaluator.evaluate_block(
+                        &groups, &basis, beta, beta_sqrt, block_rows, num_rows, stride,
+                    );
+                    evaluator
+                },
+            )
+            .map(|evaluator| evaluator.sums)
+            .reduce(
+                || {
+                    groups
+                        .iter()
+                        .map(|group| EvaluatedError::new(group.data.num_evals()))
+                        .collect()
+                },
+                |mut sums0, sums1| {
+                    for (sum0, sum1) in sums0.iter_mut().zip(sums1.iter()) {
+                        *sum0 += sum1;
+                    }
+                    sums0
+                },
+            );
 
-        for row_index in rows {
-            // Fetch fixed data
-            for (fixed, query) in fixed.iter_mut().zip(indexed.fixed.iter()) {
-                let row_idx = query.row_idx(row_index, num_rows);
-                *fixed = query.column[row_idx];
+        // Compute e(X) = ∑ₛ eₛ(X)
+        let mut error_poly = vec![F::ZERO; max_num_evals];
+        for sum in sums.iter() {
+            for (coeff, sum_coeff) in error_poly.iter_mut().zip(sum.to_coefficients()) {
+                *coeff += sum_coeff;
             }
+        }
+        error_poly
+    }
+}
 
-            // Fetch witness data and interpolate
-            for (witness, query) in witness.iter_mut().zip(indexed.witness.iter()) {
-                let row_idx = query.row_idx(row_index, num_rows);
-                let eval0 = query.column[0][row_idx];
-                let eval1 = query.column[1][row_idx];
-                witness.evaluate(eval0, eval1);
-            }
+/// A group of constraints Hₛ = ∑ⱼ yⱼ⋅Gⱼ whose error polynomial eₛ(X) is evaluated independently of the other groups.
+struct ConstraintGroup<'a, F: Field, const N: usize> {
+    graph: ExpressionGraph<Paired<'a, F, N>>,
+    // Buffers containing the evaluations of the challenges and invariant calculations over Dₛ,
+    // whose size is the number of evaluations of eₛ(X) = ∑ᵢ βᵢ(X) Hₛ,ᵢ(X).
+    data: GraphData<F>,
+}
+
+impl<'a, F: Field, const N: usize> ConstraintGroup<'a, F, N> {
+    fn new(graph: ExpressionGraph<Paired<'a, F, N>>, basis: &[[F; N]], num_evals: usize) -> Self {
+        let mut data = graph.data(num_evals);
+
+        // For N transcripts with respective challenge c₀, ..., c_{N−1},
+        // compute the evaluations of the polynomial c(X) = ∑ₖ Lₖ(X)⋅cₖ
+        for (idx, queried_challenge) in graph.challenges.iter().enumerate() {
+            interpolate(
+                queried_challenge.value.map(|challenge| *challenge),
+                basis,
+                data.challenge_mut(idx),
+            );
+        }
+        graph.evaluate_invariants(&mut data);
+
+        Self { graph, data }
+    }
+}
 
-            // Evaluate the expression in the current row and add it to e(D)
-            for (eval_idx, eval) in sum.evals.iter_mut().enumerate() {
-                // For each `eval_idx` j = 0, 1, ..., d, evaluate the expression eᵢ(j) = βᵢ(j) G(fᵢ, wᵢ(j), rᵢ(j))
-                *eval += indexed.expr.evaluate(
-                    &|&constant| constant,
-                    &|&challenge_idx| challenges[challenge_idx].evals[eval_idx],
-                    &|&fixed_idx| fixed[fixed_idx],
-                    &|&witness_idx| witness[witness_idx].evals[eval_idx],
-                    &|&negated| -negated,
-                    &|a, b| a + b,
-                    &|a, b| a * b,
-                );
+/// Buffers used by a single thread for evaluating the error polynomials eₛ(X) over a subset of blocks.
+struct BlockEvaluator<F: Field> {
+    // For each group, we allocate buffers for storing the evaluations of its variables at each row.
+    // - Fixed variables are considered as constants, so we only need to fetch the value from the proving key
+    //   and consider fᵢ(j) = fᵢ for all j
+    // - Witness variables are interpolated from the values at the N accumulators,
+    //   and the evaluations are stored in a buffer.
+    data: Vec<GraphData<F>>,
+    beta: EvaluatedError<F>,
+    beta_sqrt: EvaluatedError<F>,
+    // Running sums for the current block eₛ,ₖ(Dₛ) = ∑ᵢ β'ᵢ mod m(Dₛ)⋅Hₛ,ᵢ(Dₛ)
+    block_sums: Vec<EvaluatedError<F>>,
+    // Running sums for eₛ(Dₛ) = ∑ₖ β''ₖ(Dₛ)⋅eₛ,ₖ(Dₛ)
+    sums: Vec<EvaluatedError<F>>,
+}
+
+impl<F: Field> BlockEvaluator<F> {
+    fn new<const N: usize>(groups: &[ConstraintGroup<'_, F, N>], max_num_evals: usize) -> Self {
+        let data = groups.iter().map(|group| group.data.clone()).collect();
+        let [block_sums, sums] = [(); 2].map(|_| {
+            groups
+                .iter()
+                .map(|group| EvaluatedError::new(group.data.num_evals()))
+                .collect()
+        });
+        Self {
+            data,
+            beta: EvaluatedError::new(max_num_evals),
+            beta_sqrt: EvaluatedError::new(max_num_evals),
+            block_sums,
+            sums,
+        }
+    }
+
+    /// Adds the evaluations β''ₖ(Dₛ)⋅eₛ,ₖ(Dₛ) of the block of `rows` to the running sums.
+    #[allow(clippy::too_many_arguments)]
+    fn evaluate_block<const N: usize>(
+        &mut self,
+        groups: &[ConstraintGroup<'_, F, N>],
+        basis: &[[F; N]],
+        beta: [&ColumnValues<F>; N],
+        beta_sqrt: [&ColumnValues<F>; N],
+        rows: Range<usize>,
+        num_rows: usize,
+        stride: usize,
+    ) {
+        let beta_sqrt_idx = rows.start - rows.start % stride;
+
+        for row_index in rows {
+            let beta_idx = row_index % stride;
+            self.beta.evaluate(beta.map(|beta| beta[beta_idx]), basis);
+
+            for (g_idx, group) in groups.iter().enumerate() {
+                let graph = &group.graph;
+
+                // Skip the row if one of the selectors of the group is off
+                let selected = graph.selectors.iter().all(|&fixed_idx| {
+                    let query = &graph.fixed[fixed_idx];
+                    !bool::from(query.column[query.row_idx(row_index, num_rows)].is_zero())
+                });
+                if !selected {
+                    continue;
+                }
+
+                let data = &mut self.data[g_idx];
+
+                // Fetch fixed data
+                for (idx, query) in graph.fixed.iter().enumerate() {
+                    let row_idx = query.row_idx(row_index, num_rows);
+                    *data.fixed_mut(idx) = query.column[row_idx];
+                }
+
+                // Fetch witness data and interpolate
+                for (idx, query) in graph.witness.iter().enumerate() {
+                    let row_idx = query.row_idx(row_index, num_rows);
+                    interpolate(
+                        query.column.map(|column| column[row_idx]),
+                        basis,
+                        data.witness_mut(idx),
+                    );
+                }
+
+                // Evaluate the graph in the current row over Dₛ, and add eₛ,ᵢ(l) = β'ᵢ(l) Hₛ(fᵢ, wᵢ(l), rᵢ(l), y(l)) to eₛ,ₖ(Dₛ)
+                let output = graph.evaluate(data);
+                let block_sum = &mut self.block_sums[g_idx];
+                for (eval_idx, eval) in block_sum.evals.iter_mut().enumerate() {
+                    *eval += self.beta.evals[eval_idx] * output.get(eval_idx);
+                }
             }
         }
 
-        // Convert the evaluations into the coefficients of the polynomial
-        sum.to_coefficients()
+        // Multiply the block sums by β''ₖ(Dₛ) and add them to eₛ(Dₛ)
+        self.beta_sqrt
+            .evaluate(beta_sqrt.map(|beta_sqrt| beta_sqrt[beta_sqrt_idx]), basis);
+        for (sum, block_sum) in self.sums.iter_mut().zip(self.block_sums.iter_mut()) {
+            for ((eval, block_eval), beta_sqrt_eval) in sum
+                .evals
+                .iter_mut()
+                .zip(block_sum.evals.iter_mut())
+                .zip(self.beta_sqrt.evals.iter())
+            {
+                *eval += *block_eval * beta_sqrt_eval;
+                *block_eval = F::ZERO;
+            }
+        }
     }
 }
 
@@ -201,26 +408,33 @@ impl<F: Field> EvaluatedError<F> {
         }
     }
 
-    /// Returns the evaluations of the linear polynomial (1-X)eval0 + Xeval1.
-    pub fn new_from_boolean_evals(eval0: F, eval1: F, num_evals: usize) -> Self {
-        let mut result = Self::new(num_evals);
-        result.evaluate(eval0, eval1);
-        result
+    /// Overwrites the current evaluations and replaces it with the evaluations of the polynomial ∑ₖ Lₖ(X)⋅evalsₖ,
+    /// where `basis` contains the evaluations of the Lagrange polynomials Lₖ(X) over {0, 1, ..., N−1}.
+    pub fn evaluate<const N: usize>(&mut self, evals: [F; N], basis: &[[F; N]]) {
+        interpolate(evals, basis, &mut self.evals);
     }
 
-    /// Overwrites the current evaluations and replaces it with the evaluations of the linear polynomial (1-X)eval0 + Xeval1.
-    pub fn evaluate(&mut self, eval0: F, eval1: F) {
+    /// Convert the n evalations into the coefficients of the polynomial.
+    pub fn to_coefficients(&self) -> Vec<F> {
+        let points: Vec<_> = field_integers().take(self.evals.len()).collect();
+        lagrange_interpolate(&points, &self.evals)
+    }
+}
+
+/// Writes into `result` the evaluations of the polynomial ∑ₖ Lₖ(X)⋅evalsₖ,
+/// where `basis` contains the evaluations of the Lagrange polynomials Lₖ(X) over {0, 1, ..., N−1}.
+fn interpolate<F: Field, const N: usize>(evals: [F; N], basis: &[[F; N]], result: &mut [F]) {
+    // The linear polynomial (1-X)eval0 + Xeval1 only requires a single addition per evaluation.
+    if let &[eval0, eval1] = evals.as_slice() {
         let mut curr = eval0;
         let diff = eval1 - eval0;
-        for eval in self.evals.iter_mut() {
+        for eval in result.iter_mut() {
             *eval = curr;
             curr += diff;
         }
+        return;
     }
-
-    /// Convert the n evalations into the coefficients of the polynomial.
-    pub fn to_coefficients(&self) -> Vec<F> {
-        let points: Vec<_> = field_integers().take(self.evals.len()).collect();
-        lagrange_interpolate(&points, &self.evals)
+    for (eval, basis) in result.iter_mut().zip(basis.iter()) {
+        *eval = zip(evals.iter(), basis.iter()).fold(F::ZERO, |acc, (e, l)| acc + *e * l);
     }
 }
@@ -9,7 +9,7 @@ use crate::{
     },
 };
 
-use super::{expression::QueryType, Data, LookupData};
+use super::{expression::QueryType, Data, LookupData, PermutationData, ShuffleData};
 
 /// Defines a QueriedExpression where leaves are references values from two accumulators.
 pub struct CommittedRef<'a, C> {
@@ -32,9 +32,12 @@ impl<'a, C: CurveAffine> Data<CommittedRef<'a, C>> {
 
         let advice: Vec<_> = acc.gate.advice.iter().collect();
 
-        let challenges: Vec<_> = acc.gate.challenges.iter().collect();
-
-        let beta = &acc.beta.beta;
+        let challenges: Vec<_> = acc
+            .gate
+            .challenges
+            .iter()
+            .map(|powers| powers.iter().collect())
+            .collect();
 
         let lookups: Vec<_> = acc
             .lookups
@@ -42,13 +45,34 @@ impl<'a, C: CurveAffine> Data<CommittedRef<'a, C>> {
             .map(|lookup| {
                 let m = &lookup.m;
                 let g = &lookup.g;
-                let h = &lookup.h;
+                let h = lookup.h.iter().collect();
                 let thetas: Vec<_> = lookup.thetas.iter().collect();
                 let r = &lookup.r;
                 LookupData { m, g, h, thetas, r }
             })
             .collect();
 
+        let shuffles: Vec<_> = acc
+            .shuffles
+            .iter()
+            .map(|shuffle| {
+                let g = &shuffle.g;
+                let h = &shuffle.h;
+                let thetas: Vec<_> = shuffle.thetas.iter().collect();
+                let r = &shuffle.r;
+                ShuffleData { g, h, thetas, r }
+            })
+            .collect();
+
+        let permutation = PermutationData {
+            ids: pk.permutation_ids.iter().collect(),
+            sigmas: pk.permutation_sigmas.iter().collect(),
+            g: acc.permutation.g.iter().collect(),
+            h: acc.permutation.h.iter().collect(),
+            r: &acc.permutation.r,
+            theta: &acc.permutation.theta,
+        };
+
         let ys: Vec<_> = acc.ys.iter().collect();
 
         Self {
@@ -57,8 +81,9 @@ impl<'a, C: CurveAffine> Data<CommittedRef<'a, C>> {
             instance,
             advice,
             challenges,
-            beta,
             lookups,
+            shuffles,
+            permutation,
             ys,
         }
     }
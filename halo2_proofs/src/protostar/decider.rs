@@ -0,0 +1,765 @@
+//! The decider turns a final `Accumulator` into a succinct proof of its correctness.
+//!
+//! Given an accumulator with error e, the decider proves that
+//! - the compressed constraint satisfies ∑ᵢ βᵢ⋅Gᵢ = e over all rows i,
+//! - the sums of the logUp columns for all lookups, shuffles and the permutation vanish,
+//! - the compressed beta columns β' and β'' are powers of their respective challenges,
+//!   up to their error columns.
+//!
+//! Since βᵢ = β'ᵢ mod m ⋅ β''ᵢ / m is not a rotation of a committed column, the prover commits to the expanded
+//! columns B'ᵢ = β'ᵢ mod m and B''ᵢ = β''ᵢ / m, and proves that
+//! - B' is periodic with period m, and agrees with β' on the first m rows,
+//! - B'' is constant over each block of m rows, and agrees with β'' at the start of each block.
+//!
+//! The sums are batched with a challenge γ into a single one, which is proved using a running-sum column
+//! Sᵢ = ∑ₖ₍ₖ₌₀..ᵢ₋₁₎ tₖ, where tᵢ = B'ᵢ⋅B''ᵢ⋅Gᵢ + ∑ⱼ γʲ⋅Lⱼ,ᵢ − e⋅L₀ᵢ.
+//! The constraint S(ωX) − S(X) − t(X) vanishes over the entire domain (including the wrap-around)
+//! if and only if ∑ᵢ tᵢ = 0.
+//! All constraints are combined with a challenge y, and divided by the vanishing polynomial.
+//! The resulting quotient is committed to, and all columns are opened at a random point x
+//! using the multi-opening argument of the commitment scheme.
+//!
+//! The proof is not zero-knowledge, since the accumulator columns are opened without additional blinding.
+
+use std::iter::{self, zip};
+
+use ff::{Field, FromUniformBytes, PrimeField, WithSmallOrderMulGroup};
+use group::Curve;
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::{
+    arithmetic::{compute_inner_product, eval_polynomial, parallelize, powers},
+    plonk::{ConstraintSystem, Error},
+    poly::{
+        commitment::{Blind, CommitmentScheme, Params, ParamsProver, Prover, Verifier, MSM},
+        EvaluationDomain, ProverQuery, Rotation, VerificationStrategy, VerifierQuery,
+    },
+    transcript::{
+        read_n_points, read_n_scalars, EncodedChallenge, TranscriptRead, TranscriptWrite,
+    },
+};
+
+use super::{
+    accumulator::{committed::Committed, compressed_verifier::sqrt_stride, Accumulator},
+    constraints::{
+        expression::{IndexedExpression, QueriedExpression, QueryType},
+        labeled::{ColumnLabel, Labeled},
+        Data,
+    },
+    verifier::VerifierAccumulator,
+    LookupTable, ProvingKey, VerifyingKey,
+};
+
+/// Constraints over the columns of an accumulator, which are checked by the decider.
+struct Constraints<F: Field> {
+    // Constraint ∑ⱼ yⱼ⋅Gⱼ, whose sum over all rows, weighted by βᵢ, must equal the error.
+    full: IndexedExpression<Labeled<F>>,
+    // Random linear combination ∑ⱼ γʲ⋅Lⱼ of the linear logUp constraints, whose sum over all rows must vanish.
+    linear: Option<IndexedExpression<Labeled<F>>>,
+    // All column queries (column, rotation), in the order in which they are evaluated.
+    queries: Vec<(ColumnLabel, i32)>,
+    // Degree of the combined constraint as a multiple of n.
+    degree: usize,
+}
+
+impl<F: Field> Constraints<F> {
+    fn new<C: CurveAffine<ScalarExt = F>>(
+        cs: &ConstraintSystem<F>,
+        lookup_tables: &[LookupTable<F>],
+        acc: &VerifierAccumulator<C>,
+        num_rows: usize,
+        gamma: F,
+    ) -> Self {
+        let data = Data::<Labeled<F>>::new(cs, acc);
+
+        let full = data.full_constraint(cs.gates(), lookup_tables, cs.shuffles(), cs.permutation());
+
+        let linear = {
+            let constraints = data.linear_constraints();
+            let gammas: Vec<_> = powers(gamma)
+                .take(constraints.len())
+                .map(Labeled::<F>::new_challenge)
+                .collect();
+            (!constraints.is_empty())
+                .then(|| Labeled::<F>::linear_combination(&constraints, &gammas))
+        };
+
+        // The running sum constraint S(ωX) − S(X) − B'(X)⋅B''(X)⋅F(X) − L(X) + e⋅L₀(X)
+        // has degree 2 + deg(F), and the beta constraints have degree 2.
+        let degree = iter::once(column_degree(&full) + 2)
+            .chain(linear.as_ref().map(column_degree))
+            .chain(iter::once(2))
+            .max()
+            .unwrap();
+
+        let full = IndexedExpression::new(full);
+        let linear = linear.map(IndexedExpression::new);
+
+        let stride = sqrt_stride(num_rows) as i32;
+        let mut queries: Vec<(ColumnLabel, i32)> = Vec::new();
+        for (label, rotation) in iter::empty()
+            .chain(Self::expression_queries(&full))
+            .chain(linear.iter().flat_map(Self::expression_queries))
+            .chain([
+                (ColumnLabel::Beta, 0),
+                (ColumnLabel::Beta, 1),
+                (ColumnLabel::BetaError, 0),
+                (ColumnLabel::BetaSqrt, 0),
+                (ColumnLabel::BetaSqrt, stride),
+                (ColumnLabel::BetaSqrtError, 0),
+            ])
+        {
+            if !queries.contains(&(label, rotation)) {
+                queries.push((label, rotation));
+            }
+        }
+
+        Self {
+            full,
+            linear,
+            queries,
+            degree,
+        }
+    }
+
+    fn expression_queries(
+        expr: &IndexedExpression<Labeled<F>>,
+    ) -> impl Iterator<Item = (ColumnLabel, i32)> + '_ {
+        expr.fixed
+            .iter()
+            .chain(expr.witness.iter())
+            .map(|query| (query.column, query.rotation))
+    }
+
+    /// Returns the domain over which the quotient of the combined constraint is computed.
+    fn domain(&self, k: u32) -> EvaluationDomain<F>
+    where
+        F: WithSmallOrderMulGroup<3>,
+    {
+        EvaluationDomain::new(self.degree as u32, k)
+    }
+}
+
+/// Evaluates the expression, where each query (column, rotation) is mapped to a value by `query`.
+fn evaluate_indexed<F: Field>(
+    expr: &IndexedExpression<Labeled<F>>,
+    query: impl Fn(ColumnLabel, i32) -> F,
+) -> F {
+    expr.expr.evaluate(
+        &|&constant| constant,
+        &|&challenge_idx| expr.challenges[challenge_idx].value,
+        &|&fixed_idx| {
+            let fixed = &expr.fixed[fixed_idx];
+            query(fixed.column, fixed.rotation)
+        },
+        &|&witness_idx| {
+            let witness = &expr.witness[witness_idx];
+            query(witness.column, witness.rotation)
+        },
+        &|&negated| -negated,
+        &|a, b| a + b,
+        &|a, b| a * b,
+    )
+}
+
+/// Returns the degree of the expression as a polynomial, where each column has degree 1.
+fn column_degree<F: Field>(expr: &QueriedExpression<Labeled<F>>) -> usize {
+    expr.evaluate(
+        &|_| 0,
+        &|_| 0,
+        &|_| 1,
+        &|_| 1,
+        &|&degree| degree,
+        &std::cmp::max,
+        &|a, b| a + b,
+    )
+}
+
+/// Columns whose evaluations are computed by the verifier, rather than opened by the prover.
+/// Instance columns are opened only if they were committed to, when `query_instance` is set.
+fn is_computed_by_verifier(label: ColumnLabel, query_instance: bool) -> bool {
+    match label {
+        ColumnLabel::Instance(_) => !query_instance,
+        ColumnLabel::PermutationId(_) => true,
+        _ => false,
+    }
+}
+
+/// Returns the committed column of the proving key or the accumulator corresponding to `label`.
+fn committed_column<'a, C: CurveAffine>(
+    pk: &'a ProvingKey<C>,
+    acc: &'a Accumulator<C>,
+    label: ColumnLabel,
+) -> &'a Committed<C> {
+    match label {
+        ColumnLabel::Selector(i) => &pk.selectors[i],
+        ColumnLabel::Fixed(i) => &pk.fixed[i],
+        ColumnLabel::PermutationId(i) => &pk.permutation_ids[i],
+        ColumnLabel::PermutationSigma(i) => &pk.permutation_sigmas[i],
+        ColumnLabel::Instance(i) => &acc.gate.instance[i],
+        ColumnLabel::Advice(i) => &acc.gate.advice[i],
+        ColumnLabel::Beta => &acc.beta.beta.powers,
+        ColumnLabel::BetaError => &acc.beta.beta.error,
+        ColumnLabel::BetaSqrt => &acc.beta.beta_sqrt.powers,
+        ColumnLabel::BetaSqrtError => &acc.beta.beta_sqrt.error,
+        ColumnLabel::LookupM(i) => &acc.lookups[i].m,
+        ColumnLabel::LookupG(i) => &acc.lookups[i].g,
+        ColumnLabel::LookupH(i, k) => &acc.lookups[i].h[k],
+        ColumnLabel::ShuffleG(i) => &acc.shuffles[i].g,
+        ColumnLabel::ShuffleH(i) => &acc.shuffles[i].h,
+        ColumnLabel::PermutationG(i) => &acc.permutation.g[i],
+        ColumnLabel::PermutationH(i) => &acc.permutation.h[i],
+    }
+}
+
+/// Returns the commitment of the verifying key or the verifier accumulator corresponding to `label`.
+/// Panics if the evaluations of the column are computed by the verifier.
+fn column_commitment<'a, C: CurveAffine>(
+    vk: &'a VerifyingKey<C>,
+    acc: &'a VerifierAccumulator<C>,
+    label: ColumnLabel,
+) -> &'a C {
+    match label {
+        ColumnLabel::Selector(i) => &vk.selector_commitments[i],
+        ColumnLabel::Fixed(i) => &vk.fixed_commitments[i],
+        ColumnLabel::PermutationSigma(i) => &vk.permutation_commitments[i],
+        ColumnLabel::Instance(i) => &acc.instance_commitments[i],
+        ColumnLabel::Advice(i) => &acc.advice[i],
+        ColumnLabel::Beta => &acc.beta.commitment,
+        ColumnLabel::BetaError => &acc.beta.error,
+        ColumnLabel::BetaSqrt => &acc.beta_sqrt.commitment,
+        ColumnLabel::BetaSqrtError => &acc.beta_sqrt.error,
+        ColumnLabel::LookupM(i) => &acc.lookup_accumulators[i].m,
+        ColumnLabel::LookupG(i) => &acc.lookup_accumulators[i].g,
+        ColumnLabel::LookupH(i, k) => &acc.lookup_accumulators[i].h[k],
+        ColumnLabel::ShuffleG(i) => &acc.shuffle_accumulators[i].g,
+        ColumnLabel::ShuffleH(i) => &acc.shuffle_accumulators[i].h,
+        ColumnLabel::PermutationG(i) => &acc.permutation_accumulator.g[i],
+        ColumnLabel::PermutationH(i) => &acc.permutation_accumulator.h[i],
+        ColumnLabel::PermutationId(_) => {
+            unreachable!("column is not committed")
+        }
+    }
+}
+
+/// Rotations at which the columns B', B'' and S committed by the decider are queried.
+fn decider_rotations(stride: usize) -> [[i32; 2]; 3] {
+    [[0, stride as i32], [0, -1], [0, 1]]
+}
+
+/// Evaluations at a single point of all polynomials involved in the decider constraints.
+struct Evaluations<F> {
+    // Lagrange polynomial L₀.
+    l_0: F,
+    // Selectors for the rows of β', and for the rows of β' except the last.
+    q_beta: F,
+    q_beta_powers: F,
+    // Selectors for the rows of β'', and for the rows of β'' except the last.
+    q_beta_sqrt: F,
+    q_beta_sqrt_powers: F,
+    // β'(X), β'(ωX), e'(X)
+    beta: [F; 3],
+    // β''(X), β''(ωᵐX), e''(X)
+    beta_sqrt: [F; 3],
+    // B'(X), B'(ωᵐX), B''(X), B''(ω⁻¹X), S(X), S(ωX)
+    decider: [[F; 2]; 3],
+    // Evaluations of the full and linear constraints
+    full: F,
+    linear: F,
+}
+
+impl<F: Field> Evaluations<F> {
+    /// Combines the evaluations of all decider constraints with the challenge y.
+    fn combine(&self, y: F, error: F, beta_challenge: F, beta_sqrt_challenge: F) -> F {
+        let [beta, beta_next, beta_error] = self.beta;
+        let [beta_sqrt, beta_sqrt_next, beta_sqrt_error] = self.beta_sqrt;
+        let [[beta_expanded, beta_expanded_next], [beta_sqrt_expanded, beta_sqrt_expanded_prev], [sum, sum_next]] =
+            self.decider;
+
+        [
+            // S(ωX) − S(X) − B'(X)⋅B''(X)⋅F(X) − L(X) + e⋅L₀(X)
+            sum_next - sum - beta_expanded * beta_sqrt_expanded * self.full - self.linear
+                + error * self.l_0,
+            // B'(ωᵐX) − B'(X)
+            beta_expanded_next - beta_expanded,
+            // q'(X)⋅(B'(X) − β'(X))
+            self.q_beta * (beta_expanded - beta),
+            // (1 − q''(X))⋅(B''(X) − B''(ω⁻¹X))
+            (F::ONE - self.q_beta_sqrt) * (beta_sqrt_expanded - beta_sqrt_expanded_prev),
+            // q''(X)⋅(B''(X) − β''(X))
+            self.q_beta_sqrt * (beta_sqrt_expanded - beta_sqrt),
+            // q'ₚ(X)⋅(e'(X) − β'⋅β'(X) + β'(ωX))
+            self.q_beta_powers * (beta_error - beta_challenge * beta + beta_next),
+            // q''ₚ(X)⋅(e''(X) − β''⋅β''(X) + β''(ωᵐX))
+            self.q_beta_sqrt_powers
+                * (beta_sqrt_error - beta_sqrt_challenge * beta_sqrt + beta_sqrt_next),
+            // L₀(X)⋅(β'(X) − 1)
+            self.l_0 * (beta - F::ONE),
+            // L₀(X)⋅(β''(X) − 1)
+            self.l_0 * (beta_sqrt - F::ONE),
+        ]
+        .into_iter()
+        .fold(F::ZERO, |acc, constraint| acc * y + constraint)
+    }
+}
+
+/// Creates a proof that the accumulator `acc` is satisfied with respect to the proving key `pk`.
+/// Returns `Error::ConstraintSystemFailure` if the accumulator is not satisfied.
+pub fn create_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    P: Prover<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    R: RngCore,
+    T: TranscriptWrite<Scheme::Curve, E>,
+>(
+    params: &'params Scheme::ParamsProver,
+    pk: &ProvingKey<Scheme::Curve>,
+    acc: &Accumulator<Scheme::Curve>,
+    mut rng: R,
+    transcript: &mut T,
+) -> Result<(), Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    let n = pk.num_rows;
+    let stride = sqrt_stride(n);
+    let query_instance = pk.query_instance();
+    acc.check_shape(pk)?;
+
+    // Hash verification key and the public part of the accumulator into transcript
+    pk.get_vk().hash_into(transcript)?;
+    let v_acc = VerifierAccumulator::from_accumulator(pk.get_vk(), acc);
+    v_acc.hash_into(transcript)?;
+
+    // Commit to the expanded beta columns B'ᵢ = β'ᵢ mod m and B''ᵢ = β''ᵢ / m
+    let [beta_expanded, beta_sqrt_expanded] = {
+        let mut beta_expanded = pk.domain.empty_lagrange();
+        let mut beta_sqrt_expanded = pk.domain.empty_lagrange();
+        for row in 0..n {
+            beta_expanded[row] = acc.beta.beta.powers.values[row % stride];
+            beta_sqrt_expanded[row] = acc.beta.beta_sqrt.powers.values[row - row % stride];
+        }
+        [beta_expanded, beta_sqrt_expanded]
+    };
+    let beta_blinds = [(); 2].map(|_| Blind(Scheme::Scalar::random(&mut rng)));
+    for (column, blind) in [&beta_expanded, &beta_sqrt_expanded]
+        .into_iter()
+        .zip(beta_blinds.iter())
+    {
+        transcript.write_point(params.commit_lagrange(column, *blind).to_affine())?;
+    }
+
+    let gamma = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+
+    let constraints = Constraints::new(&pk.cs, pk.lookup_tables(), &v_acc, n, gamma);
+    let domain = constraints.domain(pk.domain.k());
+
+    // Get all distinct columns queried by the constraints
+    let mut labels: Vec<ColumnLabel> = Vec::new();
+    for (label, _) in constraints.queries.iter() {
+        if !labels.contains(label) {
+            labels.push(*label);
+        }
+    }
+    let columns: Vec<_> = labels
+        .iter()
+        .map(|label| committed_column(pk, acc, *label))
+        .collect();
+    let column_idx = |label: ColumnLabel| labels.iter().position(|l| *l == label).unwrap();
+
+    // Evaluate the constraints over each row, and compute the terms of the running sum
+    let row_query = |row: usize| {
+        let columns = &columns;
+        let column_idx = &column_idx;
+        move |label: ColumnLabel, rotation: i32| {
+            let row = (row as i32 + rotation).rem_euclid(n as i32) as usize;
+            columns[column_idx(label)].values[row]
+        }
+    };
+    let mut terms = vec![Scheme::Scalar::ZERO; n];
+    parallelize(&mut terms, |terms, start| {
+        for (i, term) in terms.iter_mut().enumerate() {
+            let row = start + i;
+            *term +=
+                acc.beta.beta_at(row, stride) * evaluate_indexed(&constraints.full, row_query(row));
+            if let Some(linear) = &constraints.linear {
+                *term += evaluate_indexed(linear, row_query(row));
+            }
+        }
+    });
+    terms[0] -= acc.error;
+
+    // Commit to the running sum Sᵢ = ∑ₖ₍ₖ₌₀..ᵢ₋₁₎ tₖ
+    let mut sum = domain.empty_lagrange();
+    let mut running_sum = Scheme::Scalar::ZERO;
+    for (sum, term) in sum.iter_mut().zip(terms.iter()) {
+        *sum = running_sum;
+        running_sum += term;
+    }
+    // The constraint S₀ − Sₙ₋₁ = tₙ₋₁ can only be satisfied if ∑ᵢ tᵢ = 0
+    if running_sum != Scheme::Scalar::ZERO {
+        return Err(Error::ConstraintSystemFailure);
+    }
+    let sum_blind = Blind(Scheme::Scalar::random(&mut rng));
+    transcript.write_point(params.commit_lagrange(&sum, sum_blind).to_affine())?;
+
+    let y = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+
+    // Compute the coefficients and extended evaluations of all columns
+    let coeff_columns: Vec<_> = columns
+        .iter()
+        .map(|column| domain.lagrange_to_coeff(column.values.to_dense()))
+        .collect();
+    let extended_columns: Vec<_> = coeff_columns
+        .iter()
+        .map(|column| domain.coeff_to_extended(column.clone()))
+        .collect();
+    let decider_blinds = [beta_blinds[0], beta_blinds[1], sum_blind];
+    let decider_coeff_columns =
+        [beta_expanded, beta_sqrt_expanded, sum].map(|column| domain.lagrange_to_coeff(column));
+    let decider_extended_columns = decider_coeff_columns
+        .clone()
+        .map(|column| domain.coeff_to_extended(column));
+
+    let [l_0, q_beta, q_beta_powers, q_beta_sqrt, q_beta_sqrt_powers] = {
+        let mut selectors = [(); 5].map(|_| domain.empty_lagrange());
+        let [l_0, q_beta, q_beta_powers, q_beta_sqrt, q_beta_sqrt_powers] = &mut selectors;
+        l_0[0] = Scheme::Scalar::ONE;
+        for row in 0..stride {
+            q_beta[row] = Scheme::Scalar::ONE;
+        }
+        for row in 0..stride - 1 {
+            q_beta_powers[row] = Scheme::Scalar::ONE;
+        }
+        for row in (0..n).step_by(stride) {
+            q_beta_sqrt[row] = Scheme::Scalar::ONE;
+        }
+        for row in (0..n - stride).step_by(stride) {
+            q_beta_sqrt_powers[row] = Scheme::Scalar::ONE;
+        }
+        selectors.map(|column| domain.coeff_to_extended(domain.lagrange_to_coeff(column)))
+    };
+
+    // Evaluate the combined constraint over the extended domain
+    let extended_len = domain.extended_len();
+    let rotation_scale = 1 << (domain.extended_k() - domain.k());
+    let rotate = move |idx: usize, rotation: i32| {
+        (idx as i32 + rotation * rotation_scale).rem_euclid(extended_len as i32) as usize
+    };
+    let decider_rotations = decider_rotations(stride);
+
+    let mut h_poly = domain.empty_extended();
+    parallelize(&mut h_poly, |h, start| {
+        for (i, h) in h.iter_mut().enumerate() {
+            let idx = start + i;
+            let query = |label: ColumnLabel, rotation: i32| {
+                extended_columns[column_idx(label)][rotate(idx, rotation)]
+            };
+
+            let evals = Evaluations {
+                l_0: l_0[idx],
+                q_beta: q_beta[idx],
+                q_beta_powers: q_beta_powers[idx],
+                q_beta_sqrt: q_beta_sqrt[idx],
+                q_beta_sqrt_powers: q_beta_sqrt_powers[idx],
+                beta: [
+                    query(ColumnLabel::Beta, 0),
+                    query(ColumnLabel::Beta, 1),
+                    query(ColumnLabel::BetaError, 0),
+                ],
+                beta_sqrt: [
+                    query(ColumnLabel::BetaSqrt, 0),
+                    query(ColumnLabel::BetaSqrt, stride as i32),
+                    query(ColumnLabel::BetaSqrtError, 0),
+                ],
+                decider: [0, 1, 2].map(|column| {
+                    decider_rotations[column]
+                        .map(|rotation| decider_extended_columns[column][rotate(idx, rotation)])
+                }),
+                full: evaluate_indexed(&constraints.full, query),
+                linear: constraints
+                    .linear
+                    .as_ref()
+                    .map_or(Scheme::Scalar::ZERO, |linear| {
+                        evaluate_indexed(linear, query)
+                    }),
+            };
+
+            *h = evals.combine(
+                y,
+                acc.error,
+                v_acc.beta.challenge,
+                v_acc.beta_sqrt.challenge,
+            );
+        }
+    });
+    drop(extended_columns);
+    drop(decider_extended_columns);
+
+    // Divide by the vanishing polynomial, and commit to the pieces of the quotient h(X)
+    let h_poly = domain.extended_to_coeff(domain.divide_by_vanishing_poly(h_poly));
+    let h_pieces: Vec<_> = h_poly
+        .chunks_exact(n)
+        .map(|piece| domain.coeff_from_vec(piece.to_vec()))
+        .collect();
+    drop(h_poly);
+    let h_blinds: Vec<_> = h_pieces
+        .iter()
+        .map(|_| Blind(Scheme::Scalar::random(&mut rng)))
+        .collect();
+    for (h_piece, h_blind) in h_pieces.iter().zip(h_blinds.iter()) {
+        transcript.write_point(params.commit(h_piece, *h_blind).to_affine())?;
+    }
+
+    let x = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+    let xn = x.pow_vartime([n as u64]);
+
+    // Send the evaluations of all committed columns
+    for (label, rotation) in constraints.queries.iter() {
+        if !is_computed_by_verifier(*label, query_instance) {
+            let point = domain.rotate_omega(x, Rotation(*rotation));
+            transcript.write_scalar(eval_polynomial(&coeff_columns[column_idx(*label)], point))?;
+        }
+    }
+    for (column, rotations) in decider_coeff_columns.iter().zip(decider_rotations) {
+        for rotation in rotations {
+            let point = domain.rotate_omega(x, Rotation(rotation));
+            transcript.write_scalar(eval_polynomial(column, point))?;
+        }
+    }
+
+    // h(X) = ∑ᵢ Xⁿⁱ⋅hᵢ(X)
+    let h_poly = h_pieces
+        .iter()
+        .rev()
+        .fold(domain.empty_coeff(), |acc, piece| acc * xn + piece);
+    let h_blind = h_blinds
+        .iter()
+        .rev()
+        .fold(Blind(Scheme::Scalar::ZERO), |acc, blind| {
+            acc * Blind(xn) + *blind
+        });
+
+    let queries = constraints
+        .queries
+        .iter()
+        .filter(|(label, _)| !is_computed_by_verifier(*label, query_instance))
+        .map(|(label, rotation)| {
+            let idx = column_idx(*label);
+            ProverQuery {
+                point: domain.rotate_omega(x, Rotation(*rotation)),
+                poly: &coeff_columns[idx],
+                blind: columns[idx].blind,
+            }
+        })
+        .chain(
+            decider_coeff_columns
+                .iter()
+                .zip(decider_blinds)
+                .zip(decider_rotations)
+                .flat_map(|((poly, blind), rotations)| {
+                    rotations.map(|rotation| ProverQuery {
+                        point: domain.rotate_omega(x, Rotation(rotation)),
+                        poly,
+                        blind,
+                    })
+                }),
+        )
+        .chain(iter::once(ProverQuery {
+            point: x,
+            poly: &h_poly,
+            blind: h_blind,
+        }))
+        .collect::<Vec<_>>();
+
+    let prover = P::new(params);
+    prover
+        .create_proof(rng, transcript, queries)
+        .map_err(|_| Error::ConstraintSystemFailure)
+}
+
+/// Verifies a proof that the accumulator `acc` is satisfied with respect to the verifying key `vk`.
+/// Returns an error if `acc` does not have the shape expected by `vk`.
+pub fn verify_proof<
+    'params,
+    Scheme: CommitmentScheme,
+    V: Verifier<'params, Scheme>,
+    E: EncodedChallenge<Scheme::Curve>,
+    T: TranscriptRead<Scheme::Curve, E>,
+    Strategy: VerificationStrategy<'params, Scheme, V>,
+>(
+    params: &'params Scheme::ParamsVerifier,
+    vk: &VerifyingKey<Scheme::Curve>,
+    acc: &VerifierAccumulator<Scheme::Curve>,
+    strategy: Strategy,
+    transcript: &mut T,
+) -> Result<Strategy::Output, Error>
+where
+    Scheme::Scalar: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+{
+    let n = vk.num_rows;
+    let k = n.trailing_zeros();
+    let stride = sqrt_stride(n);
+    let query_instance = vk.query_instance;
+    acc.check_shape(vk)?;
+
+    // Hash verification key and the accumulator into transcript
+    vk.hash_into(transcript)?;
+    acc.hash_into(transcript)?;
+
+    let beta_commitments = read_n_points(transcript, 2)?;
+
+    let gamma = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+
+    let constraints = Constraints::new(&vk.cs, &vk.lookup_tables, acc, n, gamma);
+    let domain = constraints.domain(k);
+
+    let sum_commitment = transcript.read_point()?;
+
+    let y = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+
+    let h_commitments = read_n_points(transcript, domain.get_quotient_poly_degree())?;
+
+    let x = *transcript.squeeze_challenge_scalar::<Scheme::Scalar>();
+    let xn = x.pow_vartime([n as u64]);
+
+    // Read the evaluations of all committed columns, and compute the remaining ones
+    let evals = constraints
+        .queries
+        .iter()
+        .map(|&(label, rotation)| match label {
+            ColumnLabel::Instance(i) if !query_instance => {
+                // ∑ⱼ instanceⱼ⋅Lⱼ(ωʳ⋅x) = ∑ⱼ instanceⱼ⋅Lⱼ₋ᵣ(x)
+                let instance = &acc.instance[i];
+                let lagrange_evals =
+                    domain.l_i_range(x, xn, -rotation..(instance.len() as i32 - rotation));
+                Ok(compute_inner_product(instance, &lagrange_evals))
+            }
+            ColumnLabel::PermutationId(i) => {
+                // idᵢ(X) = δⁱ⋅X
+                let point = domain.rotate_omega(x, Rotation(rotation));
+                Ok(Scheme::Scalar::DELTA.pow_vartime([i as u64]) * point)
+            }
+            _ => transcript.read_scalar(),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let decider_rotations = decider_rotations(stride);
+    let decider_evals = read_n_scalars(transcript, 6)?;
+    let decider_evals = [0, 1, 2].map(|column| [0, 1].map(|i| decider_evals[2 * column + i]));
+
+    let query = |label: ColumnLabel, rotation: i32| {
+        let idx = constraints
+            .queries
+            .iter()
+            .position(|query| *query == (label, rotation))
+            .unwrap();
+        evals[idx]
+    };
+
+    // Compute the expected evaluation of h(X) at x, rejecting the proof if x is one of the
+    // roots of unity at which the vanishing polynomials cannot be inverted
+    let expected_h_eval = {
+        let l_0 = domain.l_i_range(x, xn, [0])[0];
+
+        // q'(x) = ∑ᵢ₍ᵢ₌₀..ₘ₋₁₎ Lᵢ(x)
+        let l_beta_evals = domain.l_i_range(x, xn, 0..stride as i32);
+        let q_beta: Scheme::Scalar = l_beta_evals.iter().sum();
+        let q_beta_powers = q_beta - l_beta_evals[stride - 1];
+
+        // q''(x) = ∑ⱼ Lₘ⋅ⱼ(x) = (xⁿ − 1)/(m⋅(x^{n/m} − 1))
+        let q_beta_sqrt = (xn - Scheme::Scalar::ONE)
+            * Option::<Scheme::Scalar>::from(
+                (Scheme::Scalar::from(stride as u64)
+                    * (x.pow_vartime([(n / stride) as u64]) - Scheme::Scalar::ONE))
+                    .invert(),
+            )
+            .ok_or(Error::Opening)?;
+        let q_beta_sqrt_powers = q_beta_sqrt - domain.l_i_range(x, xn, [-(stride as i32)])[0];
+
+        let evals = Evaluations {
+            l_0,
+            q_beta,
+            q_beta_powers,
+            q_beta_sqrt,
+            q_beta_sqrt_powers,
+            beta: [
+                query(ColumnLabel::Beta, 0),
+                query(ColumnLabel::Beta, 1),
+                query(ColumnLabel::BetaError, 0),
+            ],
+            beta_sqrt: [
+                query(ColumnLabel::BetaSqrt, 0),
+                query(ColumnLabel::BetaSqrt, stride as i32),
+                query(ColumnLabel::BetaSqrtError, 0),
+            ],
+            decider: decider_evals,
+            full: evaluate_indexed(&constraints.full, query),
+            linear: constraints
+                .linear
+                .as_ref()
+                .map_or(Scheme::Scalar::ZERO, |linear| {
+                    evaluate_indexed(linear, query)
+                }),
+        };
+
+        evals.combine(y, acc.error, acc.beta.challenge, acc.beta_sqrt.challenge)
+            * Option::<Scheme::Scalar>::from((xn - Scheme::Scalar::ONE).invert())
+                .ok_or(Error::Opening)?
+    };
+
+    // Commitment to h(X) = ∑ᵢ Xⁿⁱ⋅hᵢ(X)
+    let h_commitment =
+        h_commitments
+            .iter()
+            .rev()
+            .fold(params.empty_msm(), |mut acc, commitment| {
+                acc.scale(xn);
+                acc.append_term(Scheme::Scalar::ONE, (*commitment).into());
+                acc
+            });
+
+    let decider_commitments = [beta_commitments[0], beta_commitments[1], sum_commitment];
+
+    let queries = constraints
+        .queries
+        .iter()
+        .zip(evals.iter())
+        .filter(|((label, _), _)| !is_computed_by_verifier(*label, query_instance))
+        .map(|(&(label, rotation), eval)| {
+            VerifierQuery::new_commitment(
+                column_commitment(vk, acc, label),
+                domain.rotate_omega(x, Rotation(rotation)),
+                *eval,
+            )
+        })
+        .chain(
+            decider_commitments
+                .iter()
+                .zip(decider_rotations)
+                .zip(decider_evals)
+                .flat_map(|((commitment, rotations), evals)| {
+                    zip(rotations, evals).map(|(rotation, eval)| {
+                        VerifierQuery::new_commitment(
+                            commitment,
+                            domain.rotate_omega(x, Rotation(rotation)),
+                            eval,
+                        )
+                    })
+                }),
+        )
+        .chain(iter::once(VerifierQuery::new_msm(
+            &h_commitment,
+            x,
+            expected_h_eval,
+        )))
+        .collect::<Vec<_>>();
+
+    let verifier = V::new(params);
+    strategy.process(|msm| {
+        verifier
+            .verify_proof(transcript, queries, msm)
+            .map_err(|_| Error::Opening)
+    })
+}
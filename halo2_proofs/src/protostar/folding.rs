@@ -0,0 +1,666 @@
+//! Non-uniform folding of steps selected by a program counter, following SuperNova.
+//!
+//! A `NonUniformStepCircuit` Fⱼ maps a state zᵢ and program counter pcᵢ = j to the next state zᵢ₊₁,
+//! and the program counter pcᵢ₊₁ = φ(zᵢ, pcᵢ) of the circuit applied at the next step.
+//! Each step is proved by an accumulator whose public inputs are (pcᵢ, zᵢ, pcᵢ₊₁, zᵢ₊₁),
+//! and a running accumulator is kept for each circuit.
+//! The circuit Fⱼ constrains pcᵢ to equal j, so that its proving key can only fold steps selecting it.
+//! Checking the running accumulators with `Accumulator::decide` proves that all steps were computed correctly.
+//!
+//! `NonUniformFoldingVerifier` reads the transcript of a `NonUniformStepFolder`, and folds each step
+//! into the running `VerifierAccumulator` selected by its program counter.
+//! Its accumulators can then be checked succinctly with the decider.
+//!
+//! The folding verifier runs natively, and is not embedded as a circuit over the other curve of the cycle,
+//! so this does not provide incrementally verifiable computation.
+
+use std::{
+    iter::{self, zip},
+    marker::PhantomData,
+};
+
+use ff::{Field, FromUniformBytes, PrimeField};
+use halo2curves::CurveAffine;
+use rand_core::RngCore;
+
+use crate::{
+    circuit::{AssignedCell, Layouter, SimpleFloorPlanner},
+    plonk::{Advice, Circuit, Column, ConstraintSystem, Error, Instance},
+    poly::commitment::Params,
+    transcript::{EncodedChallenge, TranscriptRead, TranscriptWrite},
+};
+
+use super::{
+    accumulator::Accumulator, prover::create_accumulator, verifier::VerifierAccumulator,
+    ProvingKey, VerifyingKey,
+};
+
+/// A function F which is applied iteratively to a state zᵢ of `ARITY` field elements.
+pub trait StepCircuit<F: Field>: Clone {
+    /// Number of field elements in the state zᵢ
+    const ARITY: usize;
+
+    type Config: Clone;
+
+    /// Configures the constraints of F.
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config;
+
+    /// Synthesizes the constraints of F given the assigned input state zᵢ,
+    /// and returns the assigned output state zᵢ₊₁.
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        layouter: impl Layouter<F>,
+        z_in: &[AssignedCell<F, F>],
+    ) -> Result<Vec<AssignedCell<F, F>>, Error>;
+
+    /// Computes zᵢ₊₁ = F(zᵢ) outside of the circuit.
+    fn output(&self, z_in: &[F]) -> Vec<F>;
+}
+
+/// A step circuit Fⱼ of a non-uniform computation, which also selects the circuit applied at the next step.
+pub trait NonUniformStepCircuit<F: Field>: StepCircuit<F> {
+    /// Synthesizes the program counter pcᵢ₊₁ = φ(zᵢ, pcᵢ) given the assigned input state zᵢ
+    /// and input program counter pcᵢ, and returns the assigned program counter.
+    fn synthesize_program_counter(
+        &self,
+        config: Self::Config,
+        layouter: impl Layouter<F>,
+        z_in: &[AssignedCell<F, F>],
+        pc_in: &AssignedCell<F, F>,
+    ) -> Result<AssignedCell<F, F>, Error>;
+
+    /// Computes pcᵢ₊₁ = φ(zᵢ, pcᵢ) outside of the circuit.
+    fn program_counter(&self, z_in: &[F], pc_in: usize) -> usize;
+}
+
+/// Configuration of a `StepCircuit`, along with the columns containing the program counters and states.
+#[derive(Clone, Debug)]
+pub struct StepConfig<Config> {
+    step: Config,
+    state: Column<Advice>,
+    instance: Column<Instance>,
+}
+
+/// Circuit proving a single step (pcᵢ₊₁, zᵢ₊₁) = Fⱼ(zᵢ, pcᵢ) of a non-uniform computation,
+/// where the instance column contains pcᵢ, zᵢ, pcᵢ₊₁ and zᵢ₊₁, in that order.
+#[derive(Clone)]
+struct NonUniformStepInstance<F: Field, SC: NonUniformStepCircuit<F>> {
+    step: SC,
+    // Program counter j of the circuit, which is fixed in its proving key
+    pc: usize,
+    _marker: PhantomData<F>,
+}
+
+impl<F: PrimeField, SC: NonUniformStepCircuit<F>> Circuit<F> for NonUniformStepInstance<F, SC> {
+    type Config = StepConfig<SC::Config>;
+    type FloorPlanner = SimpleFloorPlanner;
+    #[cfg(feature = "circuit-params")]
+    type Params = ();
+
+    fn without_witnesses(&self) -> Self {
+        self.clone()
+    }
+
+    fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+        let state = meta.advice_column();
+        let instance = meta.instance_column();
+        meta.enable_equality(state);
+        meta.enable_equality(instance);
+        // Contains the program counter of the circuit
+        let constant = meta.fixed_column();
+        meta.enable_constant(constant);
+
+        StepConfig {
+            step: SC::configure(meta),
+            state,
+            instance,
+        }
+    }
+
+    fn synthesize(
+        &self,
+        config: Self::Config,
+        mut layouter: impl Layouter<F>,
+    ) -> Result<(), Error> {
+        let (pc_in, z_in) = layouter.assign_region(
+            || "pc_in, z_in",
+            |mut region| {
+                let pc_in = region.assign_advice_from_instance(
+                    || "pc_in",
+                    config.instance,
+                    0,
+                    config.state,
+                    0,
+                )?;
+                // The fixed constant j binds the proving key of Fⱼ to the program counter pcᵢ = j selecting it
+                region.constrain_constant(pc_in.cell(), program_counter::<F>(self.pc))?;
+
+                let z_in = (0..SC::ARITY)
+                    .map(|i| {
+                        region.assign_advice_from_instance(
+                            || "z_in",
+                            config.instance,
+                            1 + i,
+                            config.state,
+                            1 + i,
+                        )
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok((pc_in, z_in))
+            },
+        )?;
+
+        let z_out =
+            self.step
+                .synthesize(config.step.clone(), layouter.namespace(|| "step"), &z_in)?;
+        if z_out.len() != SC::ARITY {
+            return Err(Error::Synthesis);
+        }
+        let pc_out = self.step.synthesize_program_counter(
+            config.step,
+            layouter.namespace(|| "program counter"),
+            &z_in,
+            &pc_in,
+        )?;
+
+        layouter.constrain_instance(pc_out.cell(), config.instance, SC::ARITY + 1)?;
+        for (i, z) in z_out.iter().enumerate() {
+            layouter.constrain_instance(z.cell(), config.instance, SC::ARITY + 2 + i)?;
+        }
+        Ok(())
+    }
+}
+
+/// Non-uniform driver which folds each step (pcᵢ₊₁, zᵢ₊₁) = Fⱼ(zᵢ, pcᵢ) with j = pcᵢ
+/// into the running accumulator of the circuit Fⱼ.
+pub struct NonUniformStepFolder<C: CurveAffine>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    pks: Vec<ProvingKey<C>>,
+    accs: Vec<Option<Accumulator<C>>>,
+    z_0: Vec<C::Scalar>,
+    z_i: Vec<C::Scalar>,
+    pc_0: usize,
+    pc_i: usize,
+    num_steps: usize,
+}
+
+impl<C: CurveAffine> std::fmt::Debug for NonUniformStepFolder<C>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NonUniformStepFolder")
+            .field("num_circuits", &self.pks.len())
+            .field("z_0", &self.z_0)
+            .field("z_i", &self.z_i)
+            .field("pc_0", &self.pc_0)
+            .field("pc_i", &self.pc_i)
+            .field("num_steps", &self.num_steps)
+            .finish()
+    }
+}
+
+impl<C: CurveAffine> NonUniformStepFolder<C>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    /// Creates a driver without any circuit, starting from the initial state `z_0`
+    /// and the initial program counter `pc_0`.
+    pub fn new(z_0: Vec<C::Scalar>, pc_0: usize) -> Self {
+        Self {
+            pks: vec![],
+            accs: vec![],
+            z_i: z_0.clone(),
+            z_0,
+            pc_0,
+            pc_i: pc_0,
+            num_steps: 0,
+        }
+    }
+
+    /// Creates the proving key for the step circuit `step`, and returns its program counter.
+    /// Circuits are identified by the order in which they are added, so a step circuit type can be added
+    /// several times with different parameters. It must have the same arity as the initial state.
+    pub fn add_circuit<'params, P: Params<'params, C>, SC>(
+        &mut self,
+        params: &P,
+        step: &SC,
+    ) -> Result<usize, Error>
+    where
+        SC: NonUniformStepCircuit<C::Scalar>,
+    {
+        if SC::ARITY != self.z_0.len() {
+            return Err(Error::InvalidInstances);
+        }
+        let circuit = NonUniformStepInstance {
+            step: step.clone(),
+            pc: self.pks.len(),
+            _marker: PhantomData,
+        };
+        self.pks.push(ProvingKey::new(params, &circuit)?);
+        self.accs.push(None);
+        Ok(self.pks.len() - 1)
+    }
+
+    /// Proves the next step (pcᵢ₊₁, zᵢ₊₁) = Fⱼ(zᵢ, pcᵢ), and folds it into the running accumulator of Fⱼ.
+    /// The step is proved with the key of the circuit Fⱼ selected by j = pcᵢ, so the accumulator fails to decide
+    /// if `step` is not the circuit which was added with program counter j.
+    pub fn prove_step<
+        'params,
+        P: Params<'params, C>,
+        SC,
+        E: EncodedChallenge<C>,
+        R: RngCore,
+        T: TranscriptWrite<C, E>,
+    >(
+        &mut self,
+        params: &P,
+        step: SC,
+        mut rng: R,
+        transcript: &mut T,
+    ) -> Result<(), Error>
+    where
+        SC: NonUniformStepCircuit<C::Scalar>,
+    {
+        if self.pc_i >= self.pks.len() || SC::ARITY != self.z_i.len() {
+            return Err(Error::InvalidInstances);
+        }
+
+        let z_next = step.output(&self.z_i);
+        if z_next.len() != SC::ARITY {
+            return Err(Error::InvalidInstances);
+        }
+        let pc_next = step.program_counter(&self.z_i, self.pc_i);
+
+        let instance: Vec<_> = iter::once(program_counter(self.pc_i))
+            .chain(self.z_i.iter().copied())
+            .chain(iter::once(program_counter(pc_next)))
+            .chain(z_next.iter().copied())
+            .collect();
+        let circuit = NonUniformStepInstance {
+            step,
+            pc: self.pc_i,
+            _marker: PhantomData,
+        };
+
+        let acc = create_accumulator(
+            params,
+            &self.pks[self.pc_i],
+            &circuit,
+            &[&instance],
+            &mut rng,
+            transcript,
+        )?;
+        self.fold(acc, transcript)?;
+
+        self.z_i = z_next;
+        self.pc_i = pc_next;
+        self.num_steps += 1;
+        Ok(())
+    }
+
+    /// Folds a fresh accumulator into the running accumulator selected by the program counter
+    /// in its first public input.
+    fn fold<E: EncodedChallenge<C>, T: TranscriptWrite<C, E>>(
+        &mut self,
+        acc: Accumulator<C>,
+        transcript: &mut T,
+    ) -> Result<(), Error> {
+        let pc = selected_circuit(
+            self.pks.len(),
+            acc.gate
+                .instance
+                .first()
+                .and_then(|instance| instance.values.get(0)),
+        )?;
+        let pk = &self.pks[pc];
+
+        self.accs[pc] = Some(match self.accs[pc].take() {
+            None => acc,
+            Some(running) => Accumulator::fold(pk, running, acc, transcript)?,
+        });
+        Ok(())
+    }
+
+    /// Checks all running accumulators, proving that all steps were computed correctly.
+    /// This requires the entire witness of the accumulators. A verifier which only has the transcript
+    /// should use a `NonUniformFoldingVerifier` instead.
+    pub fn verify<'params, P: Params<'params, C>>(&self, params: &P) -> bool {
+        if self.num_steps == 0 {
+            return self.z_i == self.z_0 && self.pc_i == self.pc_0;
+        }
+        zip(&self.pks, &self.accs).all(|(pk, acc)| match acc {
+            None => true,
+            Some(acc) => Accumulator::decide(params, pk, acc),
+        })
+    }
+
+    /// Returns the proving key of the step circuit with program counter `pc`.
+    pub fn pk(&self, pc: usize) -> Option<&ProvingKey<C>> {
+        self.pks.get(pc)
+    }
+
+    /// Returns the running accumulator of the step circuit with program counter `pc`,
+    /// if at least one of its steps was proved.
+    pub fn accumulator(&self, pc: usize) -> Option<&Accumulator<C>> {
+        self.accs.get(pc).and_then(Option::as_ref)
+    }
+
+    /// Returns the initial state z₀.
+    pub fn z_0(&self) -> &[C::Scalar] {
+        &self.z_0
+    }
+
+    /// Returns the current state zᵢ.
+    pub fn z_i(&self) -> &[C::Scalar] {
+        &self.z_i
+    }
+
+    /// Returns the program counter pcᵢ of the circuit applied at the next step.
+    pub fn program_counter(&self) -> usize {
+        self.pc_i
+    }
+
+    /// Returns the number of steps i which were folded into the running accumulators.
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+}
+
+/// Verifier of a `NonUniformStepFolder`, which reads each step from the transcript and folds it
+/// into the running verifier accumulator of the circuit selected by its program counter.
+#[derive(Debug)]
+pub struct NonUniformFoldingVerifier<C: CurveAffine> {
+    vks: Vec<VerifyingKey<C>>,
+    accs: Vec<Option<VerifierAccumulator<C>>>,
+    z_i: Vec<C::Scalar>,
+    pc_i: usize,
+    num_steps: usize,
+}
+
+impl<C: CurveAffine> NonUniformFoldingVerifier<C> {
+    /// Creates a verifier for the step circuits with verifying keys `vks`, in the order in which they were added
+    /// to the `NonUniformStepFolder`, starting from the initial state `z_0` and the initial program counter `pc_0`.
+    pub fn new(vks: Vec<VerifyingKey<C>>, z_0: Vec<C::Scalar>, pc_0: usize) -> Self {
+        Self {
+            accs: vec![None; vks.len()],
+            vks,
+            z_i: z_0,
+            pc_i: pc_0,
+            num_steps: 0,
+        }
+    }
+
+    /// Reads the proof of the step (pcᵢ₊₁, zᵢ₊₁) = Fⱼ(zᵢ, pcᵢ) claimed by the prover,
+    /// and folds it into the running accumulator of the circuit Fⱼ selected by j = pcᵢ.
+    /// The verifying key of Fⱼ is hashed into the transcript before the step is read,
+    /// binding the step to the key selected by its program counter.
+    pub fn fold<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
+        &mut self,
+        pc_next: usize,
+        z_next: Vec<C::Scalar>,
+        transcript: &mut T,
+    ) -> Result<(), Error> {
+        if z_next.len() != self.z_i.len() {
+            return Err(Error::InvalidInstances);
+        }
+        let instance: Vec<_> = iter::once(program_counter(self.pc_i))
+            .chain(self.z_i.iter().copied())
+            .chain(iter::once(program_counter(pc_next)))
+            .chain(z_next.iter().copied())
+            .collect();
+
+        let pc = selected_circuit(self.vks.len(), instance.first())?;
+        let vk = &self.vks[pc];
+        let acc = VerifierAccumulator::new(vk, &[&instance], transcript)?;
+        self.accs[pc] = Some(match self.accs[pc].take() {
+            None => acc,
+            Some(running) => VerifierAccumulator::fold(vk, running, acc, transcript)?,
+        });
+
+        self.z_i = z_next;
+        self.pc_i = pc_next;
+        self.num_steps += 1;
+        Ok(())
+    }
+
+    /// Returns the verifying key of the step circuit with program counter `pc`.
+    pub fn vk(&self, pc: usize) -> Option<&VerifyingKey<C>> {
+        self.vks.get(pc)
+    }
+
+    /// Returns the running accumulator of the step circuit with program counter `pc`,
+    /// if at least one of its steps was folded.
+    pub fn accumulator(&self, pc: usize) -> Option<&VerifierAccumulator<C>> {
+        self.accs.get(pc).and_then(Option::as_ref)
+    }
+
+    /// Returns the current state zᵢ.
+    pub fn z_i(&self) -> &[C::Scalar] {
+        &self.z_i
+    }
+
+    /// Returns the program counter pcᵢ of the circuit applied at the next step.
+    pub fn program_counter(&self) -> usize {
+        self.pc_i
+    }
+
+    /// Returns the number of steps i which were folded into the running accumulators.
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+}
+
+/// Encodes a program counter as a public input.
+fn program_counter<F: PrimeField>(pc: usize) -> F {
+    F::from(pc as u64)
+}
+
+/// Returns the index j of the circuit selected by the program counter pc = j, given as the first public input of a step.
+fn selected_circuit<F: PrimeField>(num_circuits: usize, pc: Option<&F>) -> Result<usize, Error> {
+    pc.and_then(|pc| (0..num_circuits).find(|j| program_counter::<F>(*j) == *pc))
+        .ok_or(Error::InvalidInstances)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::marker::PhantomData;
+
+    use ff::Field;
+    use halo2curves::pasta::pallas;
+    use rand_core::OsRng;
+
+    use crate::{
+        circuit::{AssignedCell, Layouter, Value},
+        plonk::{Advice, Column, ConstraintSystem, Error, Expression, Fixed, Selector},
+        poly::{self, commitment::ParamsProver, Rotation},
+        transcript::{
+            Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
+        },
+    };
+
+    use super::{
+        create_accumulator, Accumulator, NonUniformFoldingVerifier, NonUniformStepCircuit,
+        NonUniformStepFolder, NonUniformStepInstance, StepCircuit,
+    };
+
+    /// Maps z ↦ a⋅z + b, and selects the other circuit next with pcᵢ₊₁ = 1 - pcᵢ.
+    #[derive(Clone)]
+    struct AffineStep {
+        a: u64,
+        b: u64,
+    }
+
+    const DOUBLE: AffineStep = AffineStep { a: 2, b: 0 };
+    const INCREMENT: AffineStep = AffineStep { a: 1, b: 1 };
+
+    impl StepCircuit<pallas::Scalar> for AffineStep {
+        const ARITY: usize = 1;
+
+        type Config = (Selector, Selector, Column<Advice>, Column<Fixed>);
+
+        fn configure(meta: &mut ConstraintSystem<pallas::Scalar>) -> Self::Config {
+            let q = meta.selector();
+            let q_pc = meta.selector();
+            let column = meta.advice_column();
+            let coefficients = meta.fixed_column();
+            meta.enable_equality(column);
+
+            meta.create_gate("affine", |meta| {
+                let q = meta.query_selector(q);
+                let [z_in, z_out] = [0, 1].map(|i| meta.query_advice(column, Rotation(i)));
+                let [a, b] = [0, 1].map(|i| meta.query_fixed(coefficients, Rotation(i)));
+                vec![q * (a * z_in + b - z_out)]
+            });
+            meta.create_gate("program counter", |meta| {
+                let q_pc = meta.query_selector(q_pc);
+                let [pc_in, pc_out] = [0, 1].map(|i| meta.query_advice(column, Rotation(i)));
+                vec![q_pc * (pc_in + pc_out - Expression::Constant(pallas::Scalar::ONE))]
+            });
+            (q, q_pc, column, coefficients)
+        }
+
+        fn synthesize(
+            &self,
+            (q, _, column, coefficients): Self::Config,
+            mut layouter: impl Layouter<pallas::Scalar>,
+            z_in: &[AssignedCell<pallas::Scalar, pallas::Scalar>],
+        ) -> Result<Vec<AssignedCell<pallas::Scalar, pallas::Scalar>>, Error> {
+            layouter.assign_region(
+                || "affine",
+                |mut region| {
+                    q.enable(&mut region, 0)?;
+                    for (offset, coefficient) in [self.a, self.b].into_iter().enumerate() {
+                        region.assign_fixed(
+                            || "coefficient",
+                            coefficients,
+                            offset,
+                            || Value::known(pallas::Scalar::from(coefficient)),
+                        )?;
+                    }
+                    let z_in = z_in[0].copy_advice(|| "z_in", &mut region, column, 0)?;
+                    let z_out = z_in.value().map(|z| self.output(&[*z])[0]);
+                    let z_out = region.assign_advice(|| "z_out", column, 1, || z_out)?;
+                    Ok(vec![z_out])
+                },
+            )
+        }
+
+        fn output(&self, z_in: &[pallas::Scalar]) -> Vec<pallas::Scalar> {
+            vec![pallas::Scalar::from(self.a) * z_in[0] + pallas::Scalar::from(self.b)]
+        }
+    }
+
+    impl NonUniformStepCircuit<pallas::Scalar> for AffineStep {
+        fn synthesize_program_counter(
+            &self,
+            (_, q_pc, column, _): Self::Config,
+            mut layouter: impl Layouter<pallas::Scalar>,
+            _z_in: &[AssignedCell<pallas::Scalar, pallas::Scalar>],
+            pc_in: &AssignedCell<pallas::Scalar, pallas::Scalar>,
+        ) -> Result<AssignedCell<pallas::Scalar, pallas::Scalar>, Error> {
+            layouter.assign_region(
+                || "program counter",
+                |mut region| {
+                    q_pc.enable(&mut region, 0)?;
+                    let pc_in = pc_in.copy_advice(|| "pc_in", &mut region, column, 0)?;
+                    let pc_out = pc_in.value().map(|pc| pallas::Scalar::ONE - pc);
+                    region.assign_advice(|| "pc_out", column, 1, || pc_out)
+                },
+            )
+        }
+
+        fn program_counter(&self, _z_in: &[pallas::Scalar], pc_in: usize) -> usize {
+            1 - pc_in
+        }
+    }
+
+    #[test]
+    fn test_non_uniform_step_folder() {
+        const K: u32 = 5;
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        // Both circuits have the same type, and are distinguished by their program counter
+        let mut folder = NonUniformStepFolder::new(vec![pallas::Scalar::ONE], 0);
+        assert_eq!(folder.add_circuit(&params, &DOUBLE).unwrap(), 0);
+        assert_eq!(folder.add_circuit(&params, &INCREMENT).unwrap(), 1);
+        assert!(folder.verify(&params));
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        for _ in 0..2 {
+            folder
+                .prove_step(&params, DOUBLE, OsRng, &mut transcript)
+                .unwrap();
+            assert_eq!(folder.program_counter(), 1);
+            folder
+                .prove_step(&params, INCREMENT, OsRng, &mut transcript)
+                .unwrap();
+            assert_eq!(folder.program_counter(), 0);
+            assert!(folder.verify(&params));
+        }
+        let proof = transcript.finalize();
+
+        // 1 ↦ 2 ↦ 3 ↦ 6 ↦ 7
+        assert_eq!(folder.num_steps(), 4);
+        assert_eq!(folder.z_i(), &[pallas::Scalar::from(7)]);
+
+        // The verifier folds the same steps from the transcript alone
+        let vks = (0..2)
+            .map(|pc| folder.pk(pc).unwrap().get_vk().clone())
+            .collect();
+        let mut verifier = NonUniformFoldingVerifier::new(vks, vec![pallas::Scalar::ONE], 0);
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof.as_slice());
+        for (pc_next, z_next) in [(1, 2), (0, 3), (1, 6), (0, 7)] {
+            verifier
+                .fold(pc_next, vec![pallas::Scalar::from(z_next)], &mut transcript)
+                .unwrap();
+        }
+        assert_eq!(verifier.num_steps(), 4);
+        for pc in 0..2 {
+            assert!(*verifier.accumulator(pc).unwrap() == *folder.accumulator(pc).unwrap());
+        }
+
+        // A step proved with a circuit other than the one selected by the program counter
+        // does not satisfy the constraints of the selected key, so its accumulator fails to decide
+        assert_eq!(folder.program_counter(), 0);
+        folder
+            .prove_step(&params, INCREMENT, OsRng, &mut Blake2bWrite::init(vec![]))
+            .unwrap();
+        assert!(!folder.verify(&params));
+    }
+
+    #[test]
+    fn test_non_uniform_program_counter() {
+        const K: u32 = 5;
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let mut folder = NonUniformStepFolder::new(vec![pallas::Scalar::ONE], 0);
+        folder.add_circuit(&params, &DOUBLE).unwrap();
+        folder.add_circuit(&params, &INCREMENT).unwrap();
+        let pk = folder.pk(0).unwrap();
+
+        // Proves a step of the doubling circuit with instance (pcᵢ, zᵢ, pcᵢ₊₁, zᵢ₊₁)
+        let decide = |instance: [u64; 4]| {
+            let instance = instance.map(pallas::Scalar::from);
+            let circuit = NonUniformStepInstance {
+                step: DOUBLE,
+                pc: 0,
+                _marker: PhantomData,
+            };
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            let acc =
+                create_accumulator(&params, pk, &circuit, &[&instance], OsRng, &mut transcript)
+                    .unwrap();
+            Accumulator::decide(&params, pk, &acc)
+        };
+
+        assert!(decide([0, 1, 1, 2]));
+        // The key of the doubling circuit cannot be used for a step selecting the other circuit
+        assert!(!decide([1, 1, 0, 2]));
+        // The next program counter must equal φ(zᵢ, pcᵢ)
+        assert!(!decide([0, 1, 0, 2]));
+    }
+}
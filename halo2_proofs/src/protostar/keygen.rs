@@ -1,35 +1,115 @@
 use core::num;
-use std::{collections::BTreeSet, ops::Range};
+use std::{collections::BTreeSet, io, ops::Range};
 
-use ff::Field;
+use blake2b_simd::Params as Blake2bParams;
+use ff::{Field, FromUniformBytes, PrimeField, WithSmallOrderMulGroup};
 use group::Curve;
 use halo2curves::CurveAffine;
+use rayon::prelude::ParallelIterator;
 
 use crate::{
     arithmetic::{log2_ceil, parallelize},
     circuit::{layouter::SyncDeps, Value},
+    helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField},
     plonk::{
-        circuit::FloorPlanner, lookup, permutation, Advice, AdviceQuery, Any, Assigned, Assignment,
-        Challenge, Circuit, Column, ConstraintSystem, Error, Expression, Fixed, FixedQuery,
-        Instance, InstanceQuery, Selector,
+        circuit::FloorPlanner, create_domain, lookup, permutation, Advice, AdviceQuery, Any,
+        Assigned, Assignment, Challenge, Circuit, Column, ConstraintSystem, Error, Expression,
+        Fixed, FixedQuery, Instance, InstanceQuery, PinnedConstraintSystem, Selector,
     },
     poly::{
         batch_invert_assigned,
         commitment::{Blind, Params},
-        empty_lagrange, empty_lagrange_assigned, EvaluationDomain, LagrangeCoeff, Polynomial,
+        empty_lagrange, empty_lagrange_assigned, EvaluationDomain, LagrangeCoeff,
+        PinnedEvaluationDomain, Polynomial,
     },
+    transcript::{EncodedChallenge, Transcript},
+    SerdeFormat,
 };
 
-use super::accumulator::committed::{batch_commit_transparent, Committed};
+use super::{
+    accumulator::{
+        column::ColumnValues,
+        committed::{batch_commit_transparent, Committed},
+    },
+    constraints::expression,
+};
+
+/// Determines how the challenges r and θ compressing the rows of each lookup table are sampled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LookupChallenges {
+    /// A single pair (r, θ) is squeezed once all multiplicities have been committed to,
+    /// and is shared by all lookup tables.
+    #[default]
+    Shared,
+    /// A pair (rⱼ, θⱼ) is squeezed for each lookup table j, once all multiplicities have been
+    /// committed to, so that the compression of each table is independent of the other ones.
+    PerTable,
+}
+
+/// Lookup arguments of the constraint system whose table expressions are identical.
+///
+/// They are proven together with a single multiplicity column m and a single column g for the table,
+/// and a column hₖ for the input of each argument k, such that ∑ᵢ gᵢ = ∑ₖ,ᵢ hₖ,ᵢ.
+/// Using one h per input rather than their sum keeps the degree of the input constraints
+/// hₖ⋅(r + ∑ⱼ θⱼ⋅inputₖ,ⱼ) − 1 equal to the one of a single argument.
+#[derive(Clone, Debug)]
+pub struct LookupTable<F: Field> {
+    // Non-empty list of arguments, in the order in which they appear in the constraint system
+    arguments: Vec<lookup::Argument<F>>,
+}
+
+impl<F: Field> LookupTable<F> {
+    /// Groups the lookup arguments by table, ordered by the first argument into each table.
+    fn group(lookups: &[lookup::Argument<F>]) -> Vec<Self> {
+        let mut tables: Vec<Self> = vec![];
+        let mut identifiers: Vec<Vec<String>> = vec![];
+        for lookup in lookups {
+            let identifier: Vec<_> = lookup
+                .table_expressions
+                .iter()
+                .map(Expression::identifier)
+                .collect();
+            match identifiers.iter().position(|other| *other == identifier) {
+                Some(index) => tables[index].arguments.push(lookup.clone()),
+                None => {
+                    identifiers.push(identifier);
+                    tables.push(Self {
+                        arguments: vec![lookup.clone()],
+                    });
+                }
+            }
+        }
+        tables
+    }
+
+    /// Lookup arguments into the table.
+    pub fn arguments(&self) -> &[lookup::Argument<F>] {
+        &self.arguments
+    }
+
+    /// Expressions of the table, which are shared by all arguments.
+    pub fn table_expressions(&self) -> &[Expression<F>] {
+        &self.arguments[0].table_expressions
+    }
+
+    /// Name of the table, given by the names of its arguments.
+    pub fn name(&self) -> String {
+        self.arguments
+            .iter()
+            .map(|arg| arg.name())
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
 
 /// Contains all fixed data for a circuit that is required to create a Protostar `Accumulator`
 #[derive(Debug, Clone)]
 pub struct ProvingKey<C: CurveAffine> {
     pub domain: EvaluationDomain<C::Scalar>,
-    // maximum number of rows in the trace (including blinding factors)
+    // number of rows in the trace, all of which can be assigned by the circuit.
+    // Folding hides the witness with blinded commitments and a masking accumulator,
+    // so no rows are reserved for blinding factors.
     pub num_rows: usize,
-    // max active rows, without blinding
-    pub usable_rows: Range<usize>,
 
     // The circuit's unmodified constraint system
     pub cs: ConstraintSystem<C::Scalar>,
@@ -37,9 +117,24 @@ pub struct ProvingKey<C: CurveAffine> {
     // Fixed columns
     pub fixed: Vec<Committed<C>>,
     pub selectors: Vec<Committed<C>>,
+
+    // Identity and permuted column labels for each column in the permutation argument
+    pub permutation_ids: Vec<Committed<C>>,
+    pub permutation_sigmas: Vec<Committed<C>>,
+
+    // Whether the prover fails when the input of a lookup is not contained in its table.
+    // It only affects the prover, so it is not serialized and is set by default.
+    strict_lookups: bool,
+
+    // Data required by the verifier
+    vk: VerifyingKey<C>,
 }
 
-impl<C: CurveAffine> ProvingKey<C> {
+impl<F, C> ProvingKey<C>
+where
+    F: WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    C: CurveAffine<ScalarExt = F>,
+{
     /// Generate algebraic representation of the circuit.
     pub fn new<'params, P, ConcreteCircuit>(
         params: &P,
@@ -47,7 +142,37 @@ impl<C: CurveAffine> ProvingKey<C> {
     ) -> Result<ProvingKey<C>, Error>
     where
         P: Params<'params, C>,
-        ConcreteCircuit: Circuit<C::Scalar>,
+        ConcreteCircuit: Circuit<F>,
+    {
+        Self::new_with_query_instance(params, circuit, false)
+    }
+
+    /// Generate algebraic representation of the circuit.
+    /// If `query_instance` is set, instance columns are committed to rather than added to the transcript,
+    /// so that the `VerifierAccumulator` only folds their commitments.
+    pub fn new_with_query_instance<'params, P, ConcreteCircuit>(
+        params: &P,
+        circuit: &ConcreteCircuit,
+        query_instance: bool,
+    ) -> Result<ProvingKey<C>, Error>
+    where
+        P: Params<'params, C>,
+        ConcreteCircuit: Circuit<F>,
+    {
+        Self::new_with_options(params, circuit, query_instance, LookupChallenges::Shared)
+    }
+
+    /// Generate algebraic representation of the circuit, as in [`Self::new_with_query_instance`],
+    /// where the challenges of the lookup arguments are sampled according to `lookup_challenges`.
+    pub fn new_with_options<'params, P, ConcreteCircuit>(
+        params: &P,
+        circuit: &ConcreteCircuit,
+        query_instance: bool,
+        lookup_challenges: LookupChallenges,
+    ) -> Result<ProvingKey<C>, Error>
+    where
+        P: Params<'params, C>,
+        ConcreteCircuit: Circuit<F>,
     {
         let num_rows = params.n() as usize;
         // k = log2(num_rows)
@@ -65,13 +190,8 @@ impl<C: CurveAffine> ProvingKey<C> {
 
         let domain = EvaluationDomain::new(degree as u32, k);
 
-        // TODO(@adr1anh): Blinding will be different for Protostar
-        if num_rows < cs.minimum_rows() {
-            return Err(Error::not_enough_rows_available(k));
-        }
-
         let mut assembly: Assembly<C::Scalar> = Assembly {
-            usable_rows: 0..num_rows - (cs.blinding_factors() + 1),
+            usable_rows: 0..num_rows,
             k,
 
             fixed: vec![empty_lagrange_assigned(num_rows); cs.num_fixed_columns],
@@ -85,7 +205,7 @@ impl<C: CurveAffine> ProvingKey<C> {
         ConcreteCircuit::FloorPlanner::synthesize(
             &mut assembly,
             circuit,
-            config,
+            config.clone(),
             cs.constants.clone(),
         )?;
 
@@ -93,31 +213,41 @@ impl<C: CurveAffine> ProvingKey<C> {
 
         let fixed: Vec<_> = fixed
             .into_iter()
-            .map(|values| {
-                let blind = Blind::default();
-                let commitment = params.commit_lagrange(&values, blind).to_affine();
-                Committed {
-                    values,
-                    commitment,
-                    blind,
-                }
-            })
+            .map(|values| commit_fixed_column(params, values))
             .collect();
 
-        let selectors = assembly
+        let selectors: Vec<_> = assembly
             .selectors
             .into_iter()
-            .map(|values| {
-                let blind = Blind::default();
-                let commitment = params.commit_lagrange(&values, blind).to_affine();
-                Committed {
-                    values,
-                    commitment,
-                    blind,
-                }
+            .map(|values| commit_fixed_column(params, values))
+            .collect();
+
+        // Label each cell (j, i) of the permutation columns by δʲ⋅ωⁱ,
+        // and compute the labels of the cells they are mapped to by the permutation.
+        #[cfg(feature = "thread-safe-region")]
+        assembly.permutation.build_ordered_mapping();
+
+        let permutation_id_values = build_permutation_ids(&domain, cs.permutation.columns.len());
+        let permutation_sigma_values: Vec<_> = assembly
+            .permutation
+            .mapping()
+            .map(|mapping| {
+                domain.lagrange_from_vec(
+                    mapping
+                        .map(|(column, row)| permutation_id_values[column][row])
+                        .collect(),
+                )
             })
             .collect();
 
+        let [permutation_ids, permutation_sigmas] =
+            [permutation_id_values, permutation_sigma_values].map(|columns| {
+                columns
+                    .into_iter()
+                    .map(|values| commit_fixed_column(params, values))
+                    .collect::<Vec<_>>()
+            });
+
         // We don't want to compress selectors for our usecase,
         // let (cs, selector_polys) = cs.compress_selectors(assembly.selectors);
         // fixed.extend(
@@ -126,61 +256,521 @@ impl<C: CurveAffine> ProvingKey<C> {
         //         .map(|poly| domain.lagrange_from_vec(poly)),
         // );
 
+        let vk = VerifyingKey::from_parts(
+            &domain,
+            num_rows,
+            cs.clone(),
+            fixed.iter().map(|c| c.commitment).collect(),
+            selectors.iter().map(|c| c.commitment).collect(),
+            permutation_sigmas.iter().map(|c| c.commitment).collect(),
+            query_instance,
+            lookup_challenges,
+        );
+
         Ok(ProvingKey {
             domain,
             num_rows,
-            usable_rows: assembly.usable_rows,
             cs,
             fixed,
             selectors,
+            permutation_ids,
+            permutation_sigmas,
+            strict_lookups: true,
+            vk,
         })
     }
+}
+
+impl<C: CurveAffine> ProvingKey<C> {
+    /// Get the underlying [`VerifyingKey`].
+    pub fn get_vk(&self) -> &VerifyingKey<C> {
+        &self.vk
+    }
 
     /// Maximum degree over all gates in the circuit
     pub fn max_folding_constraints_degree(&self) -> usize {
-        let mut max_degree = 0;
+        self.vk.max_folding_constraints_degree
+    }
 
-        // Get maximum degree over all gate polynomials
-        for gate in &self.cs.gates {
-            for poly in gate.polynomials() {
-                max_degree = std::cmp::max(max_degree, poly.folding_degree());
-            }
+    /// Whether instance columns are committed to, rather than added to the transcript
+    pub fn query_instance(&self) -> bool {
+        self.vk.query_instance
+    }
+
+    /// How the challenges of the lookup arguments are sampled
+    pub fn lookup_challenges(&self) -> LookupChallenges {
+        self.vk.lookup_challenges
+    }
+
+    /// Lookup arguments of the constraint system, grouped by table
+    pub fn lookup_tables(&self) -> &[LookupTable<C::Scalar>] {
+        &self.vk.lookup_tables
+    }
+
+    /// Whether creating an accumulator fails with `Error::LookupInputNotInTable`
+    /// when the input of a lookup is not contained in its table.
+    pub fn strict_lookups(&self) -> bool {
+        self.strict_lookups
+    }
+
+    /// Sets whether missing lookup inputs are reported when creating an accumulator.
+    /// If `strict` is unset, they are skipped, and the accumulator is only rejected by the decider.
+    /// This is useful for debugging circuits whose witness does not satisfy all lookups.
+    ///
+    /// Note that the input of a lookup is evaluated at every row of the trace,
+    /// including the rows where its selector is disabled, so that the input at these rows
+    /// must also be contained in the table (usually as the all-zero row).
+    /// A missing row does not contribute to the multiplicities mᵢ, but still gets a term hᵢ,
+    /// so the sums ∑ᵢ gᵢ and ∑ₖ,ᵢ hₖ,ᵢ differ and the accumulator fails `Accumulator::decide`
+    /// (with `DecideError::Lookup`), and any accumulator folded from it does as well.
+    pub fn set_strict_lookups(&mut self, strict: bool) {
+        self.strict_lookups = strict;
+    }
+
+    /// For each challenge cᵢ, the largest power cᵢᵈ appearing in the constraints of the circuit
+    pub fn max_challenge_powers(&self) -> &[usize] {
+        &self.vk.max_challenge_powers
+    }
+
+    /// Total number of linearly-independent constraints, each of which is weighted by its own challenge yⱼ
+    pub fn num_folding_constraints(&self) -> usize {
+        self.vk.num_folding_constraints
+    }
+
+    pub fn selector_ref(&self) -> Vec<&ColumnValues<C::Scalar>> {
+        self.selectors.iter().map(|c| &c.values).collect()
+    }
+
+    pub fn fixed_ref(&self) -> Vec<&ColumnValues<C::Scalar>> {
+        self.fixed.iter().map(|c| &c.values).collect()
+    }
+}
+
+impl<F, C> ProvingKey<C>
+where
+    F: SerdePrimeField + WithSmallOrderMulGroup<3> + FromUniformBytes<64>,
+    C: SerdeCurveAffine<ScalarExt = F>,
+{
+    /// Writes a proving key to a buffer.
+    ///
+    /// Writes the size of the domain, whether instance columns are
+    /// committed to and how lookup challenges are sampled, followed by the fixed, selector and permutation σ columns along with their commitments.
+    /// The permutation id columns only depend on the domain, and are recomputed when reading the key.
+    /// Field and curve elements are written according to `format`, as in [`crate::plonk::ProvingKey::write`].
+    pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        writer.write_all(&self.domain.k().to_be_bytes())?;
+        writer.write_all(&[self.vk.query_instance as u8])?;
+        writer.write_all(&[match self.vk.lookup_challenges {
+            LookupChallenges::Shared => 0,
+            LookupChallenges::PerTable => 1,
+        }])?;
+        for columns in [&self.fixed, &self.selectors, &self.permutation_sigmas] {
+            write_slice(columns, writer, |column, writer| {
+                column.write(writer, format)
+            })?;
         }
+        Ok(())
+    }
 
-        // Get maximum of all lookup constraints.
-        // Add 1 to account for theta challenge
-        // Add 1 to account for h/g
-        for lookup in &self.cs.lookups {
-            for poly in lookup
-                .input_expressions
-                .iter()
-                .chain(lookup.table_expressions.iter())
-            {
-                max_degree = std::cmp::max(max_degree, poly.folding_degree() + 2);
+    /// Reads a proving key from a buffer.
+    ///
+    /// The `ConstraintSystem` is rebuilt by configuring `ConcreteCircuit`,
+    /// and the number of columns read must match it.
+    /// The permutation id columns are recomputed and committed to with `commitment_params`,
+    /// whose size must match the one of the key.
+    /// Field and curve elements are read according to `format`, as in [`crate::plonk::ProvingKey::read`].
+    pub fn read<'params, R: io::Read, ConcreteCircuit: Circuit<F>, P: Params<'params, C>>(
+        reader: &mut R,
+        commitment_params: &P,
+        format: SerdeFormat,
+        #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
+    ) -> io::Result<Self> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        let k = u32::from_be_bytes(bytes);
+        if k != commitment_params.k() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Proving key does not match the size of the commitment parameters",
+            ));
+        }
+        let mut query_instance = [0u8; 1];
+        reader.read_exact(&mut query_instance)?;
+        let query_instance = query_instance[0] != 0;
+        let mut lookup_challenges = [0u8; 1];
+        reader.read_exact(&mut lookup_challenges)?;
+        let lookup_challenges = match lookup_challenges[0] {
+            0 => LookupChallenges::Shared,
+            1 => LookupChallenges::PerTable,
+            _ => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "Invalid lookup challenges mode",
+                ))
             }
+        };
+
+        let (domain, cs, _) = create_domain::<C, ConcreteCircuit>(
+            k,
+            #[cfg(feature = "circuit-params")]
+            params,
+        );
+        let num_rows = 1 << k;
+
+        let mut read_columns = || {
+            read_vec(reader, |reader| {
+                let mut column = Committed::read_with_num_rows(reader, format, num_rows)?;
+                column.values.densify();
+                Ok(column)
+            })
+        };
+        let fixed = read_columns()?;
+        let selectors = read_columns()?;
+        let permutation_sigmas = read_columns()?;
+
+        let shape_ok = fixed.len() == cs.num_fixed_columns
+            && selectors.len() == cs.num_selectors
+            && permutation_sigmas.len() == cs.permutation.columns.len();
+        if !shape_ok {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "Proving key does not match the constraint system of the circuit",
+            ));
         }
-        // add 1 for beta
-        // add 1 for ys
-        max_degree + 2
+
+        let permutation_ids = build_permutation_ids(&domain, cs.permutation.columns.len())
+            .into_iter()
+            .map(|values| commit_fixed_column(commitment_params, values))
+            .collect();
+
+        let vk = VerifyingKey::from_parts(
+            &domain,
+            num_rows,
+            cs.clone(),
+            fixed.iter().map(|c| c.commitment).collect(),
+            selectors.iter().map(|c| c.commitment).collect(),
+            permutation_sigmas.iter().map(|c| c.commitment).collect(),
+            query_instance,
+            lookup_challenges,
+        );
+
+        Ok(ProvingKey {
+            domain,
+            num_rows,
+            cs,
+            fixed,
+            selectors,
+            permutation_ids,
+            permutation_sigmas,
+            strict_lookups: true,
+            vk,
+        })
     }
 
-    /// Total number of linearly-independent constraints, whose degrees are larger than 1
-    pub fn num_folding_constraints(&self) -> usize {
-        self.cs
-            .gates
+    /// Writes a proving key to a vector of bytes using [`Self::write`].
+    pub fn to_bytes(&self, format: SerdeFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes, format)
+            .expect("Writing to vector should not fail");
+        bytes
+    }
+
+    /// Reads a proving key from a slice of bytes using [`Self::read`].
+    pub fn from_bytes<'params, ConcreteCircuit: Circuit<F>, P: Params<'params, C>>(
+        mut bytes: &[u8],
+        commitment_params: &P,
+        format: SerdeFormat,
+        #[cfg(feature = "circuit-params")] params: ConcreteCircuit::Params,
+    ) -> io::Result<Self> {
+        Self::read::<_, ConcreteCircuit, _>(
+            &mut bytes,
+            commitment_params,
+            format,
+            #[cfg(feature = "circuit-params")]
+            params,
+        )
+    }
+}
+
+/// Contains the fixed data for a circuit that is required to create and fold a `VerifierAccumulator`.
+/// Unlike the `ProvingKey`, it only stores commitments to the fixed and selector columns.
+#[derive(Debug, Clone)]
+pub struct VerifyingKey<C: CurveAffine> {
+    // number of rows in the trace
+    pub num_rows: usize,
+
+    // The circuit's unmodified constraint system
+    pub cs: ConstraintSystem<C::Scalar>,
+
+    // Commitments to the fixed columns
+    pub fixed_commitments: Vec<C>,
+    pub selector_commitments: Vec<C>,
+    pub permutation_commitments: Vec<C>,
+
+    // Whether instance columns are committed to, rather than added to the transcript
+    pub query_instance: bool,
+
+    // How the challenges of the lookup arguments are sampled
+    pub lookup_challenges: LookupChallenges,
+
+    // Cached folding parameters of `cs` (which don't change after construction)
+    pub lookup_tables: Vec<LookupTable<C::Scalar>>,
+    pub max_folding_constraints_degree: usize,
+    pub num_folding_constraints: usize,
+    pub max_challenge_powers: Vec<usize>,
+
+    // The representative of this `VerifyingKey` in transcripts.
+    transcript_repr: C::Scalar,
+}
+
+impl<C: CurveAffine> VerifyingKey<C>
+where
+    C::Scalar: FromUniformBytes<64>,
+{
+    fn from_parts(
+        domain: &EvaluationDomain<C::Scalar>,
+        num_rows: usize,
+        cs: ConstraintSystem<C::Scalar>,
+        fixed_commitments: Vec<C>,
+        selector_commitments: Vec<C>,
+        permutation_commitments: Vec<C>,
+        query_instance: bool,
+        lookup_challenges: LookupChallenges,
+    ) -> Self {
+        // Compute cached values.
+        let lookup_tables = LookupTable::group(&cs.lookups);
+        let max_folding_constraints_degree = max_folding_constraints_degree(&cs, &lookup_tables);
+        let num_folding_constraints = num_folding_constraints(&cs, &lookup_tables);
+        let max_challenge_powers = max_challenge_powers(&cs);
+
+        let mut vk = Self {
+            num_rows,
+            cs,
+            fixed_commitments,
+            selector_commitments,
+            permutation_commitments,
+            query_instance,
+            lookup_challenges,
+            lookup_tables,
+            max_folding_constraints_degree,
+            num_folding_constraints,
+            max_challenge_powers,
+            // Temporary, this is not pinned.
+            transcript_repr: C::Scalar::ZERO,
+        };
+
+        let mut hasher = Blake2bParams::new()
+            .hash_length(64)
+            .personal(b"Protostar-Vk-Key")
+            .to_state();
+
+        let s = format!("{:?}", vk.pinned(domain));
+
+        hasher.update(&(s.len() as u64).to_le_bytes());
+        hasher.update(s.as_bytes());
+
+        // Hash in final Blake2bState
+        vk.transcript_repr = C::Scalar::from_uniform_bytes(hasher.finalize().as_array());
+
+        vk
+    }
+}
+
+impl<C: CurveAffine> VerifyingKey<C> {
+    /// Hashes a verification key into a transcript.
+    pub fn hash_into<E: EncodedChallenge<C>, T: Transcript<C, E>>(
+        &self,
+        transcript: &mut T,
+    ) -> std::io::Result<()> {
+        transcript.common_scalar(self.transcript_repr)?;
+
+        Ok(())
+    }
+
+    /// Obtains a pinned representation of this verification key that contains
+    /// the minimal information necessary to reconstruct the verification key.
+    fn pinned<'a>(
+        &'a self,
+        domain: &'a EvaluationDomain<C::Scalar>,
+    ) -> PinnedVerificationKey<'a, C> {
+        PinnedVerificationKey {
+            base_modulus: C::Base::MODULUS,
+            scalar_modulus: C::Scalar::MODULUS,
+            domain: domain.pinned(),
+            num_rows: &self.num_rows,
+            cs: self.cs.pinned(),
+            fixed_commitments: &self.fixed_commitments,
+            selector_commitments: &self.selector_commitments,
+            permutation_commitments: &self.permutation_commitments,
+            query_instance: self.query_instance,
+            lookup_challenges: self.lookup_challenges,
+        }
+    }
+}
+
+/// Minimal representation of a verification key that can be used to identify
+/// its active contents.
+#[allow(dead_code)]
+#[derive(Debug)]
+struct PinnedVerificationKey<'a, C: CurveAffine> {
+    base_modulus: &'static str,
+    scalar_modulus: &'static str,
+    domain: PinnedEvaluationDomain<'a, C::Scalar>,
+    num_rows: &'a usize,
+    cs: PinnedConstraintSystem<'a, C::Scalar>,
+    fixed_commitments: &'a Vec<C>,
+    selector_commitments: &'a Vec<C>,
+    permutation_commitments: &'a Vec<C>,
+    query_instance: bool,
+    lookup_challenges: LookupChallenges,
+}
+
+/// Commits to a column of the proving key, using a sparse multiexp if few of its rows are non-zero.
+/// The values are stored densely, since they are read at every row of each fold.
+fn commit_fixed_column<'params, C: CurveAffine, P: Params<'params, C>>(
+    params: &P,
+    values: Polynomial<C::Scalar, LagrangeCoeff>,
+) -> Committed<C> {
+    let mut values = ColumnValues::new(values);
+    let blind = Blind::default();
+    let commitment = values.commit(params, blind).to_affine();
+    values.densify();
+    Committed {
+        values,
+        commitment,
+        blind,
+    }
+}
+
+/// Maximum degree over all gates in the circuit,
+/// where powers of challenges are considered as independent variables.
+fn max_folding_constraints_degree<F: Field>(
+    cs: &ConstraintSystem<F>,
+    lookup_tables: &[LookupTable<F>],
+) -> usize {
+    let mut max_degree = 0;
+
+    // Get maximum degree over all gate polynomials
+    for gate in &cs.gates {
+        for poly in gate.polynomials() {
+            max_degree = std::cmp::max(max_degree, expression::folding_degree(poly));
+        }
+    }
+
+    // Get maximum of all lookup constraints.
+    for table in lookup_tables {
+        max_degree = std::cmp::max(max_degree, lookup_degree(table));
+    }
+    // Get maximum of all shuffle constraints h⋅(r + ∑ⱼ θⱼ⋅inputⱼ) − 1 and g⋅(r + ∑ⱼ θⱼ⋅shuffleⱼ) − 1,
+    // whose degree is that of the largest expression, plus 1 for the θⱼ challenges and 1 for h/g.
+    for shuffle in &cs.shuffles {
+        for poly in shuffle
+            .input_expressions
             .iter()
-            .map(|gate| gate.polynomials().len())
-            .sum::<usize>()
-            + 2 * self.cs.lookups.len()
+            .chain(shuffle.shuffle_expressions.iter())
+        {
+            max_degree = std::cmp::max(max_degree, expression::folding_degree(poly) + 2);
+        }
+    }
+    // Get degree of the permutation constraints h⋅(r + w + θ⋅id) − 1 and g⋅(r + w + θ⋅σ) − 1.
+    // The challenges r, θ and the column w have degree 1, while id and σ are fixed,
+    // so the sum r + w + θ⋅id has degree 1, and multiplying by h or g gives degree 2.
+    if !cs.permutation.columns.is_empty() {
+        max_degree = std::cmp::max(max_degree, 2);
     }
 
-    pub fn selector_ref(&self) -> Vec<&[C::Scalar]> {
-        self.selectors.iter().map(|c| c.values.as_ref()).collect()
+    // add 2 for beta, since βᵢ = β'ᵢ mod m ⋅ β''ᵢ / m
+    // add 1 for ys
+    max_degree + 3
+}
+
+/// Degree of the folding constraints hₖ⋅(r + ∑ⱼ θⱼ⋅inputₖ,ⱼ) − 1 and g⋅(r + ∑ⱼ θⱼ⋅tableⱼ) − m of a lookup table,
+/// given by the largest degree of its input and table expressions,
+/// plus 1 for the θⱼ challenges and 1 for h/g.
+fn lookup_degree<F: Field>(table: &LookupTable<F>) -> usize {
+    table
+        .arguments
+        .iter()
+        .flat_map(|arg| arg.input_expressions.iter())
+        .chain(table.table_expressions().iter())
+        .map(expression::folding_degree)
+        .max()
+        .unwrap_or(0)
+        + 2
+}
+
+/// For each challenge cᵢ, returns the largest d such that cᵢᵈ appears in a gate, lookup or shuffle expression.
+/// The result is at least 1, so that the challenge itself is always available.
+fn max_challenge_powers<F: Field>(cs: &ConstraintSystem<F>) -> Vec<usize> {
+    let mut max_powers = vec![1; cs.num_challenges];
+
+    let gate_exprs = cs.gates.iter().flat_map(|gate| gate.polynomials().iter());
+    let lookup_exprs = cs.lookups.iter().flat_map(|lookup| {
+        lookup
+            .input_expressions
+            .iter()
+            .chain(lookup.table_expressions.iter())
+    });
+    let shuffle_exprs = cs.shuffles.iter().flat_map(|shuffle| {
+        shuffle
+            .input_expressions
+            .iter()
+            .chain(shuffle.shuffle_expressions.iter())
+    });
+
+    for expr in gate_exprs.chain(lookup_exprs).chain(shuffle_exprs) {
+        expression::max_challenge_powers(expr, &mut max_powers);
     }
+    max_powers
+}
 
-    pub fn fixed_ref(&self) -> Vec<&[C::Scalar]> {
-        self.fixed.iter().map(|c| c.values.as_ref()).collect()
+/// Total number of linearly-independent constraints, including linear ones which are only folded for masked accumulators
+fn num_folding_constraints<F: Field>(
+    cs: &ConstraintSystem<F>,
+    lookup_tables: &[LookupTable<F>],
+) -> usize {
+    cs.gates
+        .iter()
+        .map(|gate| gate.polynomials().len())
+        .sum::<usize>()
+        + lookup_tables
+            .iter()
+            .map(|table| 1 + table.arguments.len())
+            .sum::<usize>()
+        + 2 * cs.shuffles.len()
+        + 2 * cs.permutation.columns.len()
+}
+
+/// Returns the labels δʲ⋅ωⁱ identifying each cell (j, i) of the `num_columns` permutation columns.
+fn build_permutation_ids<F: WithSmallOrderMulGroup<3>>(
+    domain: &EvaluationDomain<F>,
+    num_columns: usize,
+) -> Vec<Polynomial<F, LagrangeCoeff>> {
+    // Compute [ω⁰, ω¹, ..., ωⁿ⁻¹]
+    let mut omega_powers = domain.empty_lagrange();
+    {
+        let omega = domain.get_omega();
+        parallelize(&mut omega_powers, |o, start| {
+            let mut cur = omega.pow_vartime(&[start as u64]);
+            for v in o.iter_mut() {
+                *v = cur;
+                cur *= &omega;
+            }
+        })
     }
+
+    // Compute [δ⁰⋅ωⁱ, δ¹⋅ωⁱ, ..., δᵐ⋅ωⁱ]
+    let mut ids = vec![omega_powers; num_columns];
+    parallelize(&mut ids, |o, start| {
+        let mut cur = F::DELTA.pow_vartime(&[start as u64]);
+        for omega_powers in o.iter_mut() {
+            for v in omega_powers.iter_mut() {
+                *v *= &cur;
+            }
+            cur *= &F::DELTA;
+        }
+    });
+    ids
 }
 
 /// Assembly to be used in circuit synthesis.
@@ -339,3 +929,84 @@ impl<F: Field> Assignment<F> for Assembly<F> {
         // Do nothing; we don't care about namespaces in this context.
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use halo2curves::pasta::pallas;
+
+    use super::{LookupChallenges, ProvingKey};
+    use crate::{
+        poly::{commitment::ParamsProver, ipa::commitment::ParamsIPA},
+        protostar::{
+            accumulator::Accumulator,
+            verifier::{
+                tests::{fixture, range_check_circuits, MulChainCircuit, RangeCheck},
+                VerifierAccumulator,
+            },
+        },
+        transcript::{Blake2bRead, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer},
+        SerdeFormat,
+    };
+
+    #[test]
+    fn test_proving_key_serialization() {
+        const K: u32 = 9;
+        let [circuit, _] = range_check_circuits();
+        let fixture = fixture(K, [&circuit], LookupChallenges::Shared);
+        let (params, pk) = (&fixture.params, &fixture.pk);
+        let proof = fixture.transcript.finalize();
+
+        for format in [
+            SerdeFormat::Processed,
+            SerdeFormat::RawBytes,
+            SerdeFormat::RawBytesUnchecked,
+        ] {
+            let bytes = pk.to_bytes(format);
+            let pk_read = ProvingKey::<pallas::Affine>::from_bytes::<RangeCheck, _>(
+                &bytes,
+                params,
+                format,
+                #[cfg(feature = "circuit-params")]
+                (),
+            )
+            .unwrap();
+
+            assert_eq!(pk_read.num_rows, pk.num_rows);
+            assert_eq!(pk_read.fixed, pk.fixed);
+            assert_eq!(pk_read.selectors, pk.selectors);
+            assert_eq!(pk_read.permutation_ids, pk.permutation_ids);
+            assert_eq!(pk_read.permutation_sigmas, pk.permutation_sigmas);
+
+            // Accumulators created with the original key are verified with the read one
+            let [acc] = &fixture.accs;
+            assert!(Accumulator::decide(params, &pk_read, acc));
+            let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            let v_acc = VerifierAccumulator::new(pk_read.get_vk(), &[], &mut v_transcript).unwrap();
+            assert!(v_acc == *acc);
+        }
+
+        // The key must match the constraint system of the circuit
+        let bytes = pk.to_bytes(SerdeFormat::Processed);
+        assert!(
+            ProvingKey::<pallas::Affine>::from_bytes::<MulChainCircuit<pallas::Scalar>, _>(
+                &bytes,
+                params,
+                SerdeFormat::Processed,
+                #[cfg(feature = "circuit-params")]
+                (),
+            )
+            .is_err()
+        );
+
+        // The permutation ids are recomputed with parameters of the same size as the key
+        let other_params = ParamsIPA::<pallas::Affine>::new(K + 1);
+        assert!(ProvingKey::<pallas::Affine>::from_bytes::<RangeCheck, _>(
+            &bytes,
+            &other_params,
+            SerdeFormat::Processed,
+            #[cfg(feature = "circuit-params")]
+            (),
+        )
+        .is_err());
+    }
+}
@@ -35,7 +35,7 @@ pub fn create_accumulator<
     transcript: &mut T,
 ) -> Result<accumulator::Accumulator<C>, Error> {
     // Hash verification key into transcript
-    // pk.vk.hash_into(transcript)?;
+    pk.get_vk().hash_into(transcript)?;
 
     // Add public inputs/outputs to the transcript, and convert them to `Polynomial`s
     // and run multi-phase IOP section to generate all `Advice` columns
@@ -43,11 +43,18 @@ pub fn create_accumulator<
         accumulator::gate::Transcript::new(params, pk, circuit, instances, &mut rng, transcript)?;
 
     // Run the 2-round logUp IOP for all lookup arguments
-    let lookups = accumulator::lookup::new(params, pk, &gate, &mut rng, transcript);
+    let lookups = accumulator::lookup::new(params, pk, &gate, &mut rng, transcript)?;
+
+    // Run the 1-round logUp IOP for all shuffle arguments
+    let shuffles = accumulator::shuffle::new(params, pk, &gate, &mut rng, transcript)?;
+
+    // Run the 1-round logUp IOP for the copy constraints
+    let permutation =
+        accumulator::permutation::Transcript::new(params, pk, &gate, &mut rng, transcript)?;
 
     // Generate random column(s) to multiply each constraint
     // so that we can compress them to a single constraint
-    let beta = accumulator::compressed_verifier::Transcript::new(params, transcript);
+    let beta = accumulator::compressed_verifier::Transcript::new(params, transcript)?;
 
     // Challenge for the RLC of all constraints (all gates and all lookups)
     let y = *transcript.squeeze_challenge_scalar::<C::Scalar>();
@@ -57,8 +64,36 @@ pub fn create_accumulator<
     Ok(accumulator::Accumulator {
         gate,
         lookups,
+        shuffles,
+        permutation,
         beta,
         ys,
         error: C::Scalar::ZERO,
+        masked: false,
     })
 }
+
+/// Runs the IOP as in `create_accumulator`, and folds the result with a random masking accumulator
+/// which satisfies the relaxed relation.
+/// The returned `Accumulator`, as well as the transcript of the folding, reveal nothing about the witness.
+/// The verifier must use `VerifierAccumulator::mask` on the accumulator read from the transcript.
+pub fn create_hiding_accumulator<
+    'params,
+    C: CurveAffine,
+    P: Params<'params, C>,
+    E: EncodedChallenge<C>,
+    R: RngCore,
+    T: TranscriptWrite<C, E>,
+    ConcreteCircuit: Circuit<C::Scalar>,
+>(
+    params: &P,
+    pk: &ProvingKey<C>,
+    circuit: &ConcreteCircuit,
+    instances: &[&[C::Scalar]],
+    mut rng: R,
+    transcript: &mut T,
+) -> Result<accumulator::Accumulator<C>, Error> {
+    let acc = create_accumulator(params, pk, circuit, instances, &mut rng, transcript)?;
+    let masking = accumulator::masking::new(params, pk, &mut rng, transcript)?;
+    accumulator::Accumulator::fold(pk, acc, masking, transcript)
+}
@@ -1,29 +1,32 @@
 use ff::{Field, FromUniformBytes, WithSmallOrderMulGroup};
-use group::Curve;
+use group::{Curve, Group};
 use halo2curves::{CurveAffine, CurveExt};
 use rand_core::RngCore;
 use std::{
     collections::{BTreeSet, HashMap},
-    iter::zip,
+    io,
+    iter::{self, zip},
 };
 
 use super::{
-    accumulator::{self, Accumulator},
-    keygen::ProvingKey,
+    accumulator::{self, lookup, Accumulator},
+    keygen::VerifyingKey,
 };
 use crate::arithmetic::{
     best_multiexp, compute_inner_product, eval_polynomial, parallelize, powers,
 };
+use crate::helpers::{read_vec, write_slice, SerdeCurveAffine, SerdePrimeField};
 use crate::plonk::Error;
 use crate::poly::commitment::{CommitmentScheme, Verifier};
 use crate::poly::VerificationStrategy;
 use crate::poly::{
     commitment::{Blind, Params, MSM},
-    Guard, VerifierQuery,
+    empty_lagrange, Guard, VerifierQuery,
 };
 use crate::transcript::{
-    read_n_points, read_n_scalars, EncodedChallenge, TranscriptRead, TranscriptWrite,
+    read_n_points, read_n_scalars, EncodedChallenge, Transcript, TranscriptRead, TranscriptWrite,
 };
+use crate::SerdeFormat;
 use rayon::prelude::{
     IndexedParallelIterator, IntoParallelIterator, IntoParallelRefIterator, ParallelIterator,
 };
@@ -31,28 +34,107 @@ use rayon::prelude::{
 #[derive(Debug, Clone, PartialEq)]
 pub struct LookupAccumulator<C: CurveAffine> {
     pub m: C,
+    pub r: C::Scalar,
+    pub thetas: Vec<C::Scalar>,
+    pub g: C,
+    // Commitments to the columns hₖ of each argument into the table
+    pub h: Vec<C>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ShuffleAccumulator<C: CurveAffine> {
     pub r: C::Scalar,
     pub thetas: Vec<C::Scalar>,
     pub g: C,
     pub h: C,
 }
 
+#[derive(Debug, Clone, PartialEq)]
+pub struct PermutationAccumulator<C: CurveAffine> {
+    pub r: C::Scalar,
+    pub theta: C::Scalar,
+    pub g: Vec<C>,
+    pub h: Vec<C>,
+}
+
+/// Commitments to the vector of powers of `challenge` and its error vector.
+/// The accumulator contains one for each of β' and β''.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BetaAccumulator<C: CurveAffine> {
+    pub challenge: C::Scalar,
+    pub commitment: C,
+    pub error: C,
+}
+
+impl<C: CurveAffine> BetaAccumulator<C> {
+    /// Computes the linear combination ∑ₖ wₖ⋅accₖ with the given `weights`, where the error is given by
+    /// e = ∑ₖ wₖ⋅eₖ + ∑ₖ wₖ⋅(r − rₖ)⋅aₖ, with r = ∑ₖ wₖ⋅rₖ the new challenge and aₖ the committed powers.
+    fn fold<'a>(weights: &[C::Scalar], accs: impl Iterator<Item = &'a Self> + Clone) -> Self
+    where
+        C: 'a,
+    {
+        let challenge =
+            accumulator::linear_combination(weights, accs.clone().map(|acc| acc.challenge));
+
+        let (commitment, error) = zip(accs, weights).fold(
+            (C::Curve::identity(), C::Curve::identity()),
+            |(commitment, error), (acc, weight)| {
+                (
+                    commitment + acc.commitment * *weight,
+                    error
+                        + acc.error * *weight
+                        + acc.commitment * (*weight * (challenge - acc.challenge)),
+                )
+            },
+        );
+
+        Self {
+            challenge,
+            commitment: commitment.to_affine(),
+            error: error.to_affine(),
+        }
+    }
+}
+
+impl<C: CurveAffine> From<&accumulator::compressed_verifier::Powers<C>> for BetaAccumulator<C> {
+    fn from(powers: &accumulator::compressed_verifier::Powers<C>) -> Self {
+        Self {
+            challenge: powers.challenge,
+            commitment: powers.powers.commitment,
+            error: powers.error.commitment,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct VerifierAccumulator<C: CurveAffine> {
+    // Instance values, which are empty if the verifying key has `query_instance` set
     pub instance: Vec<Vec<C::Scalar>>,
+    // Instance commitments, which are empty unless the verifying key has `query_instance` set
+    pub instance_commitments: Vec<C>,
     pub advice: Vec<C>,
-    pub challenges: Vec<C::Scalar>,
+    pub challenges: Vec<Vec<C::Scalar>>,
     pub lookup_accumulators: Vec<LookupAccumulator<C>>,
-    pub beta: C::Scalar,
-    pub beta_commitment: C,
-    pub beta_error: C,
+    pub shuffle_accumulators: Vec<ShuffleAccumulator<C>>,
+    pub permutation_accumulator: PermutationAccumulator<C>,
+    pub beta: BetaAccumulator<C>,
+    pub beta_sqrt: BetaAccumulator<C>,
     pub ys: Vec<C::Scalar>,
     pub error: C::Scalar,
 }
 
 impl<C: CurveAffine> PartialEq<accumulator::Accumulator<C>> for VerifierAccumulator<C> {
     fn eq(&self, other: &accumulator::Accumulator<C>) -> bool {
-        let instance_ok = {
+        let instance_ok = if !self.instance_commitments.is_empty() {
+            let len_ok = self.instance_commitments.len() == other.gate.instance.len();
+            let commitments_ok = self
+                .instance_commitments
+                .iter()
+                .zip(other.gate.instance.iter())
+                .all(|(a, b)| *a == b.commitment);
+
+            len_ok && commitments_ok
+        } else {
             let len_ok = self.instance.len() == other.gate.instance.len();
             let values_ok = self
                 .instance
@@ -85,62 +167,196 @@ impl<C: CurveAffine> PartialEq<accumulator::Accumulator<C>> for VerifierAccumula
                         && a.r == b.r
                         && a.thetas == b.thetas
                         && a.g == b.g.commitment
-                        && a.h == b.h.commitment
+                        && a.h.len() == b.h.len()
+                        && zip(&a.h, &b.h).all(|(a, b)| *a == b.commitment)
                 });
 
             len_ok && commitments_ok
         };
+        let shuffle_ok = {
+            let len_ok = self.shuffle_accumulators.len() == other.shuffles.len();
+            let commitments_ok = self
+                .shuffle_accumulators
+                .iter()
+                .zip(other.shuffles.iter())
+                .all(|(a, b)| {
+                    a.r == b.r
+                        && a.thetas == b.thetas
+                        && a.g == b.g.commitment
+                        && a.h == b.h.commitment
+                });
 
-        let beta_ok = {
-            self.beta == other.beta.beta.values[1]
-                && self.beta_commitment == other.beta.beta.commitment
-                && self.beta_error == other.beta.error.commitment
+            len_ok && commitments_ok
+        };
+        let permutation_ok = {
+            let a = &self.permutation_accumulator;
+            let b = &other.permutation;
+            let len_ok = a.g.len() == b.g.len() && a.h.len() == b.h.len();
+            let commitments_ok = zip(a.g.iter(), b.g.iter()).all(|(a, b)| *a == b.commitment)
+                && zip(a.h.iter(), b.h.iter()).all(|(a, b)| *a == b.commitment);
+
+            len_ok && commitments_ok && a.r == b.r && a.theta == b.theta
         };
 
+        let beta_ok = self.beta == BetaAccumulator::from(&other.beta.beta)
+            && self.beta_sqrt == BetaAccumulator::from(&other.beta.beta_sqrt);
+
         let ys_ok = self.ys == other.ys;
         let error_ok = self.error == other.error;
 
-        instance_ok && advice_ok && lookup_ok && beta_ok && ys_ok && error_ok
+        instance_ok
+            && advice_ok
+            && lookup_ok
+            && shuffle_ok
+            && permutation_ok
+            && beta_ok
+            && ys_ok
+            && error_ok
+    }
+}
+
+impl<C: CurveAffine> VerifierAccumulator<C> {
+    /// Returns the public part of the accumulator, as seen by the verifier.
+    pub fn from_accumulator(vk: &VerifyingKey<C>, acc: &Accumulator<C>) -> Self {
+        let (instance, instance_commitments) = if vk.query_instance {
+            let commitments = acc.gate.instance.iter().map(|c| c.commitment).collect();
+            (vec![], commitments)
+        } else {
+            let values = acc
+                .gate
+                .instance
+                .iter()
+                .map(|instance| instance.values.iter().copied().collect())
+                .collect();
+            (values, vec![])
+        };
+        let advice = acc.gate.advice.iter().map(|c| c.commitment).collect();
+        let lookup_accumulators = acc
+            .lookups
+            .iter()
+            .map(|lookup| LookupAccumulator {
+                m: lookup.m.commitment,
+                r: lookup.r,
+                thetas: lookup.thetas.clone(),
+                g: lookup.g.commitment,
+                h: lookup.h.iter().map(|h| h.commitment).collect(),
+            })
+            .collect();
+        let shuffle_accumulators = acc
+            .shuffles
+            .iter()
+            .map(|shuffle| ShuffleAccumulator {
+                r: shuffle.r,
+                thetas: shuffle.thetas.clone(),
+                g: shuffle.g.commitment,
+                h: shuffle.h.commitment,
+            })
+            .collect();
+        let permutation_accumulator = PermutationAccumulator {
+            r: acc.permutation.r,
+            theta: acc.permutation.theta,
+            g: acc.permutation.g.iter().map(|c| c.commitment).collect(),
+            h: acc.permutation.h.iter().map(|c| c.commitment).collect(),
+        };
+
+        Self {
+            instance,
+            instance_commitments,
+            advice,
+            challenges: acc.gate.challenges.clone(),
+            lookup_accumulators,
+            shuffle_accumulators,
+            permutation_accumulator,
+            beta: BetaAccumulator::from(&acc.beta.beta),
+            beta_sqrt: BetaAccumulator::from(&acc.beta.beta_sqrt),
+            ys: acc.ys.clone(),
+            error: acc.error,
+        }
     }
 }
 
 impl<C: CurveAffine> VerifierAccumulator<C> {
     /// Create a new `VerifierAccumulator` by reading the IOP transcripts from the Prover and save commitments and challenges
+    /// Returns `Error::InvalidInstances` if the verifying key has `query_instance` set,
+    /// in which case `new_with_instance_commitments` must be used instead,
+    /// and `Error::InstanceTooLarge` if an instance column is longer than the number of rows.
     pub fn new<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
-        pk: &ProvingKey<C>,
+        vk: &VerifyingKey<C>,
         instances: &[&[C::Scalar]],
-        // TODO(@gnosed): replace pk with vk: VerifiyingKey<C>
         transcript: &mut T,
     ) -> Result<Self, Error> {
-        //
-        // Get instance commitments
-        //
         // Check that instances matches the expected number of instance columns
-        if instances.len() != pk.cs.num_instance_columns {
+        if vk.query_instance || instances.len() != vk.cs.num_instance_columns {
             return Err(Error::InvalidInstances);
         }
 
+        // Hash verification key into transcript
+        vk.hash_into(transcript)?;
+
         for instance in instances.iter() {
             for value in instance.iter() {
                 transcript.common_scalar(*value)?;
             }
         }
 
-        let instance: Vec<_> = instances.iter().map(|instance| instance.to_vec()).collect();
+        // Pad the instance columns to the full number of rows, so that they can be folded row by row
+        let instance = instances
+            .iter()
+            .map(|instance| {
+                if instance.len() > vk.num_rows {
+                    return Err(Error::InstanceTooLarge);
+                }
+                let mut column = instance.to_vec();
+                column.resize(vk.num_rows, C::Scalar::ZERO);
+                Ok(column)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Self::read_transcript(vk, instance, vec![], transcript)
+    }
+
+    /// Create a new `VerifierAccumulator` for a verifying key with `query_instance` set,
+    /// where the instance columns are given by their commitments (see `commit_instance`).
+    /// Only the commitments are folded, so the size of the accumulator does not depend on the number of public inputs.
+    pub fn new_with_instance_commitments<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
+        vk: &VerifyingKey<C>,
+        instance_commitments: &[C],
+        transcript: &mut T,
+    ) -> Result<Self, Error> {
+        // Check that instances matches the expected number of instance columns
+        if !vk.query_instance || instance_commitments.len() != vk.cs.num_instance_columns {
+            return Err(Error::InvalidInstances);
+        }
 
         // Hash verification key into transcript
-        // TODO(@gnosed): is it necessary? If yes, change it when the VerifyingKey was implemented
-        // vk.hash_into(transcript)?;
+        vk.hash_into(transcript)?;
+
+        // The commitments are known by both parties, and the identity cannot be added to the transcript.
+        for commitment in instance_commitments.iter() {
+            if !bool::from(commitment.is_identity()) {
+                transcript.common_point(*commitment)?;
+            }
+        }
+
+        Self::read_transcript(vk, vec![], instance_commitments.to_vec(), transcript)
+    }
 
+    /// Reads the remainder of the IOP transcript, once the instance columns have been added to it.
+    fn read_transcript<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
+        vk: &VerifyingKey<C>,
+        instance: Vec<Vec<C::Scalar>>,
+        instance_commitments: Vec<C>,
+        transcript: &mut T,
+    ) -> Result<Self, Error> {
         //
         // Get advice commitments and challenges
         //
         let (advice, challenges) = {
-            let mut advice_commitments = vec![C::identity(); pk.cs.num_advice_columns];
-            let mut challenges = vec![C::Scalar::ZERO; pk.cs.num_challenges];
+            let mut advice_commitments = vec![C::identity(); vk.cs.num_advice_columns];
+            let mut challenges = vec![C::Scalar::ZERO; vk.cs.num_challenges];
 
-            for current_phase in pk.cs.phases() {
-                for (phase, commitment) in pk
+            for current_phase in vk.cs.phases() {
+                for (phase, commitment) in vk
                     .cs
                     .advice_column_phase
                     .iter()
@@ -150,19 +366,24 @@ impl<C: CurveAffine> VerifierAccumulator<C> {
                         *commitment = transcript.read_point()?;
                     }
                 }
-                for (phase, challenge) in pk.cs.challenge_phase.iter().zip(challenges.iter_mut()) {
+                for (phase, challenge) in vk.cs.challenge_phase.iter().zip(challenges.iter_mut()) {
                     if current_phase == *phase {
                         *challenge = *transcript.squeeze_challenge_scalar::<()>();
                     }
                 }
             }
 
+            // Compute the powers of each challenge which are substituted in the constraints
+            let challenges = zip(challenges, &vk.max_challenge_powers)
+                .map(|(challenge, max_power)| powers(challenge).skip(1).take(*max_power).collect())
+                .collect();
+
             (advice_commitments, challenges)
         };
         //
         // Get lookup commitments to m(x), g(x) and h(x) polys
         //
-        let num_lookups = pk.cs.lookups.len();
+        let num_lookups = vk.lookup_tables.len();
 
         // Read all commitments m_i(X)
         let mut m_commitments = vec![C::identity(); num_lookups];
@@ -170,71 +391,302 @@ impl<C: CurveAffine> VerifierAccumulator<C> {
             *m_commitment = transcript.read_point()?;
         }
 
-        // Get challenge r, theta
-        let [r, theta] = [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+        // Get challenges r, theta, shared by all lookups or sampled for each of them
+        let lookup_challenges =
+            lookup::squeeze_challenges(vk.lookup_challenges, num_lookups, transcript);
 
-        // Get h_i(X), g_i(X) from the transcript for each lookup
-        let lookup_accumulators: Vec<_> = pk
-            .cs
-            .lookups
-            .iter()
+        // Get g_i(X), and h_i,k(X) for each argument k, from the transcript for each lookup table
+        let lookup_accumulators: Vec<_> = zip(&vk.lookup_tables, lookup_challenges)
             .enumerate()
-            .map(|(i, arg)| {
-                let num_thetas = arg.input_expressions().len();
+            .map(|(i, (table, [r, theta]))| {
+                let num_thetas = table.table_expressions().len();
                 let thetas: Vec<_> = powers(theta).take(num_thetas).collect();
                 let g_commitment = transcript.read_point()?;
-                let h_commitment = transcript.read_point()?;
+                let h_commitments = read_n_points(transcript, table.arguments().len())?;
                 Ok(LookupAccumulator {
                     m: m_commitments[i],
                     r,
                     thetas,
                     g: g_commitment,
-                    h: h_commitment,
+                    h: h_commitments,
                 })
             })
             .collect::<Result<Vec<LookupAccumulator<C>>, Error>>()?;
 
         //
-        // Get beta commitment
+        // Get shuffle commitments to g(x) and h(x) polys
+        //
+        let shuffle_accumulators = if vk.cs.shuffles.is_empty() {
+            vec![]
+        } else {
+            // Get challenge r, theta
+            let [r, theta] = [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+
+            vk.cs
+                .shuffles
+                .iter()
+                .map(|arg| {
+                    let num_thetas = arg.input_expressions().len();
+                    let thetas: Vec<_> = powers(theta).take(num_thetas).collect();
+                    let g = transcript.read_point()?;
+                    let h = transcript.read_point()?;
+                    Ok(ShuffleAccumulator { r, thetas, g, h })
+                })
+                .collect::<Result<Vec<ShuffleAccumulator<C>>, Error>>()?
+        };
+
+        //
+        // Get permutation commitments to h(x) and g(x) polys
+        //
+        let permutation_accumulator = {
+            let num_columns = vk.cs.permutation.columns.len();
+            if num_columns == 0 {
+                PermutationAccumulator {
+                    r: C::Scalar::ZERO,
+                    theta: C::Scalar::ZERO,
+                    g: vec![],
+                    h: vec![],
+                }
+            } else {
+                let [r, theta] =
+                    [(); 2].map(|_| *transcript.squeeze_challenge_scalar::<C::Scalar>());
+                let h = read_n_points(transcript, num_columns)?;
+                let g = read_n_points(transcript, num_columns)?;
+                PermutationAccumulator { r, theta, g, h }
+            }
+        };
+
+        //
+        // Get beta commitments to β' and β''
         //
         let beta = *transcript.squeeze_challenge_scalar::<C::Scalar>();
-        let beta_commitment = transcript.read_point()?;
-        let beta_error = C::identity();
+        let beta_sqrt =
+            beta.pow_vartime([accumulator::compressed_verifier::sqrt_stride(vk.num_rows) as u64]);
+        let beta = BetaAccumulator {
+            challenge: beta,
+            commitment: transcript.read_point()?,
+            error: C::identity(),
+        };
+        let beta_sqrt = BetaAccumulator {
+            challenge: beta_sqrt,
+            commitment: transcript.read_point()?,
+            error: C::identity(),
+        };
 
         // Challenge for the RLC of all constraints (all gates and all lookups)
         let y = *transcript.squeeze_challenge_scalar::<C::Scalar>();
-        let ys: Vec<C::Scalar> = powers(y).take(pk.num_folding_constraints()).collect();
+        let ys: Vec<C::Scalar> = powers(y).take(vk.num_folding_constraints).collect();
 
         Ok(VerifierAccumulator {
             instance,
+            instance_commitments,
             advice,
             challenges,
             beta,
-            beta_commitment,
-            beta_error,
+            beta_sqrt,
             lookup_accumulators,
+            shuffle_accumulators,
+            permutation_accumulator,
             ys,
             error: C::Scalar::ZERO,
         })
     }
+    /// Writes all challenges and commitments of the accumulator to the transcript.
+    /// Trailing zeros of instance columns are skipped, so that the result is the same
+    /// whether the instance values were padded to the full column or not.
+    /// Commitments to the identity cannot be written to the transcript and are skipped as well.
+    pub(crate) fn hash_into<E: EncodedChallenge<C>, T: Transcript<C, E>>(
+        &self,
+        transcript: &mut T,
+    ) -> io::Result<()> {
+        for instance in self.instance.iter() {
+            let len = instance
+                .iter()
+                .rposition(|value| !bool::from(value.is_zero()))
+                .map_or(0, |i| i + 1);
+            for value in instance[..len].iter() {
+                transcript.common_scalar(*value)?;
+            }
+        }
+        for challenge in self
+            .challenges
+            .iter()
+            .flatten()
+            .chain(
+                self.lookup_accumulators
+                    .iter()
+                    .flat_map(|lookup| iter::once(&lookup.r).chain(lookup.thetas.iter())),
+            )
+            .chain(
+                self.shuffle_accumulators
+                    .iter()
+                    .flat_map(|shuffle| iter::once(&shuffle.r).chain(shuffle.thetas.iter())),
+            )
+            .chain([
+                &self.permutation_accumulator.r,
+                &self.permutation_accumulator.theta,
+                &self.beta.challenge,
+                &self.beta_sqrt.challenge,
+            ])
+            .chain(self.ys.iter())
+            .chain(iter::once(&self.error))
+        {
+            transcript.common_scalar(*challenge)?;
+        }
+        for commitment in self
+            .instance_commitments
+            .iter()
+            .chain(self.advice.iter())
+            .chain(
+                self.lookup_accumulators
+                    .iter()
+                    .flat_map(|lookup| [&lookup.m, &lookup.g].into_iter().chain(&lookup.h)),
+            )
+            .chain(
+                self.shuffle_accumulators
+                    .iter()
+                    .flat_map(|shuffle| [&shuffle.g, &shuffle.h]),
+            )
+            .chain(self.permutation_accumulator.g.iter())
+            .chain(self.permutation_accumulator.h.iter())
+            .chain([
+                &self.beta.commitment,
+                &self.beta.error,
+                &self.beta_sqrt.commitment,
+                &self.beta_sqrt.error,
+            ])
+        {
+            if !bool::from(commitment.is_identity()) {
+                transcript.common_point(*commitment)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads the random masking accumulator sent by `create_hiding_accumulator`, and folds it into `self`.
+    /// Returns an error if the accumulator does not match the shape of `vk`, or if the transcript cannot be read.
+    pub fn mask<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
+        self,
+        vk: &VerifyingKey<C>,
+        transcript: &mut T,
+    ) -> Result<Self, Error> {
+        self.check_shape(vk)?;
+
+        // Instance columns of the masking accumulator are zero
+        let instance = self
+            .instance
+            .iter()
+            .map(|instance| vec![C::Scalar::ZERO; instance.len()])
+            .collect();
+        let instance_commitments = vec![C::identity(); self.instance_commitments.len()];
+
+        let advice = read_n_points(transcript, vk.cs.num_advice_columns)?;
+        let challenges = vk
+            .max_challenge_powers
+            .iter()
+            .map(|max_power| {
+                let challenge = transcript.read_scalar()?;
+                Ok(powers(challenge).skip(1).take(*max_power).collect())
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let lookup_accumulators = vk
+            .lookup_tables
+            .iter()
+            .map(|table| {
+                let [r, theta] = read_n_scalars(transcript, 2)?.try_into().unwrap();
+                let thetas = powers(theta)
+                    .take(table.table_expressions().len())
+                    .collect();
+                let [m, g] = read_n_points(transcript, 2)?.try_into().unwrap();
+                let h = read_n_points(transcript, table.arguments().len())?;
+                Ok(LookupAccumulator { m, r, thetas, g, h })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let shuffle_accumulators = vk
+            .cs
+            .shuffles
+            .iter()
+            .map(|arg| {
+                let [r, theta] = read_n_scalars(transcript, 2)?.try_into().unwrap();
+                let thetas = powers(theta).take(arg.input_expressions().len()).collect();
+                let [g, h] = read_n_points(transcript, 2)?.try_into().unwrap();
+                Ok(ShuffleAccumulator { r, thetas, g, h })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let permutation_accumulator = {
+            let num_columns = vk.cs.permutation.columns.len();
+            if num_columns == 0 {
+                PermutationAccumulator {
+                    r: C::Scalar::ZERO,
+                    theta: C::Scalar::ZERO,
+                    g: vec![],
+                    h: vec![],
+                }
+            } else {
+                let [r, theta] = read_n_scalars(transcript, 2)?.try_into().unwrap();
+                let h = read_n_points(transcript, num_columns)?;
+                let g = read_n_points(transcript, num_columns)?;
+                PermutationAccumulator { r, theta, g, h }
+            }
+        };
+
+        let beta = transcript.read_scalar()?;
+        let beta_sqrt =
+            beta.pow_vartime([accumulator::compressed_verifier::sqrt_stride(vk.num_rows) as u64]);
+        let beta = BetaAccumulator {
+            challenge: beta,
+            commitment: transcript.read_point()?,
+            error: C::identity(),
+        };
+        let beta_sqrt = BetaAccumulator {
+            challenge: beta_sqrt,
+            commitment: transcript.read_point()?,
+            error: C::identity(),
+        };
+
+        let y = transcript.read_scalar()?;
+        let ys = powers(y).take(vk.num_folding_constraints).collect();
+        let error = transcript.read_scalar()?;
+
+        let masking = Self {
+            instance,
+            instance_commitments,
+            advice,
+            challenges,
+            lookup_accumulators,
+            shuffle_accumulators,
+            permutation_accumulator,
+            beta,
+            beta_sqrt,
+            ys,
+            error,
+        };
+        Self::fold(vk, self, masking, transcript)
+    }
+
+    /// Folds two accumulators given the quotient of the error polynomial sent by `Accumulator::fold`.
+    /// Returns an error if the accumulators do not match the shape of `vk`, or if the transcript cannot be read.
     pub fn fold<E: EncodedChallenge<C>, T: TranscriptRead<C, E>>(
-        pk: &ProvingKey<C>,
+        vk: &VerifyingKey<C>,
         acc0: Self,
         acc1: Self,
         transcript: &mut T,
-    ) -> Self {
+    ) -> Result<Self, Error> {
+        acc0.check_shape(vk)?;
+        acc1.check_shape(vk)?;
+
         //
         // Get error commitments
         // (We subtract 2 since we expect the quotient of the error polynomial)
         //
-        let final_error_poly_len = pk.max_folding_constraints_degree() + 1;
+        let final_error_poly_len = vk.max_folding_constraints_degree + 1;
         // Prover doesn't send the first two coefficient since Verifier already know e(0) and e(1)
         let quotient_final_error_poly_len = final_error_poly_len - 2;
 
-        let mut e_commitments = vec![C::Scalar::ZERO; quotient_final_error_poly_len];
-        for e_commitment in e_commitments.iter_mut() {
-            *e_commitment = transcript.read_scalar().unwrap();
-        }
+        let e_commitments = read_n_scalars(transcript, quotient_final_error_poly_len)?;
 
         let alpha = *transcript.squeeze_challenge_scalar::<C::Scalar>();
 
@@ -245,84 +697,368 @@ impl<C: CurveAffine> VerifierAccumulator<C> {
             + alpha * acc1.error;
         let error = final_error;
 
+        Ok(Self::merge(
+            &[C::Scalar::ONE - alpha, alpha],
+            &[acc0, acc1],
+            error,
+        ))
+    }
+
+    /// Folds `N` accumulators with a single challenge α, given the quotient of the error polynomial
+    /// sent by `Accumulator::fold_many`.
+    pub fn fold_many<E: EncodedChallenge<C>, T: TranscriptRead<C, E>, const N: usize>(
+        vk: &VerifyingKey<C>,
+        accs: [Self; N],
+        transcript: &mut T,
+    ) -> Result<Self, Error> {
+        if N == 0 {
+            return Err(Error::AccumulatorShapeMismatch);
+        }
+        for acc in &accs {
+            acc.check_shape(vk)?;
+        }
+
+        // The error polynomial e(X) has degree d⋅(N−1), and the prover sends its quotient
+        // by the polynomial vanishing over {0, 1, ..., N−1}, since the verifier already knows e(0), ..., e(N−1).
+        let final_error_poly_len = vk.max_folding_constraints_degree * (N - 1) + 1;
+        let quotient_final_error_poly_len = final_error_poly_len - N;
+
+        let e_commitments = read_n_scalars(transcript, quotient_final_error_poly_len)?;
+
+        let alpha = *transcript.squeeze_challenge_scalar::<C::Scalar>();
+
+        // eval e'(alpha), then eval e(alpha) = ∏ₖ (alpha − k)⋅e'(alpha) + ∑ₖ Lₖ(alpha)⋅e(k)
+        let weights = accumulator::lagrange_basis::<_, N>(alpha);
+        let quotient_final_error_poly = eval_polynomial(&e_commitments, alpha);
+        let error = accumulator::vanishing_at_integers(alpha, N) * quotient_final_error_poly
+            + accumulator::linear_combination(&weights, accs.iter().map(|acc| acc.error));
+
+        Ok(Self::merge(&weights, &accs, error))
+    }
+
+    /// Checks that the accumulator contains the number of columns, challenges and constraints
+    /// expected by the verifying key, and that all instance columns have the same number of rows.
+    pub(super) fn check_shape(&self, vk: &VerifyingKey<C>) -> Result<(), Error> {
+        let cs = &vk.cs;
+        let instance_ok = if vk.query_instance {
+            self.instance.is_empty() && self.instance_commitments.len() == cs.num_instance_columns
+        } else {
+            self.instance.len() == cs.num_instance_columns
+                && self
+                    .instance
+                    .iter()
+                    .all(|instance| instance.len() == vk.num_rows)
+                && self.instance_commitments.is_empty()
+        };
+        let shape_ok = instance_ok
+            && self.advice.len() == cs.num_advice_columns
+            && self.challenges.len() == vk.max_challenge_powers.len()
+            && zip(&self.challenges, &vk.max_challenge_powers)
+                .all(|(powers, max_power)| powers.len() == *max_power)
+            && self.lookup_accumulators.len() == vk.lookup_tables.len()
+            && zip(&self.lookup_accumulators, &vk.lookup_tables).all(|(lookup, table)| {
+                lookup.thetas.len() == table.table_expressions().len()
+                    && lookup.h.len() == table.arguments().len()
+            })
+            && self.shuffle_accumulators.len() == cs.shuffles.len()
+            && zip(&self.shuffle_accumulators, &cs.shuffles)
+                .all(|(shuffle, arg)| shuffle.thetas.len() == arg.input_expressions.len())
+            && self.permutation_accumulator.g.len() == cs.permutation.columns.len()
+            && self.permutation_accumulator.h.len() == cs.permutation.columns.len()
+            && self.ys.len() == vk.num_folding_constraints;
+
+        if shape_ok {
+            Ok(())
+        } else {
+            Err(Error::AccumulatorShapeMismatch)
+        }
+    }
+
+    /// Computes the linear combination ∑ₖ wₖ⋅accₖ of all accumulators with the given `weights`,
+    /// where the new `error` is given by the folding reduction.
+    fn merge(weights: &[C::Scalar], accs: &[Self], error: C::Scalar) -> Self {
+        let acc0 = &accs[0];
+        let fold_scalars = |values: &mut dyn Iterator<Item = C::Scalar>| {
+            accumulator::linear_combination(weights, values)
+        };
+        let fold_points = |points: &mut dyn Iterator<Item = C>| {
+            zip(points, weights)
+                .fold(C::Curve::identity(), |acc, (point, weight)| {
+                    acc + point * *weight
+                })
+                .to_affine()
+        };
+
         // Fold instances
-        let instance = zip(acc0.instance.iter(), acc1.instance.iter())
-            .map(|(instance0, instance1)| {
-                zip(instance0.iter(), instance1.iter())
-                    .map(|(i0, i1)| (*i1 - *i0) * i1 + *i0)
+        let instance = (0..acc0.instance.len())
+            .map(|i| {
+                (0..acc0.instance[i].len())
+                    .map(|j| fold_scalars(&mut accs.iter().map(|acc| acc.instance[i][j])))
                     .collect()
             })
             .collect();
 
         // Fold all commitments
-        let advice = zip(acc0.advice.iter(), acc1.advice.iter())
-            .map(|(acc0, acc1)| ((*acc1 - *acc0) * alpha + *acc0).to_affine())
+        let instance_commitments = (0..acc0.instance_commitments.len())
+            .map(|i| fold_points(&mut accs.iter().map(|acc| acc.instance_commitments[i])))
+            .collect();
+        let advice = (0..acc0.advice.len())
+            .map(|i| fold_points(&mut accs.iter().map(|acc| acc.advice[i])))
             .collect();
 
         // Fold lookups
-        let lookup_accumulators = zip(
-            acc0.lookup_accumulators.iter(),
-            acc1.lookup_accumulators.iter(),
-        )
-        .map(|(acc0, acc1)| LookupAccumulator {
-            m: ((acc1.m - acc0.m) * alpha + acc0.m).to_affine(),
-            r: (acc1.r - acc0.r) * alpha + acc0.r,
-            thetas: zip(acc0.thetas.iter(), acc1.thetas.iter())
-                .map(|(theta0, theta1)| (*theta1 - *theta0) * alpha + *theta0)
-                .collect(),
-            g: ((acc1.g - acc0.g) * alpha + acc0.g).to_affine(),
-            h: ((acc1.h - acc0.h) * alpha + acc0.h).to_affine(),
-        })
-        .collect();
-
-        // Compute commitment to error vector for beta commitment correctness
-        let beta_error = {
-            let error0 = acc0.beta_error;
-            let error1 = acc1.beta_error;
-            let beta_com0 = acc0.beta_commitment;
-            let beta_com1 = acc1.beta_commitment;
-            let beta0 = acc0.beta;
-            let beta1 = acc1.beta;
-            let error_quotient = beta_com0 * (beta1 - beta0) + beta_com1 * (beta0 - beta1);
-            (error0 * (C::Scalar::ONE - alpha)
-                + error1 * C::Scalar::ONE
-                + error_quotient * ((C::Scalar::ONE - alpha) * alpha))
-                .to_affine()
-        };
+        let lookup_accumulators = (0..acc0.lookup_accumulators.len())
+            .map(|i| {
+                let lookups: Vec<_> = accs.iter().map(|acc| &acc.lookup_accumulators[i]).collect();
+                LookupAccumulator {
+                    m: fold_points(&mut lookups.iter().map(|lookup| lookup.m)),
+                    r: fold_scalars(&mut lookups.iter().map(|lookup| lookup.r)),
+                    thetas: (0..lookups[0].thetas.len())
+                        .map(|j| fold_scalars(&mut lookups.iter().map(|lookup| lookup.thetas[j])))
+                        .collect(),
+                    g: fold_points(&mut lookups.iter().map(|lookup| lookup.g)),
+                    h: (0..lookups[0].h.len())
+                        .map(|k| fold_points(&mut lookups.iter().map(|lookup| lookup.h[k])))
+                        .collect(),
+                }
+            })
+            .collect();
 
-        // fold beta challenge
-        let beta = (acc1.beta - acc0.beta) * alpha + acc0.beta;
+        // Fold shuffles
+        let shuffle_accumulators = (0..acc0.shuffle_accumulators.len())
+            .map(|i| {
+                let shuffles: Vec<_> = accs
+                    .iter()
+                    .map(|acc| &acc.shuffle_accumulators[i])
+                    .collect();
+                ShuffleAccumulator {
+                    r: fold_scalars(&mut shuffles.iter().map(|shuffle| shuffle.r)),
+                    thetas: (0..shuffles[0].thetas.len())
+                        .map(|j| {
+                            fold_scalars(&mut shuffles.iter().map(|shuffle| shuffle.thetas[j]))
+                        })
+                        .collect(),
+                    g: fold_points(&mut shuffles.iter().map(|shuffle| shuffle.g)),
+                    h: fold_points(&mut shuffles.iter().map(|shuffle| shuffle.h)),
+                }
+            })
+            .collect();
+
+        // Fold permutation
+        let permutation_accumulator = {
+            let permutations: Vec<_> = accs
+                .iter()
+                .map(|acc| &acc.permutation_accumulator)
+                .collect();
+            PermutationAccumulator {
+                r: fold_scalars(&mut permutations.iter().map(|permutation| permutation.r)),
+                theta: fold_scalars(&mut permutations.iter().map(|permutation| permutation.theta)),
+                g: (0..acc0.permutation_accumulator.g.len())
+                    .map(|i| {
+                        fold_points(&mut permutations.iter().map(|permutation| permutation.g[i]))
+                    })
+                    .collect(),
+                h: (0..acc0.permutation_accumulator.h.len())
+                    .map(|i| {
+                        fold_points(&mut permutations.iter().map(|permutation| permutation.h[i]))
+                    })
+                    .collect(),
+            }
+        };
 
-        let beta_commitment = ((acc1.beta_commitment - acc0.beta_commitment) * alpha
-            + acc0.beta_commitment)
-            .to_affine();
+        // Fold beta commitments and errors
+        let beta = BetaAccumulator::fold(weights, accs.iter().map(|acc| &acc.beta));
+        let beta_sqrt = BetaAccumulator::fold(weights, accs.iter().map(|acc| &acc.beta_sqrt));
 
-        // Fold all challenges
-        let challenges = zip(acc0.challenges.iter(), acc1.challenges.iter())
-            .map(|(acc0, acc1)| (*acc1 - *acc0) * alpha + *acc0)
+        // Fold all challenges, where each power is considered as an independent challenge
+        let challenges = (0..acc0.challenges.len())
+            .map(|i| {
+                (0..acc0.challenges[i].len())
+                    .map(|power| fold_scalars(&mut accs.iter().map(|acc| acc.challenges[i][power])))
+                    .collect()
+            })
             .collect();
 
         // fold ys challenges
-        let ys = zip(acc0.ys.iter(), acc1.ys.iter())
-            .map(|(acc0, acc1)| (*acc1 - *acc0) * alpha + *acc0)
+        let ys = (0..acc0.ys.len())
+            .map(|i| fold_scalars(&mut accs.iter().map(|acc| acc.ys[i])))
             .collect();
 
         Self {
             instance,
+            instance_commitments,
             advice,
             challenges,
             lookup_accumulators,
+            shuffle_accumulators,
+            permutation_accumulator,
             beta,
-            beta_commitment,
-            beta_error,
+            beta_sqrt,
             ys,
             error,
         }
     }
 }
 
+impl<C: SerdeCurveAffine> VerifierAccumulator<C>
+where
+    C::Scalar: SerdePrimeField,
+{
+    /// Writes a verifier accumulator to a buffer.
+    ///
+    /// Field and curve elements are written according to `format`,
+    /// as in [`crate::plonk::VerifyingKey::write`].
+    pub fn write<W: io::Write>(&self, writer: &mut W, format: SerdeFormat) -> io::Result<()> {
+        let write_scalars = |scalars: &[C::Scalar], writer: &mut W| {
+            write_slice(scalars, writer, |scalar, writer| {
+                scalar.write(writer, format)
+            })
+        };
+        let write_points = |points: &[C], writer: &mut W| {
+            write_slice(points, writer, |point, writer| point.write(writer, format))
+        };
+
+        write_slice(&self.instance, writer, |instance, writer| {
+            write_scalars(instance, writer)
+        })?;
+        write_points(&self.instance_commitments, writer)?;
+        write_points(&self.advice, writer)?;
+        write_slice(&self.challenges, writer, |powers, writer| {
+            write_scalars(powers, writer)
+        })?;
+        write_slice(&self.lookup_accumulators, writer, |lookup, writer| {
+            lookup.m.write(writer, format)?;
+            lookup.r.write(writer, format)?;
+            write_scalars(&lookup.thetas, writer)?;
+            lookup.g.write(writer, format)?;
+            write_points(&lookup.h, writer)
+        })?;
+        write_slice(&self.shuffle_accumulators, writer, |shuffle, writer| {
+            shuffle.r.write(writer, format)?;
+            write_scalars(&shuffle.thetas, writer)?;
+            shuffle.g.write(writer, format)?;
+            shuffle.h.write(writer, format)
+        })?;
+        let permutation = &self.permutation_accumulator;
+        permutation.r.write(writer, format)?;
+        permutation.theta.write(writer, format)?;
+        write_points(&permutation.g, writer)?;
+        write_points(&permutation.h, writer)?;
+        for beta in [&self.beta, &self.beta_sqrt] {
+            beta.challenge.write(writer, format)?;
+            beta.commitment.write(writer, format)?;
+            beta.error.write(writer, format)?;
+        }
+        write_scalars(&self.ys, writer)?;
+        self.error.write(writer, format)
+    }
+
+    /// Reads a verifier accumulator from a buffer.
+    ///
+    /// Field and curve elements are read according to `format`,
+    /// as in [`crate::plonk::VerifyingKey::read`].
+    /// Returns an error if the accumulator does not match the shape of `vk`.
+    pub fn read<R: io::Read>(
+        reader: &mut R,
+        format: SerdeFormat,
+        vk: &VerifyingKey<C>,
+    ) -> io::Result<Self> {
+        let read_scalars =
+            |reader: &mut R| read_vec(reader, |reader| C::Scalar::read(reader, format));
+        let read_points = |reader: &mut R| read_vec(reader, |reader| C::read(reader, format));
+        let read_beta = |reader: &mut R| -> io::Result<_> {
+            Ok(BetaAccumulator {
+                challenge: C::Scalar::read(reader, format)?,
+                commitment: C::read(reader, format)?,
+                error: C::read(reader, format)?,
+            })
+        };
+
+        let instance = read_vec(reader, read_scalars)?;
+        let instance_commitments = read_points(reader)?;
+        let advice = read_points(reader)?;
+        let challenges = read_vec(reader, read_scalars)?;
+        let lookup_accumulators = read_vec(reader, |reader| {
+            Ok(LookupAccumulator {
+                m: C::read(reader, format)?,
+                r: C::Scalar::read(reader, format)?,
+                thetas: read_scalars(reader)?,
+                g: C::read(reader, format)?,
+                h: read_points(reader)?,
+            })
+        })?;
+        let shuffle_accumulators = read_vec(reader, |reader| {
+            Ok(ShuffleAccumulator {
+                r: C::Scalar::read(reader, format)?,
+                thetas: read_scalars(reader)?,
+                g: C::read(reader, format)?,
+                h: C::read(reader, format)?,
+            })
+        })?;
+        let permutation_accumulator = PermutationAccumulator {
+            r: C::Scalar::read(reader, format)?,
+            theta: C::Scalar::read(reader, format)?,
+            g: read_points(reader)?,
+            h: read_points(reader)?,
+        };
+        let beta = read_beta(reader)?;
+        let beta_sqrt = read_beta(reader)?;
+        let ys = read_scalars(reader)?;
+        let error = C::Scalar::read(reader, format)?;
+
+        let acc = Self {
+            instance,
+            instance_commitments,
+            advice,
+            challenges,
+            lookup_accumulators,
+            shuffle_accumulators,
+            permutation_accumulator,
+            beta,
+            beta_sqrt,
+            ys,
+            error,
+        };
+        acc.check_shape(vk)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+        Ok(acc)
+    }
+
+    /// Writes a verifier accumulator to a vector of bytes using [`Self::write`].
+    pub fn to_bytes(&self, format: SerdeFormat) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write(&mut bytes, format)
+            .expect("Writing to vector should not fail");
+        bytes
+    }
+
+    /// Reads a verifier accumulator from a slice of bytes using [`Self::read`].
+    pub fn from_bytes(
+        mut bytes: &[u8],
+        format: SerdeFormat,
+        vk: &VerifyingKey<C>,
+    ) -> io::Result<Self> {
+        Self::read(&mut bytes, format, vk)
+    }
+}
+
+/// Returns the commitment to an instance column containing `instance`, padded with zeros,
+/// as expected by `VerifierAccumulator::new_with_instance_commitments`.
+pub fn commit_instance<'params, C: CurveAffine, P: Params<'params, C>>(
+    params: &P,
+    instance: &[C::Scalar],
+) -> C {
+    let mut column = empty_lagrange(params.n() as usize);
+    for (v, value) in zip(column.iter_mut(), instance.iter()) {
+        *v = *value;
+    }
+    params
+        .commit_lagrange(&column, Blind::default())
+        .to_affine()
+}
+
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use ff::{BatchInvert, FromUniformBytes, PrimeField, PrimeFieldBits};
 
     use crate::{
@@ -337,15 +1073,21 @@ mod tests {
             ipa::{
                 commitment::{IPACommitmentScheme, ParamsIPA},
                 multiopen::{ProverIPA, VerifierIPA},
+                strategy::SingleStrategy,
             },
             VerificationStrategy,
         },
         protostar,
-        protostar::accumulator::Accumulator,
-        protostar::verifier::{LookupAccumulator, VerifierAccumulator},
+        protostar::accumulator::{Accumulator, DecideError},
+        protostar::verifier::{
+            commit_instance, BetaAccumulator, LookupAccumulator, PermutationAccumulator,
+            ShuffleAccumulator, VerifierAccumulator,
+        },
+        protostar::{ColumnLabel, LookupChallenges},
         transcript::{
             Blake2bRead, Blake2bWrite, Challenge255, TranscriptReadBuffer, TranscriptWriterBuffer,
         },
+        SerdeFormat,
     };
 
     use halo2curves::pasta::{self, pallas, Fp};
@@ -626,10 +1368,16 @@ mod tests {
 
     #[derive(Debug, Clone)]
     /// A range-constrained value in the circuit produced by the RangeCheckConfig.
-    struct RangeConstrained<F: PrimeFieldBits, const RANGE: usize>(AssignedCell<Assigned<F>, F>);
+    pub(crate) struct RangeConstrained<F: PrimeFieldBits, const RANGE: usize>(
+        AssignedCell<Assigned<F>, F>,
+    );
 
     #[derive(Debug, Clone)]
-    struct RangeCheckConfig<F: PrimeFieldBits, const RANGE: usize, const LOOKUP_RANGE: usize> {
+    pub(crate) struct RangeCheckConfig<
+        F: PrimeFieldBits,
+        const RANGE: usize,
+        const LOOKUP_RANGE: usize,
+    > {
         q_range_check: Selector,
         q_lookup: Selector,
         value: Column<Advice>,
@@ -722,7 +1470,11 @@ mod tests {
         }
     }
     #[derive(Default)]
-    struct RangeCheckCircuit<F: PrimeFieldBits, const RANGE: usize, const LOOKUP_RANGE: usize> {
+    pub(crate) struct RangeCheckCircuit<
+        F: PrimeFieldBits,
+        const RANGE: usize,
+        const LOOKUP_RANGE: usize,
+    > {
         value: Value<Assigned<F>>,
         lookup_value: Value<Assigned<F>>,
     }
@@ -760,18 +1512,370 @@ mod tests {
         }
     }
 
-    fn check_v_and_p_transcripts<C: CurveAffine>(
-        v_acc: VerifierAccumulator<C>,
-        p_acc: Accumulator<C>,
-    ) {
-        for (col, instance) in v_acc.instance.iter().enumerate() {
-            for (row, v_instance_value) in instance.iter().enumerate() {
-                let p_instance_value = p_acc.gate.instance[col].values[row];
-                assert_eq!(
-                    *v_instance_value, p_instance_value,
-                    "V and P instance at col {col} and row {row} are NOT EQUAL"
-                )
-            }
+    #[derive(Clone, Debug)]
+    pub(crate) struct TwoLookupsConfig<F: PrimeFieldBits, const RANGE: usize> {
+        q_lookup: Selector,
+        a: Column<Advice>,
+        b: Column<Advice>,
+        table: RangeTableConfig<F, RANGE>,
+        // Copy of the range table for looking up b, if it is not shared with a
+        table_b: Option<RangeTableConfig<F, RANGE>>,
+    }
+
+    /// Looks up the values of two advice columns in a range table, using two lookup arguments.
+    /// If `SHARED_TABLE` is unset, each column is looked up in its own copy of the table.
+    #[derive(Default)]
+    pub(crate) struct TwoLookupsCircuit<
+        F: PrimeFieldBits,
+        const RANGE: usize,
+        const SHARED_TABLE: bool,
+    > {
+        values: Vec<(Value<F>, Value<F>)>,
+    }
+
+    impl<F: PrimeFieldBits, const RANGE: usize, const SHARED_TABLE: bool> Circuit<F>
+        for TwoLookupsCircuit<F, RANGE, SHARED_TABLE>
+    {
+        type Params = ();
+        type Config = TwoLookupsConfig<F, RANGE>;
+        type FloorPlanner = V1;
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                values: vec![(Value::unknown(), Value::unknown()); self.values.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let q_lookup = meta.complex_selector();
+            let a = meta.advice_column();
+            let b = meta.advice_column();
+            let table = RangeTableConfig::configure(meta);
+            let table_b = (!SHARED_TABLE).then(|| RangeTableConfig::configure(meta));
+
+            meta.lookup("lookup a", |meta| {
+                let q_lookup = meta.query_selector(q_lookup);
+                let a = meta.query_advice(a, Rotation::cur());
+                vec![(q_lookup * a, table.value)]
+            });
+            meta.lookup("lookup b", |meta| {
+                let q_lookup = meta.query_selector(q_lookup);
+                let b = meta.query_advice(b, Rotation::cur());
+                vec![(q_lookup * b, table_b.as_ref().unwrap_or(&table).value)]
+            });
+
+            TwoLookupsConfig {
+                q_lookup,
+                a,
+                b,
+                table,
+                table_b,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            config.table.load(&mut layouter)?;
+            if let Some(table_b) = &config.table_b {
+                table_b.load(&mut layouter)?;
+            }
+
+            layouter.assign_region(
+                || "values",
+                |mut region| {
+                    for (offset, (a, b)) in self.values.iter().enumerate() {
+                        config.q_lookup.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", config.a, offset, || *a)?;
+                        region.assign_advice(|| "b", config.b, offset, || *b)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    pub(crate) struct MulChainConfig {
+        q_mul: Selector,
+        a: Column<Advice>,
+        b: Column<Advice>,
+        c: Column<Advice>,
+        instance: Column<Instance>,
+    }
+
+    /// Computes x⋅b₀⋅b₁⋅...⋅bₖ, where x and the result are public,
+    /// and the output of each multiplication is copied into the next one.
+    #[derive(Clone, Default)]
+    pub(crate) struct MulChainCircuit<F: Field> {
+        bs: Vec<Value<F>>,
+        // Assign a different value to the copied cell to break a copy constraint
+        tamper: bool,
+    }
+
+    impl<F: Field> Circuit<F> for MulChainCircuit<F> {
+        type Config = MulChainConfig;
+        type FloorPlanner = V1;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                bs: vec![Value::unknown(); self.bs.len()],
+                tamper: self.tamper,
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let q_mul = meta.selector();
+            let [a, b, c] = [(); 3].map(|_| meta.advice_column());
+            let instance = meta.instance_column();
+            for column in [a, b, c] {
+                meta.enable_equality(column);
+            }
+            meta.enable_equality(instance);
+
+            meta.create_gate("mul", |meta| {
+                let q_mul = meta.query_selector(q_mul);
+                let [a, b, c] = [a, b, c].map(|column| meta.query_advice(column, Rotation::cur()));
+                vec![q_mul * (a * b - c)]
+            });
+
+            MulChainConfig {
+                q_mul,
+                a,
+                b,
+                c,
+                instance,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let out = layouter.assign_region(
+                || "mul chain",
+                |mut region| {
+                    let mut prev: Option<AssignedCell<F, F>> = None;
+                    for (offset, b) in self.bs.iter().enumerate() {
+                        config.q_mul.enable(&mut region, offset)?;
+                        let a = match &prev {
+                            None => region.assign_advice_from_instance(
+                                || "x",
+                                config.instance,
+                                0,
+                                config.a,
+                                offset,
+                            )?,
+                            Some(prev) if self.tamper => {
+                                let a = region.assign_advice(
+                                    || "a",
+                                    config.a,
+                                    offset,
+                                    || prev.value().map(|v| *v + F::ONE),
+                                )?;
+                                region.constrain_equal(prev.cell(), a.cell())?;
+                                a
+                            }
+                            Some(prev) => {
+                                prev.copy_advice(|| "a", &mut region, config.a, offset)?
+                            }
+                        };
+                        region.assign_advice(|| "b", config.b, offset, || *b)?;
+                        let c = a.value().zip(*b).map(|(a, b)| *a * b);
+                        prev = Some(region.assign_advice(|| "c", config.c, offset, || c)?);
+                    }
+                    Ok(prev.unwrap())
+                },
+            )?;
+            layouter.constrain_instance(out.cell(), config.instance, 1)
+        }
+    }
+
+    /// Returns a `MulChainCircuit` computing x⋅b₀⋅b₁⋅...⋅bₖ, along with its public inputs x and x⋅b₀⋅b₁⋅...⋅bₖ.
+    fn mul_chain(
+        x: u64,
+        bs: &[u64],
+        tamper: bool,
+    ) -> (MulChainCircuit<pallas::Scalar>, Vec<pallas::Scalar>) {
+        let x = pallas::Scalar::from(x);
+        let bs: Vec<_> = bs.iter().map(|b| pallas::Scalar::from(*b)).collect();
+        let out = bs.iter().fold(x, |acc, b| acc * b);
+        let circuit = MulChainCircuit {
+            bs: bs.into_iter().map(Value::known).collect(),
+            tamper,
+        };
+        (circuit, vec![x, out])
+    }
+
+    #[derive(Clone, Debug)]
+    struct ShuffleArgConfig {
+        q_shuffle: Selector,
+        original: [Column<Advice>; 2],
+        shuffled: [Column<Advice>; 2],
+    }
+
+    /// Checks that the rows of `shuffled` are a permutation of the rows of `original`,
+    /// using the shuffle argument of the constraint system.
+    #[derive(Clone, Default)]
+    struct ShuffleArgCircuit<F: Field> {
+        original: Vec<[Value<F>; 2]>,
+        shuffled: Vec<[Value<F>; 2]>,
+    }
+
+    impl<F: Field> Circuit<F> for ShuffleArgCircuit<F> {
+        type Config = ShuffleArgConfig;
+        type FloorPlanner = V1;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                original: vec![[Value::unknown(); 2]; self.original.len()],
+                shuffled: vec![[Value::unknown(); 2]; self.shuffled.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let q_shuffle = meta.complex_selector();
+            let original = [(); 2].map(|_| meta.advice_column());
+            let shuffled = [(); 2].map(|_| meta.advice_column());
+
+            meta.shuffle("shuffle", |meta| {
+                let q_shuffle = meta.query_selector(q_shuffle);
+                zip(original, shuffled)
+                    .map(|(original, shuffled)| {
+                        (
+                            q_shuffle.clone() * meta.query_advice(original, Rotation::cur()),
+                            q_shuffle.clone() * meta.query_advice(shuffled, Rotation::cur()),
+                        )
+                    })
+                    .collect()
+            });
+
+            ShuffleArgConfig {
+                q_shuffle,
+                original,
+                shuffled,
+            }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            layouter.assign_region(
+                || "shuffle",
+                |mut region| {
+                    for (offset, (original, shuffled)) in
+                        zip(self.original.iter(), self.shuffled.iter()).enumerate()
+                    {
+                        config.q_shuffle.enable(&mut region, offset)?;
+                        for (column, value) in zip(config.original, original) {
+                            region.assign_advice(|| "original", column, offset, || *value)?;
+                        }
+                        for (column, value) in zip(config.shuffled, shuffled) {
+                            region.assign_advice(|| "shuffled", column, offset, || *value)?;
+                        }
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct ChallengePowerConfig {
+        q: Selector,
+        a: Column<Advice>,
+        b: Column<Advice>,
+        theta: Challenge,
+    }
+
+    /// Computes bᵢ = aᵢ⋅θ³ + θ² in a second phase, where θ is a challenge sampled after the aᵢ.
+    #[derive(Clone, Default)]
+    struct ChallengePowerCircuit<F: Field> {
+        a: Vec<Value<F>>,
+    }
+
+    impl<F: Field> Circuit<F> for ChallengePowerCircuit<F> {
+        type Config = ChallengePowerConfig;
+        type FloorPlanner = V1;
+        #[cfg(feature = "circuit-params")]
+        type Params = ();
+
+        fn without_witnesses(&self) -> Self {
+            Self {
+                a: vec![Value::unknown(); self.a.len()],
+            }
+        }
+
+        fn configure(meta: &mut ConstraintSystem<F>) -> Self::Config {
+            let q = meta.selector();
+            let a = meta.advice_column_in(FirstPhase);
+            let theta = meta.challenge_usable_after(FirstPhase);
+            let b = meta.advice_column_in(SecondPhase);
+
+            meta.create_gate("b = a⋅θ³ + θ²", |meta| {
+                let q = meta.query_selector(q);
+                let [a, b] = [a, b].map(|column| meta.query_advice(column, Rotation::cur()));
+                let theta = meta.query_challenge(theta);
+                vec![
+                    q * (a * theta.clone() * theta.clone() * theta.clone() + theta.clone() * theta
+                        - b),
+                ]
+            });
+
+            ChallengePowerConfig { q, a, b, theta }
+        }
+
+        fn synthesize(
+            &self,
+            config: Self::Config,
+            mut layouter: impl Layouter<F>,
+        ) -> Result<(), Error> {
+            let theta = layouter.get_challenge(config.theta);
+            layouter.assign_region(
+                || "challenge powers",
+                |mut region| {
+                    for (offset, a) in self.a.iter().enumerate() {
+                        config.q.enable(&mut region, offset)?;
+                        region.assign_advice(|| "a", config.a, offset, || *a)?;
+                        let b = a.zip(theta).map(|(a, theta)| {
+                            let theta_sq = theta.square();
+                            a * theta_sq * theta + theta_sq
+                        });
+                        region.assign_advice(|| "b", config.b, offset, || b)?;
+                    }
+                    Ok(())
+                },
+            )
+        }
+    }
+
+    pub(crate) fn check_v_and_p_transcripts<C: CurveAffine>(
+        v_acc: VerifierAccumulator<C>,
+        p_acc: Accumulator<C>,
+    ) {
+        for (col, instance) in v_acc.instance.iter().enumerate() {
+            for (row, v_instance_value) in instance.iter().enumerate() {
+                let p_instance_value = p_acc.gate.instance[col].values[row];
+                assert_eq!(
+                    *v_instance_value, p_instance_value,
+                    "V and P instance at col {col} and row {row} are NOT EQUAL"
+                )
+            }
+        }
+        for (col, v_commitment) in v_acc.instance_commitments.iter().enumerate() {
+            assert_eq!(
+                *v_commitment, p_acc.gate.instance[col].commitment,
+                "V and P instance commitments at col {col} are NOT EQUAL"
+            );
         }
         assert_eq!(
             p_acc
@@ -796,27 +1900,136 @@ mod tests {
                     r: v.r,
                     thetas: v.thetas.clone(),
                     g: v.g.commitment,
-                    h: v.h.commitment
+                    h: v.h.iter().map(|c| c.commitment).collect(),
                 })
                 .collect::<Vec<LookupAccumulator<C>>>(),
             v_acc.lookup_accumulators
         );
         assert_eq!(
-            p_acc.beta.beta.values[1], v_acc.beta,
-            "V and P Beta challenge NOT EQUAL"
+            PermutationAccumulator {
+                r: p_acc.permutation.r,
+                theta: p_acc.permutation.theta,
+                g: p_acc.permutation.g.iter().map(|c| c.commitment).collect(),
+                h: p_acc.permutation.h.iter().map(|c| c.commitment).collect(),
+            },
+            v_acc.permutation_accumulator,
+            "V and P Permutation Transcripts NOT EQUAL"
+        );
+        assert_eq!(
+            p_acc
+                .shuffles
+                .iter()
+                .map(|v| ShuffleAccumulator {
+                    r: v.r,
+                    thetas: v.thetas.clone(),
+                    g: v.g.commitment,
+                    h: v.h.commitment
+                })
+                .collect::<Vec<ShuffleAccumulator<C>>>(),
+            v_acc.shuffle_accumulators,
+            "V and P Shuffle Transcripts NOT EQUAL"
         );
         assert_eq!(
-            p_acc.beta.error.commitment, v_acc.beta_error,
-            "V and P Beta error NOT EQUAL"
+            BetaAccumulator::from(&p_acc.beta.beta),
+            v_acc.beta,
+            "V and P Beta NOT EQUAL"
         );
         assert_eq!(
-            p_acc.beta.beta.commitment, v_acc.beta_commitment,
-            "V and P Beta Commitment NOT EQUAL"
+            BetaAccumulator::from(&p_acc.beta.beta_sqrt),
+            v_acc.beta_sqrt,
+            "V and P Beta sqrt NOT EQUAL"
         );
         assert_eq!(p_acc.ys, v_acc.ys, "V and P Y challenge NOT EQUAL");
         assert_eq!(p_acc.error, v_acc.error, "V and P Error NOT EQUAL");
     }
 
+    /// Proving key of a circuit along with accumulators for `N` of its witnesses.
+    pub(crate) struct Fixture<const N: usize> {
+        pub params: ParamsIPA<pallas::Affine>,
+        pub pk: protostar::ProvingKey<pallas::Affine>,
+        pub accs: [Accumulator<pallas::Affine>; N],
+        // Transcript to which all accumulators were written, in order
+        pub transcript: Blake2bWrite<Vec<u8>, pallas::Affine, Challenge255<pallas::Affine>>,
+    }
+
+    /// Creates parameters of size 2ᵏ and the proving key of the first of `circuits`,
+    /// whose lookup challenges are sampled according to `lookup_challenges`,
+    /// and an accumulator for each of `circuits`.
+    pub(crate) fn fixture<ConcreteCircuit: Circuit<pallas::Scalar>, const N: usize>(
+        k: u32,
+        circuits: [&ConcreteCircuit; N],
+        lookup_challenges: LookupChallenges,
+    ) -> Fixture<N> {
+        let params = ParamsIPA::<pallas::Affine>::new(k);
+        let pk =
+            protostar::ProvingKey::new_with_options(&params, circuits[0], false, lookup_challenges)
+                .unwrap();
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let accs = circuits.map(|circuit| {
+            protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                circuit,
+                &[],
+                OsRng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        Fixture {
+            params,
+            pk,
+            accs,
+            transcript,
+        }
+    }
+
+    /// Checks that the verifier reading `proof` obtains the same accumulators as the prover,
+    /// where `accs` are two accumulators followed by their fold.
+    pub(crate) fn check_verifier_fold(
+        vk: &protostar::VerifyingKey<pallas::Affine>,
+        proof: &[u8],
+        accs: [Accumulator<pallas::Affine>; 3],
+    ) {
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(proof);
+        let v_acc0 = VerifierAccumulator::new(vk, &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(vk, &[], &mut v_transcript).unwrap();
+        let v_acc2 =
+            VerifierAccumulator::fold(vk, v_acc0.clone(), v_acc1.clone(), &mut v_transcript)
+                .unwrap();
+        for (v_acc, acc) in zip([v_acc0, v_acc1, v_acc2], accs) {
+            check_v_and_p_transcripts(v_acc, acc);
+        }
+    }
+
+    pub(crate) type RangeCheck = RangeCheckCircuit<pallas::Scalar, 8, 256>;
+
+    /// Circuits checking that 4 and 5 are 3-bit values, and looking up 12 and 220 in a table of 8-bit values.
+    pub(crate) fn range_check_circuits() -> [RangeCheck; 2] {
+        [(4, 12), (5, 220)].map(|(value, lookup_value)| RangeCheck {
+            value: Value::known(pallas::Scalar::from(value).into()),
+            lookup_value: Value::known(pallas::Scalar::from(lookup_value).into()),
+        })
+    }
+
+    /// Circuit looking up each pair of `values` in a table of 4-bit values,
+    /// which is shared by both lookups if `SHARED_TABLE` is set.
+    pub(crate) fn two_lookups_circuit<const SHARED_TABLE: bool>(
+        values: [(u64, u64); 3],
+    ) -> TwoLookupsCircuit<pallas::Scalar, 16, SHARED_TABLE> {
+        TwoLookupsCircuit {
+            values: values
+                .iter()
+                .map(|(a, b)| {
+                    (
+                        Value::known(pallas::Scalar::from(*a)),
+                        Value::known(pallas::Scalar::from(*b)),
+                    )
+                })
+                .collect(),
+        }
+    }
+
     #[test]
     fn test_one_verifier_acc() {
         let mut rng: OsRng = OsRng;
@@ -845,7 +2058,7 @@ mod tests {
         let proof: Vec<u8> = transcript.finalize();
 
         let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-        let v_acc = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
+        let v_acc = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
 
         check_v_and_p_transcripts(v_acc, p_acc);
     }
@@ -875,14 +2088,15 @@ mod tests {
         )
         .unwrap();
 
-        let p_acc1 = Accumulator::fold(&pk, p_acc.clone(), p_acc.clone(), &mut transcript);
+        let p_acc1 = Accumulator::fold(&pk, p_acc.clone(), p_acc.clone(), &mut transcript).unwrap();
 
         let proof: Vec<u8> = transcript.finalize();
 
         let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-        let v_acc = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
+        let v_acc = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
         let v_acc1 =
-            VerifierAccumulator::fold(&pk, v_acc.clone(), v_acc.clone(), &mut v_transcript);
+            VerifierAccumulator::fold(pk.get_vk(), v_acc.clone(), v_acc.clone(), &mut v_transcript)
+                .unwrap();
 
         check_v_and_p_transcripts(v_acc1, p_acc1);
     }
@@ -925,8 +2139,8 @@ mod tests {
         let proof: Vec<u8> = transcript.finalize();
 
         let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
-        let v_acc0 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
-        let v_acc1 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
+        let v_acc0 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
 
         // Check acc0 and v_acc0 transcripts
         check_v_and_p_transcripts(v_acc0, acc0);
@@ -969,15 +2183,20 @@ mod tests {
         )
         .unwrap();
 
-        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript);
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
 
         let proof: Vec<u8> = transcript.finalize();
         let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
 
-        let v_acc0 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
-        let v_acc1 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
-        let v_acc2 =
-            VerifierAccumulator::fold(&pk, v_acc0.clone(), v_acc1.clone(), &mut v_transcript);
+        let v_acc0 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
 
         check_v_and_p_transcripts(v_acc0, acc0);
         check_v_and_p_transcripts(v_acc1, acc1);
@@ -1031,18 +2250,1237 @@ mod tests {
         )
         .unwrap();
 
-        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript);
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
 
         let proof: Vec<u8> = transcript.finalize();
         let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
 
-        let v_acc0 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
-        let v_acc1 = VerifierAccumulator::new(&pk, &[], &mut v_transcript).unwrap();
-        let v_acc2 =
-            VerifierAccumulator::fold(&pk, v_acc0.clone(), v_acc1.clone(), &mut v_transcript);
+        let v_acc0 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
 
         check_v_and_p_transcripts(v_acc0, acc0);
         check_v_and_p_transcripts(v_acc1, acc1);
         check_v_and_p_transcripts(v_acc2, acc2);
     }
+
+    #[test]
+    fn test_permutation() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit0, instance0) = mul_chain(2, &[3, 4, 5], false);
+        let (circuit1, instance1) = mul_chain(7, &[11, 13, 17], false);
+
+        MockProver::run(K, &circuit0, vec![instance0.clone()])
+            .unwrap()
+            .assert_satisfied();
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc0 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[&instance0],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc1 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit1,
+            &[&instance1],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+
+        assert!(Accumulator::decide(&params, &pk, &acc0));
+        assert!(Accumulator::decide(&params, &pk, &acc1));
+
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc2));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        let v_acc0 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance0], &mut v_transcript).unwrap();
+        let v_acc1 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance1], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
+
+        check_v_and_p_transcripts(v_acc0, acc0);
+        check_v_and_p_transcripts(v_acc1, acc1);
+        check_v_and_p_transcripts(v_acc2, acc2);
+
+        // Breaking a copy constraint must be detected by the decider
+        let (circuit_bad, instance_bad) = mul_chain(2, &[3, 4, 5], true);
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc_bad = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit_bad,
+            &[&instance_bad],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        assert!(!Accumulator::decide(&params, &pk, &acc_bad));
+    }
+
+    #[test]
+    fn test_query_instance() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit0, instance0) = mul_chain(2, &[3, 4, 5], false);
+        let (circuit1, instance1) = mul_chain(7, &[11, 13, 17], false);
+
+        let pk = protostar::ProvingKey::new_with_query_instance(&params, &circuit0, true).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc0 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[&instance0],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc1 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit1,
+            &[&instance1],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+
+        assert!(Accumulator::decide(&params, &pk, &acc0));
+        assert!(Accumulator::decide(&params, &pk, &acc1));
+
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc2));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        // Raw instance values are rejected when the key expects commitments
+        assert!(VerifierAccumulator::new(pk.get_vk(), &[&instance0], &mut v_transcript).is_err());
+
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let [commitment0, commitment1] =
+            [&instance0, &instance1].map(|instance| commit_instance(&params, instance));
+        let v_acc0 = VerifierAccumulator::new_with_instance_commitments(
+            pk.get_vk(),
+            &[commitment0],
+            &mut v_transcript,
+        )
+        .unwrap();
+        let v_acc1 = VerifierAccumulator::new_with_instance_commitments(
+            pk.get_vk(),
+            &[commitment1],
+            &mut v_transcript,
+        )
+        .unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
+
+        // Only the commitments to the instance columns are folded
+        assert!(v_acc2.instance.is_empty());
+        assert!(check_decider(&params, &pk, &acc2, &v_acc2));
+
+        check_v_and_p_transcripts(v_acc0, acc0);
+        check_v_and_p_transcripts(v_acc1, acc1);
+        check_v_and_p_transcripts(v_acc2, acc2);
+
+        // A commitment to different public inputs leads to different challenges
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[&instance0],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc_bad = VerifierAccumulator::new_with_instance_commitments(
+            pk.get_vk(),
+            &[commitment1],
+            &mut v_transcript,
+        )
+        .unwrap();
+        assert_ne!(v_acc_bad.ys, acc.ys);
+    }
+
+    #[test]
+    fn test_shuffle() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+        const N: u64 = 16;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        // Rows (i, i²) shuffled by a fixed permutation i ↦ 5⋅i + offset mod N
+        let shuffle_circuit = |offset: u64, tamper: bool| {
+            let row = |i: u64| {
+                let i = pallas::Scalar::from(i % N);
+                [Value::known(i), Value::known(i.square())]
+            };
+            let original: Vec<_> = (0..N).map(row).collect();
+            let mut shuffled: Vec<_> = (0..N).map(|i| row(5 * i + offset)).collect();
+            if tamper {
+                shuffled[0][1] = shuffled[0][1].map(|v| v + pallas::Scalar::ONE);
+            }
+            ShuffleArgCircuit { original, shuffled }
+        };
+
+        let circuit0 = shuffle_circuit(3, false);
+        let circuit1 = shuffle_circuit(7, false);
+
+        MockProver::run(K, &circuit0, vec![])
+            .unwrap()
+            .assert_satisfied();
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc0 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc1 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit1,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+
+        assert!(Accumulator::decide(&params, &pk, &acc0));
+        assert!(Accumulator::decide(&params, &pk, &acc1));
+
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc2));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        let v_acc0 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
+
+        check_v_and_p_transcripts(v_acc0, acc0);
+        check_v_and_p_transcripts(v_acc1, acc1);
+        check_v_and_p_transcripts(v_acc2, acc2);
+
+        // Shuffling a row that is not part of the original must be detected by the decider
+        let circuit_bad = shuffle_circuit(3, true);
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc_bad = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit_bad,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        assert!(!Accumulator::decide(&params, &pk, &acc_bad));
+    }
+
+    #[test]
+    fn test_challenge_powers() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+        const N: usize = 16;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let challenge_power_circuit = |rng: &mut OsRng| ChallengePowerCircuit {
+            a: (0..N)
+                .map(|_| Value::known(pallas::Scalar::random(&mut *rng)))
+                .collect(),
+        };
+        let circuit0 = challenge_power_circuit(&mut rng);
+        let circuit1 = challenge_power_circuit(&mut rng);
+
+        MockProver::run(K, &circuit0, vec![])
+            .unwrap()
+            .assert_satisfied();
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        // θ² and θ³ are substituted, so the gate a⋅θ³ + θ² − b has degree 2
+        assert_eq!(pk.max_challenge_powers(), &[3]);
+        assert_eq!(pk.max_folding_constraints_degree(), 2 + 3);
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc0 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc1 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit1,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+
+        assert!(Accumulator::decide(&params, &pk, &acc0));
+        assert!(Accumulator::decide(&params, &pk, &acc1));
+
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc2));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        let v_acc0 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc1 = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
+
+        assert!(check_decider(&params, &pk, &acc2, &v_acc2));
+
+        check_v_and_p_transcripts(v_acc0, acc0);
+        check_v_and_p_transcripts(v_acc1, acc1);
+        check_v_and_p_transcripts(v_acc2, acc2);
+    }
+
+    fn check_decider<C: CurveAffine>(
+        params: &ParamsIPA<C>,
+        pk: &protostar::ProvingKey<C>,
+        acc: &Accumulator<C>,
+        v_acc: &VerifierAccumulator<C>,
+    ) -> bool
+    where
+        C::Scalar: FromUniformBytes<64>,
+    {
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        protostar::decider::create_proof::<IPACommitmentScheme<C>, ProverIPA<C>, _, _, _>(
+            params,
+            pk,
+            acc,
+            OsRng,
+            &mut transcript,
+        )
+        .unwrap();
+        let proof = transcript.finalize();
+
+        let mut transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        protostar::decider::verify_proof::<IPACommitmentScheme<C>, VerifierIPA<C>, _, _, _>(
+            params.verifier_params(),
+            pk.get_vk(),
+            v_acc,
+            SingleStrategy::new(params),
+            &mut transcript,
+        )
+        .is_ok()
+    }
+
+    #[test]
+    fn test_decider() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit0, instance0) = mul_chain(2, &[3, 4, 5], false);
+        let (circuit1, instance1) = mul_chain(7, &[11, 13, 17], false);
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc0 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit0,
+            &[&instance0],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc1 = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit1,
+            &[&instance1],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let acc2 = Accumulator::fold(&pk, acc0.clone(), acc1.clone(), &mut transcript).unwrap();
+        // Folding two folded accumulators exercises the interpolation of non-trivial beta errors
+        let acc3 = Accumulator::fold(&pk, acc1, acc0.clone(), &mut transcript).unwrap();
+        let acc4 = Accumulator::fold(&pk, acc2.clone(), acc3, &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc4));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        let v_acc0 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance0], &mut v_transcript).unwrap();
+        let v_acc1 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance1], &mut v_transcript).unwrap();
+        let v_acc2 = VerifierAccumulator::fold(
+            pk.get_vk(),
+            v_acc0.clone(),
+            v_acc1.clone(),
+            &mut v_transcript,
+        )
+        .unwrap();
+        let v_acc3 =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc1, v_acc0.clone(), &mut v_transcript)
+                .unwrap();
+        let v_acc4 =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc2.clone(), v_acc3, &mut v_transcript)
+                .unwrap();
+        check_v_and_p_transcripts(v_acc4.clone(), acc4.clone());
+
+        assert!(check_decider(&params, &pk, &acc0, &v_acc0));
+        assert!(check_decider(&params, &pk, &acc2, &v_acc2));
+        assert!(check_decider(&params, &pk, &acc4, &v_acc4));
+
+        // The proof must not verify against a different accumulator
+        let mut v_acc_bad = v_acc2.clone();
+        v_acc_bad.error += pallas::Scalar::ONE;
+        assert!(!check_decider(&params, &pk, &acc2, &v_acc_bad));
+
+        let mut v_acc_bad = v_acc2.clone();
+        v_acc_bad.instance[0][0] += pallas::Scalar::ONE;
+        assert!(!check_decider(&params, &pk, &acc2, &v_acc_bad));
+
+        // A malformed accumulator is rejected instead of indexing missing columns
+        let mut v_acc_bad = v_acc2;
+        v_acc_bad.advice.pop();
+        assert!(!check_decider(&params, &pk, &acc2, &v_acc_bad));
+    }
+
+    #[test]
+    fn test_fold_many() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuits = [
+            mul_chain(2, &[3, 4, 5], false),
+            mul_chain(7, &[11, 13, 17], false),
+            mul_chain(19, &[23, 29, 31], false),
+        ];
+
+        let pk = protostar::ProvingKey::new(&params, &circuits[0].0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let accs: [_; 3] = std::array::from_fn(|k| {
+            let (circuit, instance) = &circuits[k];
+            protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                circuit,
+                &[instance],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        let acc3 = Accumulator::fold_many(&pk, accs.clone(), &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc3));
+
+        // Folding a folded accumulator exercises the interpolation of non-trivial errors
+        let [acc0, acc1, acc2] = accs;
+        let acc4 =
+            Accumulator::fold_many(&pk, [acc3.clone(), acc0, acc1, acc2], &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc4));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+
+        let v_accs: [_; 3] = std::array::from_fn(|k| {
+            let (_, instance) = &circuits[k];
+            VerifierAccumulator::new(pk.get_vk(), &[instance], &mut v_transcript).unwrap()
+        });
+        let v_acc3 =
+            VerifierAccumulator::fold_many(pk.get_vk(), v_accs.clone(), &mut v_transcript).unwrap();
+        let [v_acc0, v_acc1, v_acc2] = v_accs;
+        let v_acc4 = VerifierAccumulator::fold_many(
+            pk.get_vk(),
+            [v_acc3.clone(), v_acc0, v_acc1, v_acc2],
+            &mut v_transcript,
+        )
+        .unwrap();
+
+        assert!(check_decider(&params, &pk, &acc3, &v_acc3));
+        assert!(check_decider(&params, &pk, &acc4, &v_acc4));
+
+        check_v_and_p_transcripts(v_acc3, acc3);
+        check_v_and_p_transcripts(v_acc4, acc4);
+    }
+
+    #[test]
+    fn test_decide_detailed() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 9;
+        const RANGE: usize = 8; // 3-bit value
+        const LOOKUP_RANGE: usize = 256; // 8-bit value
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuit0 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(4).into()),
+            lookup_value: Value::known(pallas::Scalar::from(12).into()),
+        };
+        let circuit1 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(5).into()),
+            lookup_value: Value::known(pallas::Scalar::from(220).into()),
+        };
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] = [circuit0, circuit1].map(|circuit| {
+            protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                &circuit,
+                &[],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+        assert_eq!(Accumulator::decide_detailed(&params, &pk, &acc), Ok(()));
+
+        // The contributions of all gates and arguments add up to the recomputed error
+        let mut acc_bad = acc.clone();
+        acc_bad.error += pallas::Scalar::ONE;
+        let errors = Accumulator::decide_detailed(&params, &pk, &acc_bad).unwrap_err();
+        match &errors[..] {
+            [DecideError::Error {
+                expected,
+                computed,
+                gates,
+                lookups,
+                shuffles,
+                permutation,
+            }] => {
+                assert_eq!(*expected, acc_bad.error);
+                assert_eq!(*computed, acc.error);
+                assert_eq!(gates[0].0, "range check");
+                assert_eq!(lookups[0].0, "lookup");
+                let total: pallas::Scalar = gates
+                    .iter()
+                    .chain(lookups.iter())
+                    .chain(shuffles.iter())
+                    .map(|(_, contribution)| *contribution)
+                    .sum::<pallas::Scalar>()
+                    + permutation;
+                assert_eq!(total, acc.error);
+            }
+            _ => panic!("unexpected errors {:?}", errors),
+        }
+
+        // Tampering with a lookup column breaks its commitment and its linear constraint
+        let mut acc_bad = acc.clone();
+        acc_bad.lookups[0].h[0].values[0] += pallas::Scalar::ONE;
+        let errors = Accumulator::decide_detailed(&params, &pk, &acc_bad).unwrap_err();
+        assert!(errors.contains(&DecideError::Commitment {
+            column: ColumnLabel::LookupH(0, 0)
+        }));
+        assert!(errors.contains(&DecideError::Lookup {
+            index: 0,
+            name: "lookup".to_string()
+        }));
+
+        // Tampering with β₁ breaks the relations at rows 0 and 1
+        let mut acc_bad = acc;
+        acc_bad.beta.beta.powers.values[1] += pallas::Scalar::ONE;
+        let errors = Accumulator::decide_detailed(&params, &pk, &acc_bad).unwrap_err();
+        assert!(errors.contains(&DecideError::Commitment {
+            column: ColumnLabel::Beta
+        }));
+        for row in [0, 1] {
+            assert!(errors.contains(&DecideError::Beta {
+                column: ColumnLabel::Beta,
+                row
+            }));
+        }
+    }
+
+    #[test]
+    fn test_fold_errors() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit, instance) = mul_chain(2, &[3, 4, 5], false);
+
+        let pk = protostar::ProvingKey::new(&params, &circuit).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit,
+            &[&instance],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+
+        // An error term which does not match the witness cannot be folded
+        let mut acc_bad = acc.clone();
+        acc_bad.error += pallas::Scalar::ONE;
+        assert!(matches!(
+            Accumulator::fold(&pk, acc.clone(), acc_bad, &mut transcript),
+            Err(Error::InconsistentAccumulatorError)
+        ));
+
+        // Accumulators must match the shape of the proving key
+        let mut acc_bad = acc.clone();
+        acc_bad.ys.pop();
+        assert!(matches!(
+            Accumulator::fold(&pk, acc.clone(), acc_bad, &mut transcript),
+            Err(Error::AccumulatorShapeMismatch)
+        ));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc = VerifierAccumulator::new(pk.get_vk(), &[&instance], &mut v_transcript).unwrap();
+
+        let mut v_acc_bad = v_acc.clone();
+        v_acc_bad.advice.pop();
+        assert!(matches!(
+            VerifierAccumulator::fold(pk.get_vk(), v_acc.clone(), v_acc_bad, &mut v_transcript),
+            Err(Error::AccumulatorShapeMismatch)
+        ));
+
+        // The proof does not contain the quotient of the error polynomial
+        assert!(matches!(
+            VerifierAccumulator::fold(pk.get_vk(), v_acc.clone(), v_acc, &mut v_transcript),
+            Err(Error::Transcript(_))
+        ));
+    }
+
+    #[test]
+    fn test_accumulator_serialization() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 9;
+        const RANGE: usize = 8; // 3-bit value
+        const LOOKUP_RANGE: usize = 256; // 8-bit value
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuit0 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(4).into()),
+            lookup_value: Value::known(pallas::Scalar::from(12).into()),
+        };
+        let circuit1 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(5).into()),
+            lookup_value: Value::known(pallas::Scalar::from(220).into()),
+        };
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+        // Columns of the proving key are read at every row, and are only committed to sparsely
+        assert!(pk.selectors.iter().all(|c| !c.values.is_sparse()));
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] = [&circuit0, &circuit1].map(|circuit| {
+            protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                circuit,
+                &[],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let [v_acc0, v_acc1] =
+            [(); 2].map(|_| VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript).unwrap());
+        let v_acc =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc0, v_acc1, &mut v_transcript).unwrap();
+
+        for format in [
+            SerdeFormat::Processed,
+            SerdeFormat::RawBytes,
+            SerdeFormat::RawBytesUnchecked,
+        ] {
+            let bytes = acc.to_bytes(format);
+            let acc_read = Accumulator::from_bytes(&bytes, format, &pk).unwrap();
+            assert_eq!(acc_read, acc);
+            assert!(Accumulator::decide(&params, &pk, &acc_read));
+
+            let v_bytes = v_acc.to_bytes(format);
+            let v_acc_read =
+                VerifierAccumulator::from_bytes(&v_bytes, format, pk.get_vk()).unwrap();
+            assert_eq!(v_acc_read, v_acc);
+            assert!(v_acc_read == acc_read);
+
+            // Truncated buffers cannot be read, unless the reads are unchecked
+            if !matches!(format, SerdeFormat::RawBytesUnchecked) {
+                assert!(Accumulator::from_bytes(&bytes[..bytes.len() - 1], format, &pk).is_err());
+                assert!(VerifierAccumulator::from_bytes(
+                    &v_bytes[..v_bytes.len() - 1],
+                    format,
+                    pk.get_vk()
+                )
+                .is_err());
+            }
+        }
+
+        // Accumulators are only read with the proving key of the circuit which produced them
+        let params_other = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K + 1);
+        let pk_other = protostar::ProvingKey::new(&params_other, &circuit0).unwrap();
+        let bytes = acc.to_bytes(SerdeFormat::Processed);
+        assert_eq!(
+            Accumulator::from_bytes(&bytes, SerdeFormat::Processed, &pk_other)
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+
+        let pk_other = protostar::ProvingKey::new(&params, &mul_chain(1, &[1], false).0).unwrap();
+        let v_bytes = v_acc.to_bytes(SerdeFormat::Processed);
+        assert_eq!(
+            VerifierAccumulator::from_bytes(&v_bytes, SerdeFormat::Processed, pk_other.get_vk())
+                .unwrap_err()
+                .kind(),
+            std::io::ErrorKind::InvalidData
+        );
+    }
+
+    #[test]
+    fn test_decide_batched() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 9;
+        const RANGE: usize = 8; // 3-bit value
+        const LOOKUP_RANGE: usize = 256; // 8-bit value
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuit0 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(4).into()),
+            lookup_value: Value::known(pallas::Scalar::from(12).into()),
+        };
+        let circuit1 = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(5).into()),
+            lookup_value: Value::known(pallas::Scalar::from(220).into()),
+        };
+
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] = [circuit0, circuit1].map(|circuit| {
+            protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                &circuit,
+                &[],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+        assert!(Accumulator::decide_batched(&params, &pk, &acc, &mut rng));
+
+        // A single invalid commitment is detected
+        let mut acc_bad = acc.clone();
+        acc_bad.gate.advice[0].blind += pallas::Scalar::ONE;
+        assert!(!Accumulator::decide_batched(
+            &params, &pk, &acc_bad, &mut rng
+        ));
+
+        let mut acc_bad = acc.clone();
+        acc_bad.lookups[0].m.values[0] += pallas::Scalar::ONE;
+        assert!(!Accumulator::decide_batched(
+            &params, &pk, &acc_bad, &mut rng
+        ));
+
+        // Relations over the columns are checked as in `decide`
+        let mut acc_bad = acc;
+        acc_bad.error += pallas::Scalar::ONE;
+        assert!(!Accumulator::decide_batched(
+            &params, &pk, &acc_bad, &mut rng
+        ));
+    }
+
+    #[test]
+    fn test_hiding_fold() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuits = [
+            mul_chain(2, &[3, 4, 5], false),
+            mul_chain(7, &[11, 13, 17], false),
+        ];
+
+        for query_instance in [false, true] {
+            let pk = protostar::ProvingKey::new_with_query_instance(
+                &params,
+                &circuits[0].0,
+                query_instance,
+            )
+            .unwrap();
+
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            let [acc0, acc1] = [0, 1].map(|k| {
+                let (circuit, instance) = &circuits[k];
+                protostar::prover::create_hiding_accumulator(
+                    &params,
+                    &pk,
+                    circuit,
+                    &[instance],
+                    &mut rng,
+                    &mut transcript,
+                )
+                .unwrap()
+            });
+            assert!(Accumulator::decide(&params, &pk, &acc0));
+            assert!(Accumulator::decide(&params, &pk, &acc1));
+
+            let acc = Accumulator::fold(&pk, acc0.clone(), acc1, &mut transcript).unwrap();
+            assert!(Accumulator::decide(&params, &pk, &acc));
+
+            let proof: Vec<u8> = transcript.finalize();
+            let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+            let [v_acc0, v_acc1] = [0, 1].map(|k| {
+                let (_, instance) = &circuits[k];
+                let v_acc = if query_instance {
+                    let commitment = commit_instance(&params, instance);
+                    VerifierAccumulator::new_with_instance_commitments(
+                        pk.get_vk(),
+                        &[commitment],
+                        &mut v_transcript,
+                    )
+                } else {
+                    VerifierAccumulator::new(pk.get_vk(), &[instance], &mut v_transcript)
+                };
+                v_acc.unwrap().mask(pk.get_vk(), &mut v_transcript).unwrap()
+            });
+            assert!(v_acc0 == acc0);
+
+            let v_acc =
+                VerifierAccumulator::fold(pk.get_vk(), v_acc0, v_acc1, &mut v_transcript).unwrap();
+            assert!(check_decider(&params, &pk, &acc, &v_acc));
+            check_v_and_p_transcripts(v_acc, acc);
+        }
+
+        // Masking accumulators also satisfy lookup arguments
+        const RANGE: usize = 8; // 3-bit value
+        const LOOKUP_RANGE: usize = 256; // 8-bit value
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(9);
+        let circuit = RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(4).into()),
+            lookup_value: Value::known(pallas::Scalar::from(12).into()),
+        };
+        let pk = protostar::ProvingKey::new(&params, &circuit).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_hiding_accumulator(
+            &params,
+            &pk,
+            &circuit,
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc = VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript)
+            .unwrap()
+            .mask(pk.get_vk(), &mut v_transcript)
+            .unwrap();
+        check_v_and_p_transcripts(v_acc, acc);
+
+        // The random masking accumulator violates the linear gate q_first⋅(1 − z),
+        // which must still be accounted for when folding
+        const W: usize = 4;
+        const H: usize = 32;
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(8);
+        let circuits = [(); 2].map(|_| MyCircuit::<pallas::Scalar, W, H>::rand(&mut rng));
+        let pk = protostar::ProvingKey::new(&params, &circuits[0]).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] = [0, 1].map(|k| {
+            protostar::prover::create_hiding_accumulator(
+                &params,
+                &pk,
+                &circuits[k],
+                &[],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap()
+        });
+        // Linear constraints are only evaluated when folding masked accumulators
+        assert!(acc0.masked && acc1.masked);
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+        assert!(acc.masked);
+        assert!(Accumulator::decide(&params, &pk, &acc));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let [v_acc0, v_acc1] = [(); 2].map(|_| {
+            VerifierAccumulator::new(pk.get_vk(), &[], &mut v_transcript)
+                .unwrap()
+                .mask(pk.get_vk(), &mut v_transcript)
+                .unwrap()
+        });
+        let v_acc =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc0, v_acc1, &mut v_transcript).unwrap();
+        check_v_and_p_transcripts(v_acc, acc);
+    }
+
+    #[test]
+    fn test_all_rows_usable() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 4;
+        const N: usize = 1 << K;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        // The multiplication chain occupies every row of the trace
+        let (circuit, instance) = mul_chain(2, &[3; N], false);
+        let pk = protostar::ProvingKey::new(&params, &circuit).unwrap();
+        assert_eq!(pk.num_rows, N);
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit,
+            &[&instance],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc = VerifierAccumulator::new(pk.get_vk(), &[&instance], &mut v_transcript).unwrap();
+        check_v_and_p_transcripts(v_acc.clone(), acc.clone());
+        assert!(check_decider(&params, &pk, &acc, &v_acc));
+
+        // The error is recomputed over all rows, so a violated gate in the last row is detected
+        let mut acc_bad = acc.clone();
+        acc_bad.gate.advice[1].values[N - 1] += pallas::Scalar::ONE;
+        assert_ne!(Accumulator::error(&pk, &acc_bad), acc_bad.error);
+        assert!(matches!(
+            Accumulator::decide_detailed(&params, &pk, &acc_bad),
+            Err(errors) if errors.iter().any(|e| matches!(e, DecideError::Error { .. }))
+        ));
+
+        // Instance columns may fill the trace, but not exceed it
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let long_instance = vec![pallas::Scalar::ONE; N + 1];
+        let result = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit,
+            &[&long_instance],
+            &mut rng,
+            &mut transcript,
+        );
+        assert!(matches!(result, Err(Error::InstanceTooLarge)));
+
+        // A chain longer than the trace cannot be assigned
+        let (circuit, _) = mul_chain(2, &[3; N + 1], false);
+        assert!(matches!(
+            protostar::ProvingKey::new(&params, &circuit),
+            Err(Error::NotEnoughRowsAvailable { .. })
+        ));
+    }
+
+    #[test]
+    fn test_lookup_missing_input() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 9;
+        const RANGE: usize = 8; // 3-bit value
+        const LOOKUP_RANGE: usize = 256; // 8-bit value
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let circuit = |lookup_value: u64| RangeCheckCircuit::<pallas::Scalar, RANGE, LOOKUP_RANGE> {
+            value: Value::known(pallas::Scalar::from(4).into()),
+            lookup_value: Value::known(pallas::Scalar::from(lookup_value).into()),
+        };
+        let pk = protostar::ProvingKey::new(&params, &circuit(12)).unwrap();
+
+        // The largest value of the table can be looked up
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit(LOOKUP_RANGE as u64 - 1),
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc));
+
+        // A value outside of the table is reported rather than skipped
+        let mut pk = pk;
+        assert!(pk.strict_lookups());
+        let missing = pallas::Scalar::from(LOOKUP_RANGE as u64);
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let result = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit(LOOKUP_RANGE as u64),
+            &[],
+            &mut rng,
+            &mut transcript,
+        );
+        match result {
+            Err(Error::LookupInputNotInTable { name, row, input }) => {
+                assert_eq!(name, "lookup");
+                assert_eq!(row, 0);
+                assert_eq!(input, vec![format!("{:?}", missing)]);
+            }
+            _ => panic!("expected a missing lookup input"),
+        }
+
+        // Without strict lookups, the accumulator is created but rejected by the decider
+        pk.set_strict_lookups(false);
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let acc = protostar::prover::create_accumulator(
+            &params,
+            &pk,
+            &circuit(LOOKUP_RANGE as u64),
+            &[],
+            &mut rng,
+            &mut transcript,
+        )
+        .unwrap();
+        let errors = Accumulator::decide_detailed(&params, &pk, &acc).unwrap_err();
+        assert!(errors
+            .iter()
+            .any(|err| matches!(err, DecideError::Lookup { index: 0, .. })));
+    }
+
+    #[test]
+    fn test_instance_fold() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit0, instance0) = mul_chain(2, &[3, 4, 5], false);
+        let (circuit1, instance1) = mul_chain(7, &[11, 13, 17], false);
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] =
+            [(&circuit0, &instance0), (&circuit1, &instance1)].map(|(circuit, instance)| {
+                protostar::prover::create_accumulator(
+                    &params,
+                    &pk,
+                    circuit,
+                    &[instance],
+                    &mut rng,
+                    &mut transcript,
+                )
+                .unwrap()
+            });
+        let acc = Accumulator::fold(&pk, acc0, acc1, &mut transcript).unwrap();
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc0 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance0], &mut v_transcript).unwrap();
+        let v_acc1 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance1], &mut v_transcript).unwrap();
+        let v_acc =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc0, v_acc1, &mut v_transcript).unwrap();
+
+        // Every instance value is folded as (i₁ − i₀)⋅α + i₀ with the same challenge α,
+        // so the ratio (i − i₀) / (i₁ − i₀) does not depend on the row.
+        let ratios: Vec<_> = (0..2)
+            .map(|row| {
+                let (i0, i1) = (instance0[row], instance1[row]);
+                (v_acc.instance[0][row] - i0) * (i1 - i0).invert().unwrap()
+            })
+            .collect();
+        assert_eq!(ratios[0], ratios[1]);
+        check_v_and_p_transcripts(v_acc, acc);
+    }
+
+    #[test]
+    fn test_decide_instance_commitments() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+        let (circuit, instance) = mul_chain(2, &[3, 4, 5], false);
+
+        for query_instance in [false, true] {
+            let pk =
+                protostar::ProvingKey::new_with_query_instance(&params, &circuit, query_instance)
+                    .unwrap();
+            let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+            let acc = protostar::prover::create_accumulator(
+                &params,
+                &pk,
+                &circuit,
+                &[&instance],
+                &mut rng,
+                &mut transcript,
+            )
+            .unwrap();
+            assert!(Accumulator::decide(&params, &pk, &acc));
+
+            let mut acc_bad = acc;
+            acc_bad.gate.instance[0].commitment = params.get_g()[0];
+            let result = Accumulator::decide_detailed(&params, &pk, &acc_bad);
+            if query_instance {
+                // Committed instance columns are checked like any other column
+                assert_eq!(
+                    result,
+                    Err(vec![DecideError::Commitment {
+                        column: ColumnLabel::Instance(0)
+                    }])
+                );
+            } else {
+                // Public instance values are known to the verifier and are not committed to
+                assert_eq!(result, Ok(()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_instance_lengths() {
+        let mut rng: OsRng = OsRng;
+        const K: u32 = 6;
+
+        let params = poly::ipa::commitment::ParamsIPA::<pallas::Affine>::new(K);
+
+        let (circuit0, instance0) = mul_chain(2, &[3, 4, 5], false);
+        let (circuit1, mut instance1) = mul_chain(7, &[11, 13, 17], false);
+        // Trailing zeros do not change the instance column
+        instance1.extend([pallas::Scalar::ZERO; 3]);
+        let pk = protostar::ProvingKey::new(&params, &circuit0).unwrap();
+
+        let mut transcript = Blake2bWrite::<_, _, Challenge255<_>>::init(vec![]);
+        let [acc0, acc1] =
+            [(&circuit0, &instance0), (&circuit1, &instance1)].map(|(circuit, instance)| {
+                protostar::prover::create_accumulator(
+                    &params,
+                    &pk,
+                    circuit,
+                    &[instance],
+                    &mut rng,
+                    &mut transcript,
+                )
+                .unwrap()
+            });
+        let acc = Accumulator::fold(&pk, acc0.clone(), acc1, &mut transcript).unwrap();
+        assert!(Accumulator::decide(&params, &pk, &acc));
+
+        let proof: Vec<u8> = transcript.finalize();
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        let v_acc0 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance0], &mut v_transcript).unwrap();
+        let v_acc1 =
+            VerifierAccumulator::new(pk.get_vk(), &[&instance1], &mut v_transcript).unwrap();
+        assert_eq!(v_acc0.instance[0].len(), pk.num_rows);
+        assert_eq!(v_acc1.instance[0].len(), pk.num_rows);
+
+        // An accumulator whose instance column does not cover all rows cannot be folded
+        let mut v_acc_bad = v_acc1.clone();
+        v_acc_bad.instance[0].truncate(2);
+        assert!(matches!(
+            VerifierAccumulator::fold(
+                pk.get_vk(),
+                v_acc0.clone(),
+                v_acc_bad,
+                &mut Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]),
+            ),
+            Err(Error::AccumulatorShapeMismatch)
+        ));
+
+        let v_acc =
+            VerifierAccumulator::fold(pk.get_vk(), v_acc0, v_acc1, &mut v_transcript).unwrap();
+        assert!(check_decider(&params, &pk, &acc, &v_acc));
+        check_v_and_p_transcripts(v_acc, acc);
+
+        // Instance columns cannot be longer than the number of rows
+        let instance_long = vec![pallas::Scalar::ZERO; pk.num_rows + 1];
+        let mut v_transcript = Blake2bRead::<_, _, Challenge255<_>>::init(&proof[..]);
+        assert!(matches!(
+            VerifierAccumulator::new(pk.get_vk(), &[&instance_long], &mut v_transcript),
+            Err(Error::InstanceTooLarge)
+        ));
+    }
 }